@@ -18,13 +18,15 @@ pub async fn get_kernel_parameters_from_mesa(
     xnames,
   )
   .await
-  .unwrap();
+  .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
 
   let mut rmap: HashMap<String, String> = HashMap::new();
   for bp in boot_param_vec {
-    let xname = bp.hosts.first().unwrap().to_string();
-    let params = bp.params;
-    rmap.insert(xname, params);
+    // Boot parameters not linked to any host are of no use here
+    let Some(xname) = bp.hosts.first() else {
+      continue;
+    };
+    rmap.insert(xname.to_string(), bp.params);
   }
 
   Ok(rmap)
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::common::server::ListenAddress;
+
+/// Manta web service
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+  /// Configuration file. Defaults to $MANTA_CONFIG, or
+  /// $XDG_CONFIG_HOME/manta/config.toml
+  #[arg(long, global = true)]
+  pub config: Option<PathBuf>,
+  /// Address to listen on, `ip:port` or `unix:/path/to/socket`. May be
+  /// repeated. Overrides `server.listen`
+  #[arg(long)]
+  pub listen: Vec<ListenAddress>,
+  /// Address serving Prometheus metrics on `/metrics`, `ip:port` or
+  /// `unix:/path/to/socket`. Overrides `server.metrics_listen`
+  #[arg(long)]
+  pub metrics_listen: Option<ListenAddress>,
+  /// Log filter, e.g. `info` or `manta_ws=debug,tower_http=info`. Overrides
+  /// `server.log_level` and $RUST_LOG
+  #[arg(long, global = true)]
+  pub log_level: Option<String>,
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+  /// Loads the configuration, reports the first error found and exits
+  CheckConfig,
+}
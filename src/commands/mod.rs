@@ -1,114 +1,78 @@
-use crate::{
-  common::{self, config::types::MantaConfiguration},
-  manta_backend_dispatcher::StaticBackendDispatcher,
-};
+use crate::common::app_state::{AppState, SelectedSite};
+use crate::common::authorization::authorize_xnames;
+use crate::common::jwks::Claims;
+use crate::error::ApiError;
+use crate::jwt_utils::AuthToken;
+use crate::openapi::{RedfishEndpointArraySchema, SiteHeader};
 use axum::{
   Json,
   extract::Path,
   response::{IntoResponse, Response},
 };
-use hyper::{HeaderMap, StatusCode};
+use hyper::StatusCode;
 use manta_backend_dispatcher::{
   interfaces::hsm::redfish_endpoint::RedfishEndpointTrait,
   types::hsm::inventory::RedfishEndpointArray,
 };
 
-pub async fn get_all_redfish(headers: HeaderMap) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      return (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        format!("ERROR - Site '{}' not found in configuration", site_name),
-      )
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
+/// Redfish endpoints of the site
+#[utoipa::path(
+    get,
+    path = "/redfish",
+    tag = "redfish",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Redfish endpoints", body = RedfishEndpointArraySchema),
+        (status = 304, description = "Not modified since the ETag or date sent by the client"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_all_redfish(
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+) -> Response {
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
   let boot_parameters_rslt =
-    backend.get_all_redfish_endpoints(auth_token).await;
+    backend.get_all_redfish_endpoints(&auth_token).await;
 
   match boot_parameters_rslt {
     Ok(boot_parameters_vec) => {
       return (StatusCode::OK, Json(boot_parameters_vec)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
-#[axum::debug_handler]
+/// Redfish endpoint of a BMC
+#[utoipa::path(
+    get,
+    path = "/redfish/{xname}",
+    tag = "redfish",
+    params(SiteHeader, ("xname" = String, Path, description = "BMC xname")),
+    responses(
+        (status = 200, description = "Redfish endpoints matching the xname", body = RedfishEndpointArraySchema),
+        (status = 304, description = "Not modified since the ETag or date sent by the client"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+#[axum::debug_handler(state = AppState)]
 pub async fn get_redfish(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Path(xname): Path<String>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      eprintln!("ERROR - Site '{}' not found in configuration", site_name);
-      std::process::exit(1);
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
   let boot_parameters_rslt = backend
     .get_redfish_endpoints(
-      auth_token,
+      &auth_token,
       Some(&xname),
       None,
       None,
@@ -123,118 +87,100 @@ pub async fn get_redfish(
     Ok(boot_parameters_vec) => {
       return (StatusCode::OK, Json(boot_parameters_vec)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
-#[axum::debug_handler]
+/// Adds Redfish endpoints. The caller must manage all of them
+#[utoipa::path(
+    post,
+    path = "/redfish",
+    tag = "redfish",
+    params(SiteHeader),
+    request_body = RedfishEndpointArraySchema,
+    responses(
+        (status = 200, description = "Redfish endpoints added"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage some of the xnames"),
+        (status = 409, description = "Redfish endpoint already exists"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+#[axum::debug_handler(state = AppState)]
 pub async fn post_redfish(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
   Json(redfish_endpoint): Json<RedfishEndpointArray>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      eprintln!("ERROR - Site '{}' not found in configuration", site_name);
-      std::process::exit(1);
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
+
+  let xname_vec: Vec<String> = redfish_endpoint
+    .redfish_endpoints
+    .iter()
+    .flatten()
+    .map(|redfish_endpoint| redfish_endpoint.id.clone())
+    .collect();
+
+  if let Err(e) =
+    authorize_xnames(&site, &claims, &auth_token, &xname_vec).await
+  {
+    return e.into_response();
+  }
 
   let boot_parameters_rslt = backend
-    .add_redfish_endpoint(auth_token, &redfish_endpoint)
+    .add_redfish_endpoint(&auth_token, &redfish_endpoint)
     .await;
 
   match boot_parameters_rslt {
     Ok(boot_parameters_vec) => {
       return (StatusCode::OK, Json(boot_parameters_vec)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
-#[axum::debug_handler]
+/// Deletes the Redfish endpoint of a BMC
+#[utoipa::path(
+    delete,
+    path = "/redfish/{xname}",
+    tag = "redfish",
+    params(SiteHeader, ("xname" = String, Path, description = "BMC xname")),
+    responses(
+        (status = 200, description = "Backend response", body = Object),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage the xname"),
+        (status = 404, description = "Redfish endpoint not found"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+#[axum::debug_handler(state = AppState)]
 pub async fn delete_redfish(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
   Path(xname): Path<String>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      eprintln!("ERROR - Site '{}' not found in configuration", site_name);
-      std::process::exit(1);
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
+  let backend = &site.backend;
 
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  if let Err(e) =
+    authorize_xnames(&site, &claims, &auth_token, std::slice::from_ref(&xname))
+      .await
+  {
+    return e.into_response();
+  }
 
   let boot_parameters_rslt =
-    backend.delete_redfish_endpoint(auth_token, &xname).await;
+    backend.delete_redfish_endpoint(&auth_token, &xname).await;
 
   match boot_parameters_rslt {
     Ok(boot_parameters_vec) => {
       return (StatusCode::OK, Json(boot_parameters_vec)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
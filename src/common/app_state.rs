@@ -0,0 +1,310 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock},
+  time::Duration,
+};
+
+use axum::{
+  extract::FromRequestParts,
+  http::{StatusCode, request::Parts},
+  response::Response,
+};
+use manta_backend_dispatcher::error::Error;
+use tokio::signal::unix::{SignalKind, signal};
+
+use crate::{
+  common::config::{
+    self,
+    types::{MantaConfiguration, Site},
+  },
+  common::{
+    audit::AuditSinks,
+    client_identity::ServiceAccountTokens,
+    jwks::JwtVerifier,
+    power_jobs::PowerJobStore,
+    rate_limit::{self, BackendLimiter, RateLimiter},
+    response_cache::ResponseCacheStore,
+    service_health::ServiceHealthMonitor,
+    shutdown::Shutdown,
+  },
+  http_response::error_respond,
+  manta_backend_dispatcher::StaticBackendDispatcher,
+};
+
+/// Header used by clients to pick the site a request targets. Requests
+/// without it go to the default `site` in the configuration file
+pub const SITE_HEADER: &str = "x-manta-site";
+
+/// Everything a handler needs to talk to the backend of a site. Built once per
+/// configuration load so requests do not re-read files from disk
+pub struct SiteContext {
+  pub name: String,
+  pub config: Site,
+  pub shasta_root_cert: Vec<u8>,
+  pub backend: StaticBackendDispatcher,
+  pub jwt_verifier: JwtVerifier,
+  pub service_account_tokens: ServiceAccountTokens,
+  pub backend_limiter: BackendLimiter,
+}
+
+impl SiteContext {
+  fn new(name: &str, site: &Site) -> Result<Self, Error> {
+    let shasta_root_cert = config::get_csm_root_cert_content(
+      &site.root_ca_cert_file,
+    )
+    .map_err(|e| {
+      Error::Message(format!(
+        "Site '{}': could not read root CA file '{}': {}",
+        name, site.root_ca_cert_file, e
+      ))
+    })?;
+
+    let backend = StaticBackendDispatcher::new(
+      &site.backend,
+      &site.shasta_base_url,
+      &shasta_root_cert,
+    )
+    .map_err(|e| Error::Message(format!("Site '{}': {}", name, e)))?;
+
+    let jwt_verifier = JwtVerifier::new(name, site, &shasta_root_cert)?;
+
+    // Calls in flight before a reload are not counted by the new limiter
+    let backend_limiter = BackendLimiter::new(
+      name,
+      site
+        .max_concurrent_backend_calls
+        .unwrap_or(rate_limit::DEFAULT_MAX_CONCURRENT_BACKEND_CALLS),
+      Duration::from_secs(
+        site
+          .backend_queue_timeout_secs
+          .unwrap_or(rate_limit::DEFAULT_BACKEND_QUEUE_TIMEOUT_SECS),
+      ),
+    );
+
+    Ok(SiteContext {
+      name: name.to_string(),
+      config: site.clone(),
+      shasta_root_cert,
+      backend,
+      jwt_verifier,
+      service_account_tokens: ServiceAccountTokens::default(),
+      backend_limiter,
+    })
+  }
+
+  /// HTTP client trusting the root CA of the site and going through its
+  /// SOCKS5 proxy, if any
+  pub fn http_client(
+    &self,
+    timeout: Duration,
+  ) -> Result<reqwest::Client, Error> {
+    let mut http_client_builder = reqwest::Client::builder().timeout(timeout);
+
+    if !self.shasta_root_cert.is_empty() {
+      let certificate = reqwest::Certificate::from_pem(&self.shasta_root_cert)
+        .map_err(|e| Error::Message(format!("Site '{}': {}", self.name, e)))?;
+      http_client_builder =
+        http_client_builder.add_root_certificate(certificate);
+    }
+
+    if let Some(socks5_proxy) = &self.config.socks5_proxy {
+      let proxy = reqwest::Proxy::all(socks5_proxy)
+        .map_err(|e| Error::Message(format!("Site '{}': {}", self.name, e)))?;
+      http_client_builder = http_client_builder.proxy(proxy);
+    }
+
+    http_client_builder
+      .build()
+      .map_err(|e| Error::Message(format!("Site '{}': {}", self.name, e)))
+  }
+}
+
+/// Immutable snapshot of the configuration and the per site backends derived
+/// from it
+pub struct AppContext {
+  pub configuration: MantaConfiguration,
+  pub sites: HashMap<String, Arc<SiteContext>>,
+  pub audit: AuditSinks,
+}
+
+impl AppContext {
+  /// Reads the configuration file and builds a backend dispatcher for every
+  /// site. Fails if any site is misconfigured so a broken configuration never
+  /// replaces a working one. Long lived parts of `previous`, e.g. the Kafka
+  /// producer, are kept if their configuration did not change
+  pub async fn load(previous: Option<&AppContext>) -> Result<Self, Error> {
+    let (configuration, sites) = Self::load_sites().await?;
+
+    Self::new(configuration, sites, previous)
+  }
+
+  /// Reads the configuration file and builds the context of every site, with
+  /// no audit sinks. Enough to validate the configuration
+  pub async fn load_sites()
+  -> Result<(MantaConfiguration, HashMap<String, Arc<SiteContext>>), Error> {
+    let settings = config::get_configuration().await?;
+
+    let configuration: MantaConfiguration =
+      settings.try_deserialize().map_err(|e| {
+        Error::Message(format!("Error processing configuration file: {}", e))
+      })?;
+
+    let mut sites = HashMap::new();
+    for (site_name, site) in &configuration.sites {
+      sites.insert(
+        site_name.clone(),
+        Arc::new(SiteContext::new(site_name, site)?),
+      );
+    }
+
+    if !sites.contains_key(&configuration.site) {
+      return Err(Error::Message(format!(
+        "Default site '{}' not found in configuration",
+        configuration.site
+      )));
+    }
+
+    Ok((configuration, sites))
+  }
+
+  /// Sets up the audit sinks of `configuration`
+  pub fn new(
+    configuration: MantaConfiguration,
+    sites: HashMap<String, Arc<SiteContext>>,
+    previous: Option<&AppContext>,
+  ) -> Result<Self, Error> {
+    let audit =
+      AuditSinks::new(&configuration, previous.map(|previous| &previous.audit))
+        .map_err(|e| {
+          Error::Message(format!("Could not set up auditor: {}", e))
+        })?;
+
+    Ok(AppContext {
+      configuration,
+      sites,
+      audit,
+    })
+  }
+
+  pub fn site(&self, site_name: &str) -> Option<Arc<SiteContext>> {
+    self.sites.get(site_name).cloned()
+  }
+}
+
+/// Shared application state handed to every handler through axum `State`
+#[derive(Clone)]
+pub struct AppState {
+  context: Arc<RwLock<Arc<AppContext>>>,
+  pub shutdown: Shutdown,
+  pub service_health: Arc<ServiceHealthMonitor>,
+  pub rate_limiter: Arc<RateLimiter>,
+  pub response_cache: Arc<ResponseCacheStore>,
+  pub power_jobs: Arc<PowerJobStore>,
+}
+
+impl AppState {
+  pub fn new(context: AppContext) -> Self {
+    let power_jobs = PowerJobStore::load(
+      &context.configuration.power_jobs.clone().unwrap_or_default(),
+    );
+
+    AppState {
+      context: Arc::new(RwLock::new(Arc::new(context))),
+      shutdown: Shutdown::default(),
+      service_health: Arc::new(ServiceHealthMonitor::default()),
+      rate_limiter: Arc::new(RateLimiter::default()),
+      response_cache: Arc::new(ResponseCacheStore::default()),
+      power_jobs: Arc::new(power_jobs),
+    }
+  }
+
+  /// Returns the current configuration snapshot. Requests keep using the
+  /// snapshot they started with even if a reload happens in the meantime
+  pub fn context(&self) -> Arc<AppContext> {
+    self
+      .context
+      .read()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      .clone()
+  }
+
+  /// Loads the configuration again and swaps it in atomically. On error the
+  /// previous configuration stays in place
+  pub async fn reload(&self) -> Result<(), Error> {
+    let new_context = AppContext::load(Some(&self.context())).await?;
+
+    *self
+      .context
+      .write()
+      .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(new_context);
+
+    Ok(())
+  }
+}
+
+/// Site a request targets, resolved from the `X-Manta-Site` header against
+/// the sites in the current configuration
+pub struct SelectedSite(pub Arc<SiteContext>);
+
+impl FromRequestParts<AppState> for SelectedSite {
+  type Rejection = Response;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &AppState,
+  ) -> Result<Self, Self::Rejection> {
+    let context = state.context();
+
+    let site_name = match parts.headers.get(SITE_HEADER) {
+      Some(header_value) => match header_value.to_str() {
+        Ok(site_name) => site_name.trim().to_string(),
+        Err(_) => {
+          return Err(error_respond(
+            StatusCode::BAD_REQUEST,
+            format!("Header '{}' is not valid", SITE_HEADER),
+          ));
+        }
+      },
+      None => context.configuration.site.clone(),
+    };
+
+    match context.site(&site_name) {
+      Some(site) => Ok(SelectedSite(site)),
+      None => Err(error_respond(
+        StatusCode::NOT_FOUND,
+        format!("Site '{}' not found", site_name),
+      )),
+    }
+  }
+}
+
+/// Reloads the configuration every time the process receives SIGHUP
+pub async fn reload_on_sighup(state: AppState) {
+  let mut sighup = match signal(SignalKind::hangup()) {
+    Ok(sighup) => sighup,
+    Err(e) => {
+      tracing::error!("Could not install SIGHUP handler: {}", e);
+      return;
+    }
+  };
+
+  while sighup.recv().await.is_some() {
+    tracing::info!("SIGHUP received, reloading configuration");
+
+    match state.reload().await {
+      Ok(()) => {
+        metrics::counter!("manta_config_reloads_total", "result" => "success")
+          .increment(1);
+        tracing::info!("Configuration reloaded")
+      }
+      Err(e) => {
+        metrics::counter!("manta_config_reloads_total", "result" => "failure")
+          .increment(1);
+        tracing::error!(
+          "Configuration reload rejected, keeping previous configuration. Reason: {}",
+          e
+        )
+      }
+    }
+  }
+}
@@ -1,14 +1,436 @@
+use std::{path::PathBuf, sync::Arc, time::Instant};
+
 use anyhow::Result;
+use axum::{
+  body::{Body, to_bytes},
+  extract::{MatchedPath, Query, Request, State},
+  http::Method,
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use super::{
+  app_state::{AppState, SITE_HEADER},
+  audit_file::{AuditFile, AuditFileWriter},
+  config::{self, types::MantaConfiguration},
+  jwks::Claims,
+  kafka::{Kafka, KafkaProducer},
+  syslog::{Syslog, SyslogSender},
+};
+use crate::error::ApiError;
+
+/// Request bodies of audited endpoints are small JSON documents
+const MAX_AUDITED_BODY_BYTES: usize = 1024 * 1024;
+
+/// Request fields whose name contains any of these, case insensitive, are
+/// not audited
+const SECRET_KEYS: [&str; 6] = [
+  "password",
+  "passwd",
+  "secret",
+  "token",
+  "credential",
+  "private_key",
+];
 
-use super::kafka::Kafka;
+const REDACTED: &str = "[REDACTED]";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Auditor {
-  pub kafka: Kafka,
-  // pub syslog: Option<Syslog>,
+  pub file: Option<AuditFile>,
+  pub kafka: Option<Kafka>,
+  pub syslog: Option<Syslog>,
 }
 
 pub trait Audit {
-  async fn produce_message(&self, data: &[u8]) -> Result<()>;
+  async fn produce_message(&self, key: Option<&str>, data: &[u8])
+  -> Result<()>;
+}
+
+/// Destinations audit events are sent to, built from the `[auditor]` section
+#[derive(Default)]
+pub struct AuditSinks {
+  pub file: Option<Arc<AuditFileWriter>>,
+  pub kafka: Option<Arc<KafkaProducer>>,
+  pub syslog: Option<Arc<SyslogSender>>,
+}
+
+impl AuditSinks {
+  /// Sinks of `previous` are reused when their configuration is unchanged,
+  /// so a reload does not start a second writer on the same audit file or a
+  /// second Kafka producer on the same spool
+  pub fn new(
+    configuration: &MantaConfiguration,
+    previous: Option<&AuditSinks>,
+  ) -> Result<Self> {
+    // The local file is always written, so sites without Kafka or syslog
+    // still keep an audit trail
+    let audit_file_path = if configuration.audit_file.is_empty() {
+      config::get_default_manta_audit_file_path()
+    } else {
+      PathBuf::from(&configuration.audit_file)
+    };
+
+    let audit_file = configuration
+      .auditor
+      .as_ref()
+      .and_then(|auditor| auditor.file.clone())
+      .unwrap_or_default();
+
+    let file = match previous.and_then(|previous| previous.file.as_ref()) {
+      Some(file) if file.writes(&audit_file_path, &audit_file) => file.clone(),
+      _ => AuditFileWriter::new(&audit_file_path, &audit_file)?,
+    };
+
+    let Some(auditor) = &configuration.auditor else {
+      return Ok(AuditSinks {
+        file: Some(file),
+        ..Default::default()
+      });
+    };
+
+    Ok(AuditSinks {
+      file: Some(file),
+      kafka: auditor
+        .kafka
+        .as_ref()
+        .map(|kafka| {
+          match previous.and_then(|previous| previous.kafka.as_ref()) {
+            Some(producer) if producer.config() == kafka => {
+              Ok(producer.clone())
+            }
+            _ => KafkaProducer::new(kafka),
+          }
+        })
+        .transpose()?,
+      syslog: auditor.syslog.as_ref().map(SyslogSender::new).transpose()?,
+    })
+  }
+
+  pub async fn send(&self, event: &AuditEvent) {
+    tracing::info!(
+      target: "audit",
+      "user={} site={} action={} xnames={:?} status={} duration_ms={}",
+      event.user,
+      event.site,
+      event.action,
+      event.xnames,
+      event.status,
+      event.duration_ms
+    );
+
+    // Sinks are independent, a slow one must not delay the others
+    tokio::join!(
+      async {
+        if let Some(file) = &self.file {
+          file.send_event(event).await;
+        }
+      },
+      async {
+        if let Some(kafka) = &self.kafka {
+          kafka.send_event(event).await;
+        }
+      },
+      async {
+        if let Some(syslog) = &self.syslog {
+          syslog.send_event(event).await;
+        }
+      }
+    );
+  }
+}
+
+impl AuditSinks {
+  /// Writes out what the sinks still buffer, called once on shutdown
+  pub async fn flush(&self) {
+    if let Some(file) = &self.file {
+      file.flush().await;
+    }
+
+    if let Some(kafka) = &self.kafka {
+      kafka.flush().await;
+    }
+  }
+}
+
+/// Xnames a handler resolved the request to, e.g. the members of a group.
+/// Set as a response extension, replaces the ones `audit_request` found in
+/// the request
+#[derive(Clone, Debug)]
+pub struct AuditedXnames(pub Vec<String>);
+
+/// One record per mutating request
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditEvent {
+  pub timestamp: String,
+  pub user: String,
+  pub site: String,
+  pub action: &'static str,
+  pub method: String,
+  pub path: String,
+  pub xnames: Vec<String>,
+  pub params: Value,
+  pub status: u16,
+  pub duration_ms: u64,
+}
+
+/// Where the xnames an action operates on are found in the request
+enum Target {
+  PathParam(&'static str),
+  /// Given as a list, `hostlist` and `group` are resolved by the handler,
+  /// see `AuditedXnames`
+  BodyXnames,
+  BodyHosts,
+  BodyRedfishEndpoints,
+  QueryIds,
+  /// Action does not name xnames, e.g. it targets a job
+  Nothing,
+}
+
+/// Audited endpoints, identified by method and route
+fn get_action(method: &Method, route: &str) -> Option<(&'static str, Target)> {
+  let action = match (method.as_str(), route) {
+    ("POST", "/power/on") => ("power.on", Target::BodyXnames),
+    ("POST", "/power/off") => ("power.off", Target::BodyXnames),
+    ("POST", "/power/reset") => ("power.reset", Target::BodyXnames),
+    ("DELETE", "/jobs/{id}") => ("power.cancel", Target::Nothing),
+    ("POST", "/bss/boot-parameters") => ("bss.post", Target::BodyHosts),
+    ("DELETE", "/bss/boot-parameters") => ("bss.delete", Target::BodyHosts),
+    ("POST", "/redfish") => ("redfish.post", Target::BodyRedfishEndpoints),
+    ("DELETE", "/redfish/{xname}") => {
+      ("redfish.delete", Target::PathParam("xname"))
+    }
+    ("PUT", "/node-migration/target/{target}/parent/{parent}") => {
+      ("node.migration", Target::QueryIds)
+    }
+    ("GET", "/console/{xname}") => {
+      ("console.attach", Target::PathParam("xname"))
+    }
+    _ => return None,
+  };
+
+  Some(action)
+}
+
+fn get_xnames(
+  target: &Target,
+  path_params: &Map<String, Value>,
+  query_params: &Map<String, Value>,
+  body: &Value,
+) -> Vec<String> {
+  let as_string_vec = |value: Option<&Value>| -> Vec<String> {
+    value
+      .and_then(Value::as_array)
+      .into_iter()
+      .flatten()
+      .filter_map(Value::as_str)
+      .map(str::to_string)
+      .collect()
+  };
+
+  match target {
+    Target::PathParam(name) => path_params
+      .get(*name)
+      .and_then(Value::as_str)
+      .map(|xname| vec![xname.to_string()])
+      .unwrap_or_default(),
+    Target::BodyXnames => as_string_vec(body.get("xnames")),
+    Target::BodyHosts => as_string_vec(body.get("hosts")),
+    Target::BodyRedfishEndpoints => body
+      .get("RedfishEndpoints")
+      .and_then(Value::as_array)
+      .into_iter()
+      .flatten()
+      .filter_map(|redfish_endpoint| redfish_endpoint["ID"].as_str())
+      .map(str::to_string)
+      .collect(),
+    Target::QueryIds => query_params
+      .get("ids")
+      .and_then(Value::as_str)
+      .map(|ids| {
+        ids
+          .split(',')
+          .map(|xname| xname.trim().to_string())
+          .filter(|xname| !xname.is_empty())
+          .collect()
+      })
+      .unwrap_or_default(),
+    Target::Nothing => Vec::new(),
+  }
+}
+
+/// Replaces the value of credential-like fields, e.g. the BMC `Password` of
+/// Redfish endpoints, so secrets never reach the audit sinks
+fn redact_secrets(value: &mut Value) {
+  match value {
+    Value::Object(map) => {
+      for (key, value) in map.iter_mut() {
+        let key = key.to_lowercase();
+
+        if SECRET_KEYS
+          .iter()
+          .any(|secret_key| key.contains(secret_key))
+        {
+          *value = Value::String(REDACTED.to_string());
+        } else {
+          redact_secrets(value);
+        }
+      }
+    }
+    Value::Array(value_vec) => value_vec.iter_mut().for_each(redact_secrets),
+    _ => {}
+  }
+}
+
+/// Middleware sending an `AuditEvent` to the configured auditor for every
+/// mutating request. Must run after the token has been verified so the user
+/// is known
+pub async fn audit_request(
+  State(state): State<AppState>,
+  matched_path: Option<MatchedPath>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let Some((action, target)) = matched_path.as_ref().and_then(|matched_path| {
+    get_action(request.method(), matched_path.as_str())
+  }) else {
+    return next.run(request).await;
+  };
+
+  let start = Instant::now();
+
+  let (parts, body) = request.into_parts();
+
+  let body_bytes = match to_bytes(body, MAX_AUDITED_BODY_BYTES).await {
+    Ok(body_bytes) => body_bytes,
+    Err(e) => {
+      return ApiError::bad_request(format!("Could not read body: {}", e))
+        .into_response();
+    }
+  };
+
+  let user = parts
+    .extensions
+    .get::<Claims>()
+    .map(|claims| claims.username().to_string())
+    .unwrap_or_else(|| "unknown".to_string());
+
+  let context = state.context();
+  let site = parts
+    .headers
+    .get(SITE_HEADER)
+    .and_then(|site| site.to_str().ok())
+    .map(|site| site.trim().to_string())
+    .unwrap_or_else(|| context.configuration.site.clone());
+
+  let path_params: Map<String, Value> = matched_path
+    .as_ref()
+    .map(|matched_path| {
+      get_path_params(matched_path.as_str(), parts.uri.path())
+    })
+    .unwrap_or_default();
+
+  let query_params: Map<String, Value> =
+    Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+      .map(|Query(query_params)| {
+        query_params
+          .into_iter()
+          .map(|(key, value)| (key, Value::String(value)))
+          .collect()
+      })
+      .unwrap_or_default();
+
+  let body: Value = serde_json::from_slice(&body_bytes).unwrap_or(Value::Null);
+
+  let xnames = get_xnames(&target, &path_params, &query_params, &body);
+
+  let mut params = json!({
+    "path": path_params,
+    "query": query_params,
+    "body": body,
+  });
+  redact_secrets(&mut params);
+
+  let mut event = AuditEvent {
+    timestamp: chrono::offset::Utc::now()
+      .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+    user,
+    site,
+    action,
+    method: parts.method.to_string(),
+    path: parts.uri.path().to_string(),
+    xnames,
+    params,
+    status: 0,
+    duration_ms: 0,
+  };
+
+  let response = next
+    .run(Request::from_parts(parts, Body::from(body_bytes)))
+    .await;
+
+  if let Some(AuditedXnames(xname_vec)) = response.extensions().get() {
+    event.xnames = xname_vec.clone();
+  }
+
+  event.status = response.status().as_u16();
+  event.duration_ms = start.elapsed().as_millis() as u64;
+
+  // Do not make the client wait for the audit backend. Tracked so events are
+  // not lost on shutdown
+  state
+    .shutdown
+    .spawn(async move { context.audit.send(&event).await });
+
+  response
+}
+
+/// Matches the route template against the actual path to get `{param}`
+/// values
+fn get_path_params(route: &str, path: &str) -> Map<String, Value> {
+  route
+    .split('/')
+    .zip(path.split('/'))
+    .filter_map(|(route_segment, path_segment)| {
+      route_segment
+        .strip_prefix('{')
+        .and_then(|name| name.strip_suffix('}'))
+        .map(|name| (name.to_string(), Value::String(path_segment.to_string())))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn redact_secrets_in_nested_fields() {
+    let mut params = json!({
+      "query": { "access_token": "abc" },
+      "body": {
+        "RedfishEndpoints": [
+          { "ID": "x1000c0s0b0", "User": "root", "Password": "hunter2" }
+        ],
+        "ClientSecret": { "value": "s3cr3t" }
+      }
+    });
+
+    redact_secrets(&mut params);
+
+    assert_eq!(
+      params,
+      json!({
+        "query": { "access_token": REDACTED },
+        "body": {
+          "RedfishEndpoints": [
+            { "ID": "x1000c0s0b0", "User": "root", "Password": REDACTED }
+          ],
+          "ClientSecret": REDACTED
+        }
+      })
+    );
+  }
 }
@@ -0,0 +1,406 @@
+use std::{
+  collections::VecDeque,
+  io::{BufRead, BufReader},
+  os::unix::fs::{DirBuilderExt, PermissionsExt},
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+  fs::File,
+  io::AsyncWriteExt,
+  sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+  },
+};
+use utoipa::ToSchema;
+
+use super::audit::{Audit, AuditEvent};
+
+/// Events waiting to be written. Once full, new events are dropped rather
+/// than making requests wait for the disk
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Rotation of the local audit file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditFile {
+  /// Size after which the file is rotated, in MiB
+  #[serde(default = "default_max_size_mb")]
+  pub max_size_mb: u64,
+  /// Age after which the file is rotated, in hours
+  #[serde(default = "default_max_age_hours")]
+  pub max_age_hours: u64,
+  /// Number of rotated files kept, older ones are deleted
+  #[serde(default = "default_max_files")]
+  pub max_files: usize,
+}
+
+fn default_max_size_mb() -> u64 {
+  100
+}
+
+fn default_max_age_hours() -> u64 {
+  24
+}
+
+fn default_max_files() -> usize {
+  7
+}
+
+impl Default for AuditFile {
+  fn default() -> Self {
+    AuditFile {
+      max_size_mb: default_max_size_mb(),
+      max_age_hours: default_max_age_hours(),
+      max_files: default_max_files(),
+    }
+  }
+}
+
+/// Appends audit events as JSON lines to the `audit_file`. Writes happen in a
+/// background task so requests never wait for the disk
+pub struct AuditFileWriter {
+  path: PathBuf,
+  config: AuditFile,
+  sender: mpsc::Sender<FileMessage>,
+}
+
+/// Audit event as read back from the audit file
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct AuditRecord {
+  pub timestamp: String,
+  pub user: String,
+  pub site: String,
+  pub action: String,
+  pub method: String,
+  pub path: String,
+  pub xnames: Vec<String>,
+  pub params: Value,
+  pub status: u16,
+  pub duration_ms: u64,
+}
+
+impl AuditFileWriter {
+  pub fn new(path: &Path, config: &AuditFile) -> Result<Arc<Self>> {
+    if let Some(parent) = path
+      .parent()
+      .filter(|parent| !parent.as_os_str().is_empty())
+    {
+      std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(parent)
+        .map_err(|e| {
+          anyhow::anyhow!(
+            "Could not create audit file directory '{}': {}",
+            parent.display(),
+            e
+          )
+        })?;
+    }
+
+    let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+
+    let rotating_file = RotatingFile {
+      path: path.to_path_buf(),
+      max_size: config.max_size_mb.saturating_mul(1024 * 1024),
+      max_age: Duration::from_secs(config.max_age_hours.saturating_mul(3600)),
+      max_files: config.max_files,
+      file: None,
+      size: 0,
+      opened_at: SystemTime::now(),
+    };
+
+    // Stops once a reload changed the audit file and this writer is dropped
+    tokio::spawn(write_lines(rotating_file, receiver));
+
+    Ok(Arc::new(AuditFileWriter {
+      path: path.to_path_buf(),
+      config: config.clone(),
+      sender,
+    }))
+  }
+
+  /// Whether this writer already writes `path` with `config`
+  pub fn writes(&self, path: &Path, config: &AuditFile) -> bool {
+    self.path == path && &self.config == config
+  }
+
+  pub async fn send_event(&self, event: &AuditEvent) {
+    let line = match serde_json::to_vec(event) {
+      Ok(line) => line,
+      Err(e) => {
+        tracing::error!("Could not serialize audit event: {}", e);
+        return;
+      }
+    };
+
+    if let Err(e) = self.produce_message(None, &line).await {
+      tracing::error!("Could not write audit event to file: {}", e);
+      metrics::counter!("manta_audit_file_failures_total").increment(1);
+    }
+  }
+
+  /// Waits for the events queued so far to be written
+  pub async fn flush(&self) {
+    let (flushed_sender, flushed_receiver) = oneshot::channel();
+
+    // Waits for room in the queue, unlike events
+    if self
+      .sender
+      .send(FileMessage::Flush(flushed_sender))
+      .await
+      .is_ok()
+    {
+      let _ = flushed_receiver.await;
+    }
+  }
+
+  /// Number of records in the audit file and its rotated files accepted by
+  /// `filter`, and the newest `keep` of them, newest first. Lines that can
+  /// not be parsed are skipped
+  pub async fn read_records(
+    &self,
+    filter: impl Fn(&AuditRecord) -> bool + Send + 'static,
+    keep: usize,
+  ) -> Result<(usize, Vec<AuditRecord>)> {
+    // Oldest rotated file first
+    let mut path_vec: Vec<PathBuf> = (1..=self.config.max_files)
+      .rev()
+      .map(|index| rotated_path(&self.path, index))
+      .collect();
+    path_vec.push(self.path.clone());
+
+    tokio::task::spawn_blocking(move || {
+      let mut total = 0;
+      // Only the newest matches are held in memory
+      let mut record_deque = VecDeque::with_capacity(keep.min(1024));
+
+      for path in path_vec {
+        let file = match std::fs::File::open(&path) {
+          Ok(file) => file,
+          Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+          Err(e) => return Err(e.into()),
+        };
+
+        for line in BufReader::new(file).lines() {
+          let Ok(record) = serde_json::from_str::<AuditRecord>(&line?) else {
+            continue;
+          };
+
+          if !filter(&record) {
+            continue;
+          }
+
+          total += 1;
+
+          if keep == 0 {
+            continue;
+          }
+
+          if record_deque.len() == keep {
+            record_deque.pop_front();
+          }
+          record_deque.push_back(record);
+        }
+      }
+
+      Ok((total, record_deque.into_iter().rev().collect()))
+    })
+    .await?
+  }
+}
+
+impl Audit for AuditFileWriter {
+  async fn produce_message(
+    &self,
+    _key: Option<&str>,
+    data: &[u8],
+  ) -> Result<()> {
+    let mut line = data.to_vec();
+    line.push(b'\n');
+
+    self
+      .sender
+      .try_send(FileMessage::Line(line))
+      .map_err(|e| match e {
+        TrySendError::Full(_) => anyhow::anyhow!("audit file queue is full"),
+        TrySendError::Closed(_) => anyhow::anyhow!("audit file writer stopped"),
+      })
+  }
+}
+
+enum FileMessage {
+  Line(Vec<u8>),
+  /// Answered once every line queued before it has been written
+  Flush(oneshot::Sender<()>),
+}
+
+struct RotatingFile {
+  path: PathBuf,
+  max_size: u64,
+  max_age: Duration,
+  max_files: usize,
+  file: Option<File>,
+  size: u64,
+  opened_at: SystemTime,
+}
+
+impl RotatingFile {
+  async fn open(&mut self) -> Result<()> {
+    let file = tokio::fs::OpenOptions::new()
+      .append(true)
+      .create(true)
+      .mode(0o600)
+      .open(&self.path)
+      .await?;
+
+    // 'mode' only applies to new files
+    file
+      .set_permissions(std::fs::Permissions::from_mode(0o600))
+      .await?;
+
+    let metadata = file.metadata().await?;
+    self.size = metadata.len();
+    self.opened_at = metadata
+      .created()
+      .or_else(|_| metadata.modified())
+      .unwrap_or_else(|_| SystemTime::now());
+    self.file = Some(file);
+
+    Ok(())
+  }
+
+  fn needs_rotation(&self, len: u64) -> bool {
+    let age = self.opened_at.elapsed().unwrap_or_default();
+
+    self.size > 0 && (self.size + len > self.max_size || age >= self.max_age)
+  }
+
+  /// `audit_file` becomes `audit_file.1`, `audit_file.1` becomes
+  /// `audit_file.2` and so on, up to `max_files`
+  async fn rotate(&mut self) -> Result<()> {
+    if let Some(mut file) = self.file.take() {
+      file.flush().await?;
+    }
+
+    let rotated_path = |index: usize| rotated_path(&self.path, index);
+
+    if self.max_files == 0 {
+      remove_if_exists(&self.path).await?;
+    } else {
+      remove_if_exists(&rotated_path(self.max_files)).await?;
+
+      for index in (1..self.max_files).rev() {
+        rename_if_exists(&rotated_path(index), &rotated_path(index + 1))
+          .await?;
+      }
+
+      rename_if_exists(&self.path, &rotated_path(1)).await?;
+    }
+
+    self.open().await?;
+    // A rotated path may keep its creation time on some filesystems
+    self.opened_at = SystemTime::now();
+
+    Ok(())
+  }
+
+  async fn write(&mut self, line: &[u8]) -> Result<()> {
+    if self.file.is_none() {
+      self.open().await?;
+    }
+
+    if self.needs_rotation(line.len() as u64) {
+      self.rotate().await?;
+    }
+
+    let Some(file) = self.file.as_mut() else {
+      return Err(anyhow::anyhow!("audit file not open"));
+    };
+
+    if let Err(e) = file.write_all(line).await {
+      // Open the file again on the next write
+      self.file = None;
+      return Err(e.into());
+    }
+
+    self.size += line.len() as u64;
+
+    Ok(())
+  }
+
+  async fn flush(&mut self) -> Result<()> {
+    if let Some(file) = self.file.as_mut() {
+      file.flush().await?;
+    }
+
+    Ok(())
+  }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+  let mut path = path.to_path_buf().into_os_string();
+  path.push(format!(".{}", index));
+  PathBuf::from(path)
+}
+
+async fn remove_if_exists(path: &Path) -> Result<()> {
+  match tokio::fs::remove_file(path).await {
+    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+    _ => Ok(()),
+  }
+}
+
+async fn rename_if_exists(from: &Path, to: &Path) -> Result<()> {
+  match tokio::fs::rename(from, to).await {
+    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+    _ => Ok(()),
+  }
+}
+
+async fn write_lines(
+  mut rotating_file: RotatingFile,
+  mut receiver: mpsc::Receiver<FileMessage>,
+) {
+  let mut message_vec = Vec::new();
+  let mut flushed_vec = Vec::new();
+
+  while receiver.recv_many(&mut message_vec, 100).await > 0 {
+    for message in message_vec.drain(..) {
+      let line = match message {
+        FileMessage::Line(line) => line,
+        FileMessage::Flush(flushed) => {
+          flushed_vec.push(flushed);
+          continue;
+        }
+      };
+
+      if let Err(e) = rotating_file.write(&line).await {
+        tracing::error!(
+          "Could not write audit event to '{}': {}",
+          rotating_file.path.display(),
+          e
+        );
+        metrics::counter!("manta_audit_file_failures_total").increment(1);
+      }
+    }
+
+    if let Err(e) = rotating_file.flush().await {
+      tracing::error!(
+        "Could not flush audit file '{}': {}",
+        rotating_file.path.display(),
+        e
+      );
+    }
+
+    for flushed in flushed_vec.drain(..) {
+      let _ = flushed.send(());
+    }
+  }
+}
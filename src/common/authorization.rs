@@ -0,0 +1,335 @@
+use manta_backend_dispatcher::{
+  interfaces::hsm::{component::ComponentTrait, group::GroupTrait},
+  types::bss::BootParameters,
+};
+
+use crate::{
+  common::{app_state::SiteContext, jwks::Claims, power_jobs::PowerJob},
+  error::ApiError,
+};
+
+/// What the caller of a mutating request is allowed to touch
+enum Access {
+  /// Any node in the site
+  Admin,
+  /// Nothing
+  ReadOnly,
+  /// Nodes in the HSM groups returned by `get_group_name_available`
+  Groups,
+}
+
+/// Keycloak group paths look like '/group/subgroup'
+fn has_any_role(claims: &Claims, role_vec: &[String]) -> bool {
+  claims
+    .realm_access
+    .roles
+    .iter()
+    .chain(claims.groups.iter())
+    .map(|role| role.trim_start_matches('/'))
+    .any(|role| role_vec.iter().any(|expected| expected == role))
+}
+
+fn get_access(site: &SiteContext, claims: &Claims) -> Access {
+  let authorization = site.config.authorization.clone().unwrap_or_default();
+
+  if has_any_role(claims, &authorization.admin_roles) {
+    Access::Admin
+  } else if has_any_role(claims, &authorization.read_only_roles) {
+    Access::ReadOnly
+  } else {
+    Access::Groups
+  }
+}
+
+fn read_only(claims: &Claims) -> ApiError {
+  ApiError::forbidden(format!(
+    "User '{}' has read-only access",
+    claims.username()
+  ))
+}
+
+/// `xname` is a component containing `node`, e.g. the BMC `x1000c0s0b0`
+/// contains the node `x1000c0s0b0n0`
+fn contains(xname: &str, node: &str) -> bool {
+  node.len() > xname.len()
+    && node.starts_with(xname)
+    && node[xname.len()..].starts_with(|c: char| c.is_alphabetic())
+}
+
+/// Xnames in `xname_vec` the caller may not operate on. Nodes must be in
+/// `member_vec`. Other components must contain at least one node of the
+/// site, listed in `node_vec`, and all the nodes they contain must be in
+/// `member_vec`
+fn get_denied_xnames<'a>(
+  xname_vec: &'a [String],
+  member_vec: &[String],
+  node_vec: &[String],
+) -> Vec<&'a str> {
+  xname_vec
+    .iter()
+    .map(|xname| xname.trim())
+    .filter(|xname| {
+      if xname.is_empty() {
+        return true;
+      }
+
+      if member_vec.iter().any(|member| member == xname) {
+        return false;
+      }
+
+      let mut contained_node_vec = node_vec
+        .iter()
+        .filter(|node| contains(xname, node))
+        .peekable();
+
+      contained_node_vec.peek().is_none()
+        || !contained_node_vec
+          .all(|node| member_vec.iter().any(|member| member == node))
+    })
+    .collect()
+}
+
+/// Checks the caller may operate on all the xnames. Nodes must be members of
+/// an HSM group available to the caller. Other components (BMCs, chassis,
+/// ...) are only allowed if the caller manages every node they contain
+pub async fn authorize_xnames(
+  site: &SiteContext,
+  claims: &Claims,
+  auth_token: &str,
+  xname_vec: &[String],
+) -> Result<(), ApiError> {
+  match get_access(site, claims) {
+    Access::Admin => return Ok(()),
+    Access::ReadOnly => return Err(read_only(claims)),
+    Access::Groups => {}
+  }
+
+  let group_name_available_vec =
+    site.backend.get_group_name_available(auth_token).await?;
+
+  let member_vec = site
+    .backend
+    .get_member_vec_from_group_name_vec(
+      auth_token,
+      &group_name_available_vec
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>(),
+    )
+    .await?;
+
+  // Every node of the site is only needed to check parent components
+  let node_vec: Vec<String> = if xname_vec
+    .iter()
+    .all(|xname| member_vec.contains(&xname.trim().to_string()))
+  {
+    Vec::new()
+  } else {
+    site
+      .backend
+      .get_all_nodes(auth_token, None)
+      .await?
+      .components
+      .into_iter()
+      .flatten()
+      .filter_map(|component| component.id)
+      .collect()
+  };
+
+  let denied_xname_vec = get_denied_xnames(xname_vec, &member_vec, &node_vec);
+
+  if denied_xname_vec.is_empty() {
+    Ok(())
+  } else {
+    Err(ApiError::forbidden(format!(
+      "User '{}' is not allowed to operate on: {}",
+      claims.username(),
+      denied_xname_vec.join(", ")
+    )))
+  }
+}
+
+/// Checks the caller may operate on the nodes of BSS boot parameters. MACs
+/// and NIDs also select nodes in BSS and are only accepted from admins
+pub async fn authorize_boot_parameters(
+  site: &SiteContext,
+  claims: &Claims,
+  auth_token: &str,
+  boot_parameters: &BootParameters,
+) -> Result<(), ApiError> {
+  let has_macs = boot_parameters
+    .macs
+    .as_ref()
+    .is_some_and(|mac_vec| !mac_vec.is_empty());
+  let has_nids = boot_parameters
+    .nids
+    .as_ref()
+    .is_some_and(|nid_vec| !nid_vec.is_empty());
+
+  if boot_parameters.hosts.is_empty() && !has_macs && !has_nids {
+    return Err(ApiError::bad_request(
+      "One of 'hosts', 'macs' and 'nids' must be set",
+    ));
+  }
+
+  match get_access(site, claims) {
+    Access::Admin => return Ok(()),
+    Access::ReadOnly => return Err(read_only(claims)),
+    Access::Groups => {}
+  }
+
+  if has_macs || has_nids {
+    return Err(ApiError::forbidden(format!(
+      "User '{}' may only select nodes by 'hosts'",
+      claims.username()
+    )));
+  }
+
+  authorize_xnames(site, claims, auth_token, &boot_parameters.hosts).await
+}
+
+/// Checks the caller may change the membership of an HSM group
+pub async fn authorize_group(
+  site: &SiteContext,
+  claims: &Claims,
+  auth_token: &str,
+  group_name: &str,
+) -> Result<(), ApiError> {
+  match get_access(site, claims) {
+    Access::Admin => return Ok(()),
+    Access::ReadOnly => return Err(read_only(claims)),
+    Access::Groups => {}
+  }
+
+  let group_name_available_vec =
+    site.backend.get_group_name_available(auth_token).await?;
+
+  if group_name_available_vec
+    .iter()
+    .any(|group_name_available| group_name_available == group_name)
+  {
+    Ok(())
+  } else {
+    Err(ApiError::forbidden(format!(
+      "User '{}' is not allowed to manage HSM group '{}'",
+      claims.username(),
+      group_name
+    )))
+  }
+}
+
+/// Checks the caller holds one of the `auditor_roles` of the site
+pub fn authorize_audit(
+  site: &SiteContext,
+  claims: &Claims,
+) -> Result<(), ApiError> {
+  let authorization = site.config.authorization.clone().unwrap_or_default();
+
+  if has_any_role(claims, &authorization.auditor_roles) {
+    Ok(())
+  } else {
+    Err(ApiError::forbidden(format!(
+      "User '{}' is not allowed to read the audit log",
+      claims.username()
+    )))
+  }
+}
+
+/// Checks the caller started the power job or is an admin of the site
+pub fn authorize_job(
+  site: &SiteContext,
+  claims: &Claims,
+  job: &PowerJob,
+) -> Result<(), ApiError> {
+  if job.user == claims.username()
+    || matches!(get_access(site, claims), Access::Admin)
+  {
+    Ok(())
+  } else {
+    Err(ApiError::forbidden(format!(
+      "User '{}' is not allowed to access job '{}'",
+      claims.username(),
+      job.id
+    )))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn to_vec(xname_vec: &[&str]) -> Vec<String> {
+    xname_vec.iter().map(|xname| xname.to_string()).collect()
+  }
+
+  #[test]
+  fn contains_only_descendants() {
+    assert!(contains("x1000c0s0b0", "x1000c0s0b0n0"));
+    assert!(contains("x1000", "x1000c0s0b0n0"));
+    assert!(!contains("x1000c0s0b0n0", "x1000c0s0b0n0"));
+    assert!(!contains("x1000c0s0b0n1", "x1000c0s0b0n10"));
+    assert!(!contains("x100", "x1000c0s0b0n0"));
+  }
+
+  #[test]
+  fn managed_nodes_are_allowed() {
+    let member_vec = to_vec(&["x1000c0s0b0n0", "x1000c0s0b0n1"]);
+
+    assert!(
+      get_denied_xnames(&to_vec(&["x1000c0s0b0n0"]), &member_vec, &[])
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn other_nodes_are_denied() {
+    let member_vec = to_vec(&["x1000c0s0b0n0"]);
+    let node_vec = to_vec(&["x1000c0s0b0n0", "x1000c0s0b0n10"]);
+
+    assert_eq!(
+      get_denied_xnames(
+        &to_vec(&["x1000c0s0b0n1", "x1000c0s0b0n10", " "]),
+        &member_vec,
+        &node_vec
+      ),
+      vec!["x1000c0s0b0n1", "x1000c0s0b0n10", ""]
+    );
+  }
+
+  #[test]
+  fn parents_of_unmanaged_nodes_are_denied() {
+    let member_vec = to_vec(&["x1000c0s0b0n0"]);
+    let node_vec = to_vec(&["x1000c0s0b0n0", "x1000c0s0b0n1", "x1000c0s1b0n0"]);
+
+    assert_eq!(
+      get_denied_xnames(
+        &to_vec(&["x1000", "x1000c0", "x1000c0s0b0"]),
+        &member_vec,
+        &node_vec
+      ),
+      vec!["x1000", "x1000c0", "x1000c0s0b0"]
+    );
+  }
+
+  #[test]
+  fn parents_of_managed_nodes_only_are_allowed() {
+    let member_vec = to_vec(&["x1000c0s0b0n0", "x1000c0s0b0n1"]);
+    let node_vec = to_vec(&["x1000c0s0b0n0", "x1000c0s0b0n1", "x1000c0s1b0n0"]);
+
+    assert!(
+      get_denied_xnames(&to_vec(&["x1000c0s0b0"]), &member_vec, &node_vec)
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn components_without_nodes_are_denied() {
+    let member_vec = to_vec(&["x1000c0s0b0n0"]);
+    let node_vec = to_vec(&["x1000c0s0b0n0"]);
+
+    assert_eq!(
+      get_denied_xnames(&to_vec(&["x3000c0s0b0"]), &member_vec, &node_vec),
+      vec!["x3000c0s0b0"]
+    );
+  }
+}
@@ -0,0 +1,245 @@
+use std::{
+  collections::HashMap,
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use jsonwebtoken::{DecodingKey, Validation, decode, decode_header};
+use rustls::pki_types::CertificateDer;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use x509_parser::{
+  certificate::X509Certificate, extensions::GeneralName, prelude::FromDer,
+};
+
+use crate::{
+  common::{
+    app_state::SiteContext,
+    jwks::{Claims, RealmAccess},
+  },
+  error::ApiError,
+};
+
+/// Service account tokens are fetched again this long before they expire
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Maps a client certificate to an identity. A certificate matches if its
+/// subject equals `subject` or one of its SANs equals `san`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientIdentity {
+  /// Name used in logs and audit records
+  pub name: String,
+  /// e.g. "CN=gitlab-ci, O=CSCS, C=CH"
+  pub subject: Option<String>,
+  /// DNS name, URI or email in the subject alternative names
+  pub san: Option<String>,
+  /// Matched against the `authorization` roles of the site, like the realm
+  /// roles of a token
+  #[serde(default)]
+  pub roles: Vec<String>,
+  #[serde(default)]
+  pub groups: Vec<String>,
+}
+
+impl ClientIdentity {
+  fn matches(&self, client_certificate: &ClientCertificate) -> bool {
+    self
+      .subject
+      .as_ref()
+      .is_some_and(|subject| subject == &client_certificate.subject)
+      || self
+        .san
+        .as_ref()
+        .is_some_and(|san| client_certificate.san_vec.contains(san))
+  }
+
+  /// Claims standing in for those of a token, so authorization and audit
+  /// work the same for certificate and token users
+  pub fn claims(&self, client_certificate: &ClientCertificate) -> Claims {
+    Claims {
+      sub: Some(client_certificate.subject.clone()),
+      preferred_username: Some(self.name.clone()),
+      realm_access: RealmAccess {
+        roles: self.roles.clone(),
+      },
+      groups: self.groups.clone(),
+      ..Default::default()
+    }
+  }
+}
+
+pub fn find_client_identity<'a>(
+  client_identity_vec: &'a [ClientIdentity],
+  client_certificate: &ClientCertificate,
+) -> Option<&'a ClientIdentity> {
+  client_identity_vec
+    .iter()
+    .find(|client_identity| client_identity.matches(client_certificate))
+}
+
+/// Keycloak account used for backend calls made on behalf of a client
+/// identity
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceAccount {
+  pub username: String,
+  pub password: Option<String>,
+  /// Read when `password` is missing, keeps the secret out of the
+  /// configuration file
+  pub password_file: Option<String>,
+}
+
+impl ServiceAccount {
+  fn get_password(&self) -> Result<String, ApiError> {
+    match (&self.password, &self.password_file) {
+      (Some(password), _) => Ok(password.clone()),
+      (None, Some(password_file)) => std::fs::read_to_string(password_file)
+        .map(|password| password.trim_end().to_string())
+        .map_err(|e| {
+          ApiError::internal(format!(
+            "Could not read password file '{}': {}",
+            password_file, e
+          ))
+        }),
+      (None, None) => Err(ApiError::internal(format!(
+        "Service account '{}' has no password",
+        self.username
+      ))),
+    }
+  }
+}
+
+/// Verified certificate presented by the client during the TLS handshake.
+/// Inserted in the request extensions by the HTTPS server
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+  pub subject: String,
+  pub san_vec: Vec<String>,
+}
+
+impl ClientCertificate {
+  pub fn from_der(certificate: &CertificateDer) -> Option<Self> {
+    let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+
+    let san_vec = certificate
+      .subject_alternative_name()
+      .ok()
+      .flatten()
+      .map(|san| {
+        san
+          .value
+          .general_names
+          .iter()
+          .filter_map(|general_name| match general_name {
+            GeneralName::DNSName(name)
+            | GeneralName::URI(name)
+            | GeneralName::RFC822Name(name) => Some(name.to_string()),
+            _ => None,
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+
+    Some(ClientCertificate {
+      subject: certificate.subject().to_string(),
+      san_vec,
+    })
+  }
+}
+
+struct CachedToken {
+  token: String,
+  expires_at: SystemTime,
+}
+
+/// Service account tokens of a site, fetched from Keycloak on first use and
+/// reused until they are about to expire
+#[derive(Default)]
+pub struct ServiceAccountTokens {
+  cache: std::sync::Mutex<HashMap<String, Arc<Mutex<Option<CachedToken>>>>>,
+}
+
+impl ServiceAccountTokens {
+  fn token_slot(&self, identity_name: &str) -> Arc<Mutex<Option<CachedToken>>> {
+    self
+      .cache
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      .entry(identity_name.to_string())
+      .or_default()
+      .clone()
+  }
+
+  /// Concurrent callers for the same account wait for the token being
+  /// fetched instead of fetching their own, other accounts are not blocked
+  pub async fn get_token(
+    &self,
+    site: &SiteContext,
+    identity_name: &str,
+  ) -> Result<String, ApiError> {
+    let service_account = site
+      .config
+      .service_accounts
+      .get(identity_name)
+      .ok_or_else(|| {
+        ApiError::forbidden(format!(
+          "No service account for '{}' in site '{}'",
+          identity_name, site.name
+        ))
+      })?;
+
+    let token_slot = self.token_slot(identity_name);
+    let mut token_slot = token_slot.lock().await;
+
+    if let Some(cached_token) = token_slot.as_ref()
+      && SystemTime::now() + TOKEN_EXPIRY_MARGIN < cached_token.expires_at
+    {
+      return Ok(cached_token.token.clone());
+    }
+
+    let keycloak_base_url =
+      site.config.keycloak_base_url.as_ref().ok_or_else(|| {
+        ApiError::internal(format!(
+          "keycloak_base_url for site '{}' not found in configuration",
+          site.name
+        ))
+      })?;
+
+    let token = csm_rs::common::authentication::get_token_from_shasta_endpoint(
+      keycloak_base_url,
+      &site.shasta_root_cert,
+      &service_account.username,
+      &service_account.get_password()?,
+    )
+    .await?;
+
+    *token_slot = Some(CachedToken {
+      expires_at: get_expiration(&token),
+      token: token.clone(),
+    });
+
+    Ok(token)
+  }
+}
+
+/// `exp` claim of a token just received from Keycloak. The signature does not
+/// need to be checked, the token is only used to decide when to fetch a new
+/// one. Tokens without `exp` are not cached
+fn get_expiration(token: &str) -> SystemTime {
+  #[derive(Deserialize)]
+  struct Expiration {
+    exp: u64,
+  }
+
+  let Ok(header) = decode_header(token) else {
+    return UNIX_EPOCH;
+  };
+
+  let mut validation = Validation::new(header.alg);
+  validation.insecure_disable_signature_validation();
+  validation.validate_aud = false;
+  validation.validate_exp = false;
+
+  decode::<Expiration>(token, &DecodingKey::from_secret(&[]), &validation)
+    .map(|token_data| UNIX_EPOCH + Duration::from_secs(token_data.claims.exp))
+    .unwrap_or(UNIX_EPOCH)
+}
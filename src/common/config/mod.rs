@@ -1,6 +1,6 @@
 pub mod types;
 
-use std::{fs::File, io::Read, path::PathBuf};
+use std::{fs::File, io::Read, path::PathBuf, sync::OnceLock};
 
 use config::Config;
 use directories::ProjectDirs;
@@ -14,7 +14,11 @@ pub fn get_default_config_path() -> PathBuf {
     "manta", /*application*/
   );
 
-  PathBuf::from(project_dirs.unwrap().config_dir())
+  // Without a home directory fall back to paths relative to the working
+  // directory instead of failing
+  project_dirs
+    .map(|project_dirs| project_dirs.config_dir().to_path_buf())
+    .unwrap_or_default()
 }
 
 pub fn get_default_manta_config_file_path() -> PathBuf {
@@ -25,7 +29,9 @@ pub fn get_default_manta_config_file_path() -> PathBuf {
     "manta", /*application*/
   );
 
-  let mut config_file_path = PathBuf::from(project_dirs.unwrap().config_dir());
+  let mut config_file_path = project_dirs
+    .map(|project_dirs| project_dirs.config_dir().to_path_buf())
+    .unwrap_or_default();
   config_file_path.push("config.toml");
   config_file_path
 }
@@ -62,12 +68,46 @@ pub fn get_default_manta_audit_file_path() -> PathBuf {
     "manta", /*application*/
   );
 
-  let mut log_file_path = PathBuf::from(project_dirs.unwrap().data_dir());
+  let mut log_file_path = project_dirs
+    .map(|project_dirs| project_dirs.data_dir().to_path_buf())
+    .unwrap_or_default();
   log_file_path.push("manta.log");
 
   log_file_path
 }
 
+pub fn get_default_audit_spool_dir() -> PathBuf {
+  // XDG Base Directory Specification
+  let project_dirs = ProjectDirs::from(
+    "local", /*qualifier*/
+    "cscs",  /*organization*/
+    "manta", /*application*/
+  );
+
+  let mut spool_dir = project_dirs
+    .map(|project_dirs| project_dirs.data_dir().to_path_buf())
+    .unwrap_or_default();
+  spool_dir.push("audit-spool");
+
+  spool_dir
+}
+
+pub fn get_default_power_jobs_file_path() -> PathBuf {
+  // XDG Base Directory Specification
+  let project_dirs = ProjectDirs::from(
+    "local", /*qualifier*/
+    "cscs",  /*organization*/
+    "manta", /*application*/
+  );
+
+  let mut power_jobs_file_path = project_dirs
+    .map(|project_dirs| project_dirs.data_dir().to_path_buf())
+    .unwrap_or_default();
+  power_jobs_file_path.push("power-jobs.json");
+
+  power_jobs_file_path
+}
+
 pub fn get_default_mgmt_plane_ca_cert_file_path() -> PathBuf {
   // XDG Base Directory Specification
   let project_dirs = ProjectDirs::from(
@@ -76,15 +116,29 @@ pub fn get_default_mgmt_plane_ca_cert_file_path() -> PathBuf {
     "manta", /*application*/
   );
 
-  let mut ca_cert_file_path = PathBuf::from(project_dirs.unwrap().config_dir());
+  let mut ca_cert_file_path = project_dirs
+    .map(|project_dirs| project_dirs.config_dir().to_path_buf())
+    .unwrap_or_default();
   ca_cert_file_path.push("alps_root_cert.pem");
 
   ca_cert_file_path
 }
 
+/// Set from the `--config` command line flag
+static CONFIG_FILE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Makes `get_config_file_path` return `config_file_path`, also on reloads
+pub fn set_config_file_path(config_file_path: PathBuf) {
+  let _ = CONFIG_FILE_PATH.set(config_file_path);
+}
+
 /// Get Manta configuration full path. Configuration may be the default one or specified by user.
 /// This function also validates if the config file is TOML format
 pub async fn get_config_file_path() -> PathBuf {
+  if let Some(config_file_path) = CONFIG_FILE_PATH.get() {
+    return config_file_path.clone();
+  }
+
   // Get config file path from ENV var
   if let Ok(env_config_file_name) = std::env::var("MANTA_CONFIG") {
     let mut env_config_file = std::path::PathBuf::new();
@@ -113,12 +167,15 @@ pub async fn get_configuration() -> Result<Config, Error> {
   };
 
   // Process config file and check format (toml) is correct
-  let config_file = config::File::new(
-    config_file_path
-      .to_str()
-      .expect("Configuration file name not defined"),
-    config::FileFormat::Toml,
-  );
+  let config_file_name = config_file_path.to_str().ok_or_else(|| {
+    Error::Message(format!(
+      "Configuration file name '{}' is not valid UTF-8",
+      config_file_path.to_string_lossy()
+    ))
+  })?;
+
+  let config_file =
+    config::File::new(config_file_name, config::FileFormat::Toml);
 
   // Process config file
   config::Config::builder()
@@ -1,6 +1,15 @@
 use std::collections::HashMap;
 
-use crate::common::audit::Auditor;
+use crate::common::{
+  audit::Auditor,
+  client_identity::{ClientIdentity, ServiceAccount},
+  cors::Cors,
+  power_jobs::PowerJobs,
+  rate_limit::RateLimits,
+  response_cache::ResponseCache,
+  service_health::ServiceHealth,
+  tls::Tls,
+};
 
 use manta_backend_dispatcher::types::K8sDetails;
 use serde::{Deserialize, Serialize};
@@ -27,35 +36,108 @@ pub struct K8sDetails {
     pub authentication: K8sAuth,
 } */
 
-/* #[derive(Serialize, Deserialize, Debug)]
-pub struct Kafka {
-    pub brokers: Vec<String>,
-    pub topic: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SysLog {
-    pub server: String,
-    pub port: u16,
-} */
-
 /* #[derive(Serialize, Deserialize, Debug)]
 pub struct Audit {
     pub kafka: Option<Kafka>,
     pub syslog: Option<SysLog>,
 } */
 
+/// How tokens sent to a site are verified. Everything is optional and
+/// derived from `keycloak_base_url` when missing
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct JwtValidation {
+  /// Keycloak realm, defaults to `shasta`
+  pub realm: Option<String>,
+  /// Defaults to `<keycloak_base_url>/realms/<realm>/protocol/openid-connect/certs`
+  pub jwks_url: Option<String>,
+  /// Expected `iss` claim, defaults to `<keycloak_base_url>/realms/<realm>`
+  pub issuer: Option<String>,
+  /// Accepted `aud` claim values, defaults to `shasta`, the client CSM
+  /// tokens are issued for. Can not be empty
+  pub audience: Option<Vec<String>>,
+  /// Clock skew tolerated on `exp` and `nbf`, in seconds
+  pub leeway: Option<u64>,
+}
+
+/// Who may change what in a site. Roles are matched against the realm roles
+/// and the groups in the caller's token
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Authorization {
+  /// May operate on any node
+  #[serde(default = "default_admin_roles")]
+  pub admin_roles: Vec<String>,
+  /// May only read, every mutating request is rejected
+  #[serde(default)]
+  pub read_only_roles: Vec<String>,
+  /// May read the audit log
+  #[serde(default = "default_auditor_roles")]
+  pub auditor_roles: Vec<String>,
+}
+
+fn default_admin_roles() -> Vec<String> {
+  vec!["pa_admin".to_string()]
+}
+
+fn default_auditor_roles() -> Vec<String> {
+  vec!["manta_auditor".to_string()]
+}
+
+impl Default for Authorization {
+  fn default() -> Self {
+    Authorization {
+      admin_roles: default_admin_roles(),
+      read_only_roles: Vec::new(),
+      auditor_roles: default_auditor_roles(),
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Site {
   pub backend: String,
   pub socks5_proxy: Option<String>,
   pub shasta_base_url: String,
+  pub keycloak_base_url: Option<String>,
+  pub jwt: Option<JwtValidation>,
+  pub authorization: Option<Authorization>,
+  /// Keycloak accounts used on behalf of the `client_identities`, by
+  /// identity name
+  #[serde(default)]
+  pub service_accounts: HashMap<String, ServiceAccount>,
   pub k8s: Option<K8sDetails>,
   // pub k8s_api_url: Option<String>,
   pub vault_base_url: Option<String>,
   pub vault_secret_path: Option<String>,
   // pub vault_role_id: Option<String>,
   pub root_ca_cert_file: String,
+  /// Entry of `service_accounts` used to check the backend services in the
+  /// background. Without it they are only checked on request
+  pub health_service_account: Option<String>,
+  /// Entry of `service_accounts` PCS is polled with while power jobs run,
+  /// and interrupted jobs are resumed with at startup. Without it the token
+  /// of the user who started the job is used
+  pub power_jobs_service_account: Option<String>,
+  /// Calls in flight to the backend, across all users. Defaults to 32
+  pub max_concurrent_backend_calls: Option<usize>,
+  /// How long a request waits for a free backend call before `429`, in
+  /// seconds. Defaults to 10
+  pub backend_queue_timeout_secs: Option<u64>,
+}
+
+/// Read at startup only, changes need a restart
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Server {
+  /// `ip:port` or `unix:/path/to/socket`, see `--listen`
+  #[serde(default)]
+  pub listen: Vec<String>,
+  /// See `--log-level`
+  pub log_level: Option<String>,
+  /// Time given to in-flight requests and websockets to finish on SIGTERM or
+  /// SIGINT, in seconds. Defaults to 30
+  pub shutdown_grace_period_secs: Option<u64>,
+  /// Address serving `/metrics`, see `--metrics-listen`. Kept apart from
+  /// `listen` so it can stay on a private interface. No metrics if missing
+  pub metrics_listen: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -66,4 +148,15 @@ pub struct MantaConfiguration {
   pub audit_file: String,
   pub sites: HashMap<String, Site>,
   pub auditor: Option<Auditor>,
+  pub server: Option<Server>,
+  pub tls: Option<Tls>,
+  /// Read at startup only, changes need a restart
+  pub cors: Option<Cors>,
+  pub service_health: Option<ServiceHealth>,
+  pub rate_limits: Option<RateLimits>,
+  pub response_cache: Option<ResponseCache>,
+  pub power_jobs: Option<PowerJobs>,
+  /// Identities of clients authenticating with a certificate
+  #[serde(default)]
+  pub client_identities: Vec<ClientIdentity>,
 }
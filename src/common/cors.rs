@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use axum::http::{HeaderName, HeaderValue, Method, Uri};
+use serde::{Deserialize, Serialize};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+/// Web origins allowed to call the API from a browser. Only same-origin
+/// requests are allowed when missing
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Cors {
+  /// e.g. `https://manta.cscs.ch`, scheme, host and port only. `*` allows
+  /// any origin, which can not be combined with `allow_credentials`
+  #[serde(default)]
+  pub allowed_origins: Vec<String>,
+  /// Defaults to `GET`, `POST`, `PUT` and `DELETE`
+  #[serde(default = "default_allowed_methods")]
+  pub allowed_methods: Vec<String>,
+  /// Defaults to `Authorization`, `Content-Type`, `X-Manta-Site` and the
+  /// headers of conditional requests
+  #[serde(default = "default_allowed_headers")]
+  pub allowed_headers: Vec<String>,
+  /// Let browsers send cookies and client certificates along
+  #[serde(default)]
+  pub allow_credentials: bool,
+  /// How long browsers may cache a preflight response, in seconds
+  pub max_age_secs: Option<u64>,
+  /// Allows any origin, method and header, with credentials. Only meant for
+  /// a local frontend dev server, e.g. Vite. Overrides everything above
+  #[serde(default)]
+  pub permissive: bool,
+}
+
+fn default_allowed_methods() -> Vec<String> {
+  ["GET", "POST", "PUT", "DELETE"]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_allowed_headers() -> Vec<String> {
+  [
+    "Authorization",
+    "Content-Type",
+    "X-Manta-Site",
+    "Cache-Control",
+    "If-None-Match",
+    "If-Modified-Since",
+  ]
+  .into_iter()
+  .map(String::from)
+  .collect()
+}
+
+/// Browsers send `Origin: <scheme>://<host>[:<port>]`, anything else in the
+/// configuration would never match
+fn parse_origin(origin: &str) -> Result<HeaderValue> {
+  let uri: Uri = origin
+    .parse()
+    .map_err(|e| anyhow::anyhow!("Origin '{}' not valid: {}", origin, e))?;
+
+  if uri.scheme().is_none()
+    || uri.authority().is_none()
+    || uri
+      .path_and_query()
+      .is_some_and(|path| path.as_str() != "/")
+    || origin.ends_with('/')
+  {
+    bail!(
+      "Origin '{}' not valid: expected <scheme>://<host>[:<port>]",
+      origin
+    );
+  }
+
+  Ok(HeaderValue::from_str(origin)?)
+}
+
+/// Layer applying `cors`. Configuration mistakes are reported here instead
+/// of making the layer panic on the first request
+pub fn cors_layer(cors: &Cors) -> Result<CorsLayer> {
+  if cors.permissive {
+    return Ok(CorsLayer::very_permissive());
+  }
+
+  let allow_origin = if cors.allowed_origins.iter().any(|origin| origin == "*")
+  {
+    if cors.allow_credentials {
+      bail!("'cors.allowed_origins' can not be '*' with 'allow_credentials'");
+    }
+    AllowOrigin::any()
+  } else {
+    AllowOrigin::list(
+      cors
+        .allowed_origins
+        .iter()
+        .map(|origin| parse_origin(origin))
+        .collect::<Result<Vec<HeaderValue>>>()?,
+    )
+  };
+
+  let allow_methods = AllowMethods::list(
+    cors
+      .allowed_methods
+      .iter()
+      .map(|method| {
+        method
+          .to_uppercase()
+          .parse::<Method>()
+          .map_err(|e| anyhow::anyhow!("Method '{}' not valid: {}", method, e))
+      })
+      .collect::<Result<Vec<Method>>>()?,
+  );
+
+  let allow_headers = AllowHeaders::list(
+    cors
+      .allowed_headers
+      .iter()
+      .map(|header| {
+        header
+          .parse::<HeaderName>()
+          .map_err(|e| anyhow::anyhow!("Header '{}' not valid: {}", header, e))
+      })
+      .collect::<Result<Vec<HeaderName>>>()?,
+  );
+
+  let mut cors_layer = CorsLayer::new()
+    .allow_origin(allow_origin)
+    .allow_methods(allow_methods)
+    .allow_headers(allow_headers)
+    .allow_credentials(cors.allow_credentials);
+
+  if let Some(max_age_secs) = cors.max_age_secs {
+    cors_layer = cors_layer.max_age(Duration::from_secs(max_age_secs));
+  }
+
+  Ok(cors_layer)
+}
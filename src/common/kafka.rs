@@ -1,131 +1,538 @@
-use std::time::Duration;
+use std::{
+  os::unix::fs::DirBuilderExt,
+  path::{Path, PathBuf},
+  sync::{
+    Arc, Weak,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+  },
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-// use kafka::producer::{Producer, Record, RequiredAcks};
 use rdkafka::{
   ClientConfig,
-  producer::{FutureProducer, FutureRecord},
+  producer::{FutureProducer, FutureRecord, Producer},
 };
 use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
 
-use super::audit::Audit;
+use super::audit::{Audit, AuditEvent};
+use super::config;
 
 use anyhow::Result;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// How often undeliverable audit events are retried
+const REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Subdirectory of the spool spooled files that can not be parsed are moved
+/// to, so they do not block the replay of the others
+const CORRUPT_DIR: &str = "corrupt";
+
+/// Shared by the producers of successive configurations, so spool file names
+/// stay unique and a producer being replaced does not replay the same files
+static SPOOL_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static REPLAY_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Kafka {
   pub brokers: Vec<String>,
   pub topic: String,
+  /// Broker acknowledgements required per message: "0", "1" or "all"
+  #[serde(default = "default_acks")]
+  pub acks: String,
+  /// none, gzip, snappy or lz4
+  #[serde(default = "default_compression")]
+  pub compression: String,
+  /// Audit event field used as message key
+  #[serde(default)]
+  pub key: KafkaMessageKey,
+  #[serde(default = "default_message_timeout_ms")]
+  pub message_timeout_ms: u64,
+  /// Events the brokers did not accept are kept here until they can be
+  /// delivered. Defaults to `$XDG_DATA_HOME/manta/audit-spool`
+  pub spool_dir: Option<String>,
+  /// Events kept in the spool at most, newer ones are dropped
+  #[serde(default = "default_max_spool_events")]
+  pub max_spool_events: usize,
+}
+
+fn default_acks() -> String {
+  "all".to_string()
+}
+
+fn default_compression() -> String {
+  "none".to_string()
+}
+
+fn default_message_timeout_ms() -> u64 {
+  5000
+}
+
+fn default_max_spool_events() -> usize {
+  100_000
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkaMessageKey {
+  #[default]
+  User,
+  /// First xname the request operates on
+  Xname,
+  Site,
+  None,
+}
+
+/// Audit event waiting in the spool
+#[derive(Serialize, Deserialize)]
+struct SpooledMessage {
+  key: Option<String>,
+  payload: String,
+}
+
+/// Long lived producer shared by all requests. Messages the brokers do not
+/// acknowledge are written to an on disk spool and replayed in the
+/// background once the brokers are back
+pub struct KafkaProducer {
+  config: Kafka,
+  producer: FutureProducer,
+  spool: Spool,
+}
+
+impl KafkaProducer {
+  pub fn new(config: &Kafka) -> Result<Arc<Self>> {
+    let producer: FutureProducer = ClientConfig::new()
+      .set("bootstrap.servers", config.brokers.join(","))
+      .set("acks", &config.acks)
+      .set("compression.type", &config.compression)
+      .set("message.timeout.ms", config.message_timeout_ms.to_string())
+      .create()?;
+
+    let spool = Spool::open(
+      config
+        .spool_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(config::get_default_audit_spool_dir),
+      config.max_spool_events,
+    )?;
+
+    let kafka_producer = Arc::new(KafkaProducer {
+      config: config.clone(),
+      producer,
+      spool,
+    });
+
+    // Stops once the configuration is reloaded and this producer dropped
+    tokio::spawn(replay_spool_periodically(Arc::downgrade(&kafka_producer)));
+
+    Ok(kafka_producer)
+  }
+
+  /// Sends the event, or spools it if the brokers can not be reached
+  pub async fn send_event(&self, event: &AuditEvent) {
+    let key = match self.config.key {
+      KafkaMessageKey::User => Some(event.user.clone()),
+      KafkaMessageKey::Xname => event.xnames.first().cloned(),
+      KafkaMessageKey::Site => Some(event.site.clone()),
+      KafkaMessageKey::None => None,
+    };
+
+    let payload = match serde_json::to_string(event) {
+      Ok(payload) => payload,
+      Err(e) => {
+        tracing::error!("Could not serialize audit event: {}", e);
+        return;
+      }
+    };
+
+    if let Err(e) = self
+      .produce_message(key.as_deref(), payload.as_bytes())
+      .await
+    {
+      tracing::warn!("Audit event not delivered to Kafka, spooling it: {}", e);
+      metrics::counter!("manta_audit_kafka_failures_total").increment(1);
+
+      if let Err(e) = self.spool.push(SpooledMessage { key, payload }).await {
+        tracing::error!("Audit event lost, could not spool it: {}", e);
+      }
+    }
+  }
+
+  /// Waits for the messages rdkafka still holds to be delivered
+  pub async fn flush(&self) {
+    let producer = self.producer.clone();
+    let timeout = Duration::from_millis(self.config.message_timeout_ms);
+
+    match tokio::task::spawn_blocking(move || producer.flush(timeout)).await {
+      Ok(Ok(())) => {}
+      Ok(Err(e)) => tracing::warn!("Could not flush Kafka producer: {}", e),
+      Err(e) => tracing::warn!("Could not flush Kafka producer: {}", e),
+    }
+  }
+
+  /// Fetches the metadata of the audit topic, fails if no broker answers
+  pub async fn check_brokers(&self, timeout: Duration) -> Result<(), String> {
+    let producer = self.producer.clone();
+    let topic = self.config.topic.clone();
+
+    tokio::task::spawn_blocking(move || {
+      producer
+        .client()
+        .fetch_metadata(Some(&topic), timeout)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+  }
+
+  pub fn config(&self) -> &Kafka {
+    &self.config
+  }
+
+  pub fn spool_depth(&self) -> usize {
+    self.spool.depth()
+  }
+
+  /// Sets the spool depth gauge, later changes keep it up to date
+  pub fn report_spool_depth(&self) {
+    metrics::gauge!("manta_audit_spool_depth").set(self.spool_depth() as f64);
+  }
+}
+
+/// On disk queue of the messages the brokers did not acknowledge, one file
+/// per message
+struct Spool {
+  dir: PathBuf,
+  depth: AtomicUsize,
+  max_events: usize,
 }
 
-impl Audit for Kafka {
-  /* fn produce_message(&self, data: &[u8]) -> Result<()> {
-      let brokers = self.brokers.clone();
-      let topic = self.topic.clone();
-
-      println!("About to publish a message at {:?} to: {}", brokers, topic);
-
-      // ~ create a producer. this is a relatively costly operation, so
-      // you'll do this typically once in your application and re-use
-      // the instance many times.
-      let mut producer = Producer::from_hosts(brokers)
-          // ~ give the brokers one second time to ack the message
-          .with_ack_timeout(Duration::from_secs(1))
-          // ~ require only one broker to ack the message
-          .with_required_acks(RequiredAcks::One)
-          // ~ build the producer with the above settings
-          .create()?;
-
-      // ~ now send a single message.  this is a synchronous/blocking
-      // operation.
-
-      // ~ we're sending 'data' as a 'value'. there will be no key
-      // associated with the sent message.
-
-      // ~ we leave the partition "unspecified" - this is a negative
-      // partition - which causes the producer to find out one on its
-      // own using its underlying partitioner.
-      producer.send(&Record {
-          topic: &topic,
-          partition: -1,
-          key: (),
-          value: data,
+impl Spool {
+  fn open(dir: PathBuf, max_events: usize) -> Result<Self> {
+    std::fs::DirBuilder::new()
+      .recursive(true)
+      .mode(0o700)
+      .create(&dir)
+      .map_err(|e| {
+        anyhow::anyhow!(
+          "Could not create audit spool directory '{}': {}",
+          dir.display(),
+          e
+        )
       })?;
 
-      // ~ we can achieve exactly the same as above in a shorter way with
-      // the following call
-      producer.send(&Record::from_value(&topic, data))?;
+    // Reported by `report_spool_depth`, the metrics recorder may not be
+    // installed yet
+    let depth = get_spool_file_vec(&dir)?.len();
 
-      Ok(())
-  } */
-
-  async fn produce_message(&self, data: &[u8]) -> Result<()> {
-    let brokers: &str = &self.brokers.join(",");
-    let topic_name: &str = &self.topic;
-
-    let producer: &FutureProducer = &ClientConfig::new()
-      .set("bootstrap.servers", brokers)
-      .set("message.timeout.ms", "5000")
-      .create()
-      .expect("Producer creation error");
-
-    let delivery_status = producer
-      .send::<Vec<u8>, _, _>(
-        FutureRecord::to(topic_name).payload(data),
-        /* FutureRecord::to(topic_name)
-        .payload(&format!("Message {}", i))
-        .key(&format!("Key {}", i))
-        .headers(OwnedHeaders::new().insert(Header {
-            key: "header_key",
-            value: Some("header_value"),
-        })), */
-        Duration::from_secs(0),
-      )
-      .await;
-
-    // This will be executed when the result is received.
-    match delivery_status {
+    Ok(Spool {
+      dir,
+      depth: AtomicUsize::new(depth),
+      max_events,
+    })
+  }
+
+  fn depth(&self) -> usize {
+    self.depth.load(Ordering::Relaxed)
+  }
+
+  /// Fails without writing anything once `max_events` are spooled
+  async fn push(&self, message: SpooledMessage) -> Result<()> {
+    if self.depth() >= self.max_events {
+      metrics::counter!("manta_audit_spool_dropped_total").increment(1);
+      return Err(anyhow::anyhow!(
+        "audit spool is full ({} events)",
+        self.max_events
+      ));
+    }
+
+    // File names sort in arrival order
+    let timestamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_nanos();
+    let sequence = SPOOL_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let file_name = format!("{:024}-{:08}.json", timestamp, sequence);
+
+    // Write to a hidden file first so replay never sees partial messages
+    let tmp_path = self.dir.join(format!(".{}", file_name));
+    let mut file = tokio::fs::OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .mode(0o600)
+      .open(&tmp_path)
+      .await?;
+    file.write_all(&serde_json::to_vec(&message)?).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&tmp_path, self.dir.join(file_name)).await?;
+
+    self.update_depth(|depth| depth + 1);
+
+    Ok(())
+  }
+
+  /// Delivers the spooled messages in order through `producer`, stops at
+  /// the first delivery failure. Files that can not be parsed are moved to
+  /// `CORRUPT_DIR`
+  async fn replay(&self, producer: &impl Audit) -> Result<()> {
+    let _replay_guard = REPLAY_LOCK.lock().await;
+
+    let spool_file_vec = get_spool_file_vec(&self.dir)?;
+
+    if spool_file_vec.is_empty() {
+      return Ok(());
+    }
+
+    tracing::info!("Replaying {} spooled audit events", spool_file_vec.len());
+
+    for spool_file in spool_file_vec {
+      let message_rslt = tokio::fs::read(&spool_file)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|content| {
+          serde_json::from_slice::<SpooledMessage>(&content)
+            .map_err(anyhow::Error::from)
+        });
+
+      let message = match message_rslt {
+        Ok(message) => message,
+        Err(e) => {
+          tracing::error!(
+            "Spooled audit event '{}' not valid, moving it to '{}': {}",
+            spool_file.display(),
+            CORRUPT_DIR,
+            e
+          );
+          metrics::counter!("manta_audit_spool_corrupt_total").increment(1);
+
+          match self.quarantine(&spool_file).await {
+            Ok(()) => self.update_depth(|depth| depth.saturating_sub(1)),
+            Err(e) => tracing::error!(
+              "Could not move '{}' out of the audit spool: {}",
+              spool_file.display(),
+              e
+            ),
+          }
+          continue;
+        }
+      };
+
+      producer
+        .produce_message(message.key.as_deref(), message.payload.as_bytes())
+        .await?;
+
+      tokio::fs::remove_file(&spool_file).await?;
+      self.update_depth(|depth| depth.saturating_sub(1));
+    }
+
+    Ok(())
+  }
+
+  async fn quarantine(&self, spool_file: &Path) -> Result<()> {
+    let corrupt_dir = self.dir.join(CORRUPT_DIR);
+    tokio::fs::create_dir_all(&corrupt_dir).await?;
+
+    let file_name = spool_file
+      .file_name()
+      .ok_or_else(|| anyhow::anyhow!("spool file without name"))?;
+    tokio::fs::rename(spool_file, corrupt_dir.join(file_name)).await?;
+
+    Ok(())
+  }
+
+  fn update_depth(&self, update: impl Fn(usize) -> usize) {
+    let previous_depth = self
+      .depth
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+        Some(update(depth))
+      })
+      .unwrap_or_else(|depth| depth);
+
+    metrics::gauge!("manta_audit_spool_depth")
+      .set(update(previous_depth) as f64);
+  }
+}
+
+impl Audit for KafkaProducer {
+  async fn produce_message(
+    &self,
+    key: Option<&str>,
+    data: &[u8],
+  ) -> Result<()> {
+    let mut record = FutureRecord::to(&self.config.topic).payload(data);
+    if let Some(key) = key {
+      record = record.key(key);
+    }
+
+    // Waits at most 'message.timeout.ms' for the brokers to acknowledge
+    match self.producer.send(record, Duration::from_secs(0)).await {
       Ok(_) => {
-        tracing::info!("Delivery status for message received");
+        tracing::debug!("Delivery status for message received");
+        Ok(())
       }
-      Err(e) => {
-        return Err(anyhow::anyhow!(
-          "Delivery status for message failed: {:?}",
-          e.0
-        ));
+      Err((e, _)) => {
+        Err(anyhow::anyhow!("Delivery status for message failed: {}", e))
       }
     }
+  }
+}
 
-    /* // This loop is non blocking: all messages will be sent one after the other, without waiting
-    // for the results.
-    let futures = (0..5)
-        .map(|i| async move {
-            // The send operation on the topic returns a future, which will be
-            // completed once the result or failure from Kafka is received.
-            let delivery_status = producer
-                .send::<Vec<u8>, _, _>(
-                    FutureRecord::to(topic_name).payload(data),
-                    /* FutureRecord::to(topic_name)
-                    .payload(&format!("Message {}", i))
-                    .key(&format!("Key {}", i))
-                    .headers(OwnedHeaders::new().insert(Header {
-                        key: "header_key",
-                        value: Some("header_value"),
-                    })), */
-                    Duration::from_secs(0),
-                )
-                .await;
-
-            // This will be executed when the result is received.
-            log::info!("Delivery status for message {} received", i);
-            delivery_status
+/// Spooled messages sorted from oldest to newest
+fn get_spool_file_vec(spool_dir: &Path) -> Result<Vec<PathBuf>> {
+  let mut spool_file_vec: Vec<PathBuf> = std::fs::read_dir(spool_dir)?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| {
+      path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .is_some_and(|file_name| {
+          file_name.ends_with(".json") && !file_name.starts_with('.')
         })
-        .collect::<Vec<_>>();
+    })
+    .collect();
 
-    // This loop will wait until all delivery statuses have been received.
-    for future in futures {
-        log::info!("Future completed. Result: {:?}", future.await);
-    } */
+  spool_file_vec.sort();
 
-    Ok(())
+  Ok(spool_file_vec)
+}
+
+async fn replay_spool_periodically(kafka_producer: Weak<KafkaProducer>) {
+  loop {
+    tokio::time::sleep(REPLAY_INTERVAL).await;
+
+    let Some(kafka_producer) = kafka_producer.upgrade() else {
+      break;
+    };
+
+    if kafka_producer.spool_depth() == 0 {
+      continue;
+    }
+
+    if let Err(e) = kafka_producer.spool.replay(kafka_producer.as_ref()).await {
+      tracing::warn!("Audit spool replay interrupted: {}", e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use super::*;
+
+  /// Accepts `capacity` messages, fails afterwards
+  struct MockProducer {
+    capacity: usize,
+    delivered: Mutex<Vec<String>>,
+  }
+
+  impl MockProducer {
+    fn new(capacity: usize) -> Self {
+      MockProducer {
+        capacity,
+        delivered: Mutex::new(Vec::new()),
+      }
+    }
+
+    fn delivered(&self) -> Vec<String> {
+      self.delivered.lock().unwrap().clone()
+    }
+  }
+
+  impl Audit for MockProducer {
+    async fn produce_message(
+      &self,
+      _key: Option<&str>,
+      data: &[u8],
+    ) -> Result<()> {
+      let mut delivered = self.delivered.lock().unwrap();
+
+      if delivered.len() >= self.capacity {
+        return Err(anyhow::anyhow!("brokers down"));
+      }
+
+      delivered.push(String::from_utf8_lossy(data).to_string());
+      Ok(())
+    }
+  }
+
+  fn temp_spool(max_events: usize) -> Spool {
+    Spool::open(
+      std::env::temp_dir()
+        .join(format!("manta-audit-spool-{}", uuid::Uuid::new_v4())),
+      max_events,
+    )
+    .unwrap()
+  }
+
+  fn message(payload: &str) -> SpooledMessage {
+    SpooledMessage {
+      key: Some("jdoe".to_string()),
+      payload: payload.to_string(),
+    }
+  }
+
+  #[tokio::test]
+  async fn replay_delivers_in_order_and_skips_corrupt_files() {
+    let spool = temp_spool(10);
+    spool.push(message("first")).await.unwrap();
+    std::fs::write(spool.dir.join("1-corrupt.json"), b"{\"key\": nul").unwrap();
+    spool.push(message("second")).await.unwrap();
+    spool.update_depth(|depth| depth + 1);
+
+    let producer = MockProducer::new(usize::MAX);
+    spool.replay(&producer).await.unwrap();
+
+    assert_eq!(producer.delivered(), ["first", "second"]);
+    assert_eq!(spool.depth(), 0);
+    assert!(get_spool_file_vec(&spool.dir).unwrap().is_empty());
+    assert!(spool.dir.join(CORRUPT_DIR).join("1-corrupt.json").exists());
+
+    std::fs::remove_dir_all(&spool.dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn replay_stops_at_first_delivery_failure() {
+    let spool = temp_spool(10);
+    spool.push(message("first")).await.unwrap();
+    spool.push(message("second")).await.unwrap();
+
+    let producer = MockProducer::new(1);
+
+    assert!(spool.replay(&producer).await.is_err());
+    assert_eq!(producer.delivered(), ["first"]);
+    assert_eq!(spool.depth(), 1);
+
+    // Delivered once the brokers are back
+    let producer = MockProducer::new(usize::MAX);
+    spool.replay(&producer).await.unwrap();
+    assert_eq!(producer.delivered(), ["second"]);
+
+    std::fs::remove_dir_all(&spool.dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn push_drops_messages_once_full() {
+    let spool = temp_spool(1);
+
+    spool.push(message("first")).await.unwrap();
+    assert!(spool.push(message("second")).await.is_err());
+    assert_eq!(spool.depth(), 1);
+    assert_eq!(get_spool_file_vec(&spool.dir).unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&spool.dir).unwrap();
+  }
+
+  #[test]
+  fn open_counts_spooled_files() {
+    let spool = temp_spool(10);
+    std::fs::write(spool.dir.join("1.json"), b"{}").unwrap();
+    std::fs::write(spool.dir.join(".2.json"), b"{}").unwrap();
+
+    assert_eq!(Spool::open(spool.dir.clone(), 10).unwrap().depth(), 1);
+
+    std::fs::remove_dir_all(&spool.dir).unwrap();
   }
 }
@@ -1,3 +1,19 @@
+pub mod app_state;
 pub mod audit;
+pub mod audit_file;
+pub mod authorization;
+pub mod client_identity;
 pub mod config;
+pub mod cors;
+pub mod jwks;
 pub mod kafka;
+pub mod pcs;
+pub mod power_jobs;
+pub mod prometheus;
+pub mod rate_limit;
+pub mod response_cache;
+pub mod server;
+pub mod service_health;
+pub mod shutdown;
+pub mod syslog;
+pub mod tls;
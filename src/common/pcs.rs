@@ -0,0 +1,97 @@
+//! PCS transitions API, shared by CSM and OCHAMI. `PCSTrait` only offers
+//! blocking calls, power jobs need the transition ID to report progress and
+//! to abort it
+
+use std::time::Duration;
+
+use manta_backend_dispatcher::error::Error;
+use serde_json::{Value, json};
+
+use crate::common::app_state::SiteContext;
+
+/// Each call to PCS gives up after this long
+const PCS_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn send(request: reqwest::RequestBuilder) -> Result<Value, Error> {
+  let response = request.send().await.map_err(Error::NetError)?;
+
+  if response.status().is_success() {
+    response.json::<Value>().await.map_err(Error::NetError)
+  } else {
+    let status = response.status();
+    let payload = response
+      .json::<Value>()
+      .await
+      .unwrap_or_else(|_| json!({ "status": status.as_u16() }));
+
+    Err(Error::CsmError(payload))
+  }
+}
+
+fn transitions_url(site: &SiteContext) -> String {
+  format!(
+    "{}/power-control/v1/transitions",
+    site.config.shasta_base_url.trim_end_matches('/')
+  )
+}
+
+/// Starts a transition and returns its ID. `operation` is one of `on`,
+/// `soft-off`, `force-off`, `soft-restart` or `hard-restart`
+pub async fn start_transition(
+  site: &SiteContext,
+  auth_token: &str,
+  operation: &str,
+  xname_vec: &[String],
+) -> Result<String, Error> {
+  let location: Vec<Value> = xname_vec
+    .iter()
+    .map(|xname| json!({ "xname": xname }))
+    .collect();
+
+  let transition = send(
+    site
+      .http_client(PCS_TIMEOUT)?
+      .post(transitions_url(site))
+      .bearer_auth(auth_token)
+      .json(&json!({ "operation": operation, "location": location })),
+  )
+  .await?;
+
+  transition["transitionID"]
+    .as_str()
+    .map(str::to_string)
+    .ok_or_else(|| {
+      Error::Message("PCS did not return a transition ID".to_string())
+    })
+}
+
+/// Status of the transition and of the task of every node
+pub async fn get_transition(
+  site: &SiteContext,
+  auth_token: &str,
+  transition_id: &str,
+) -> Result<Value, Error> {
+  send(
+    site
+      .http_client(PCS_TIMEOUT)?
+      .get(format!("{}/{}", transitions_url(site), transition_id))
+      .bearer_auth(auth_token),
+  )
+  .await
+}
+
+/// Asks PCS to stop the tasks not started yet
+pub async fn abort_transition(
+  site: &SiteContext,
+  auth_token: &str,
+  transition_id: &str,
+) -> Result<(), Error> {
+  send(
+    site
+      .http_client(PCS_TIMEOUT)?
+      .delete(format!("{}/{}", transitions_url(site), transition_id))
+      .bearer_auth(auth_token),
+  )
+  .await
+  .map(|_| ())
+}
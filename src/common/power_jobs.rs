@@ -0,0 +1,1095 @@
+use std::{
+  collections::HashMap,
+  os::unix::fs::PermissionsExt,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::common::{
+  app_state::{AppState, SiteContext},
+  config, pcs,
+};
+use crate::error::{ApiError, ErrorCode};
+
+/// Polls failing in a row before a job is given up
+const MAX_POLL_ERRORS: u32 = 5;
+
+/// `[power_jobs]`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PowerJobs {
+  /// Where jobs are kept across restarts. Defaults to
+  /// `$XDG_DATA_HOME/manta/power-jobs.json`. Read at startup only
+  pub file: Option<String>,
+  /// Finished jobs beyond this many are forgotten, oldest first
+  #[serde(default = "default_max_jobs")]
+  pub max_jobs: usize,
+  /// How often PCS is asked for the progress of a transition
+  #[serde(default = "default_poll_interval_secs")]
+  pub poll_interval_secs: u64,
+  /// Jobs not done this long after they were created are marked failed
+  #[serde(default = "default_timeout_mins")]
+  pub timeout_mins: u64,
+}
+
+fn default_max_jobs() -> usize {
+  200
+}
+
+fn default_poll_interval_secs() -> u64 {
+  3
+}
+
+fn default_timeout_mins() -> u64 {
+  30
+}
+
+impl Default for PowerJobs {
+  fn default() -> Self {
+    PowerJobs {
+      file: None,
+      max_jobs: default_max_jobs(),
+      poll_interval_secs: default_poll_interval_secs(),
+      timeout_mins: default_timeout_mins(),
+    }
+  }
+}
+
+#[derive(
+  Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+  On,
+  Off,
+  Reset,
+}
+
+impl PowerAction {
+  /// PCS transition operation
+  fn operation(&self, force: bool) -> &'static str {
+    match (self, force) {
+      (PowerAction::On, _) => "on",
+      (PowerAction::Off, false) => "soft-off",
+      (PowerAction::Off, true) => "force-off",
+      (PowerAction::Reset, false) => "soft-restart",
+      (PowerAction::Reset, true) => "hard-restart",
+    }
+  }
+
+  fn as_str(&self) -> &'static str {
+    match self {
+      PowerAction::On => "on",
+      PowerAction::Off => "off",
+      PowerAction::Reset => "reset",
+    }
+  }
+}
+
+#[derive(
+  Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+  /// Transition not created in PCS yet
+  Pending,
+  Running,
+  /// Every node succeeded
+  Completed,
+  /// The transition could not start, timed out or some nodes failed
+  Failed,
+  Cancelled,
+  /// The server restarted while the job was running. Resumed at startup if
+  /// the site has a `power_jobs_service_account`
+  Interrupted,
+}
+
+impl JobStatus {
+  /// Still being worked on by manta-ws
+  pub fn is_active(&self) -> bool {
+    matches!(self, JobStatus::Pending | JobStatus::Running)
+  }
+
+  fn as_str(&self) -> &'static str {
+    match self {
+      JobStatus::Pending => "pending",
+      JobStatus::Running => "running",
+      JobStatus::Completed => "completed",
+      JobStatus::Failed => "failed",
+      JobStatus::Cancelled => "cancelled",
+      JobStatus::Interrupted => "interrupted",
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct NodeTransition {
+  pub status: String,
+  pub at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct JobNode {
+  pub xname: String,
+  /// `pending` until PCS reports the node, then the PCS task status, e.g.
+  /// `in-progress`, `succeeded`, `failed` or `unsupported`. `cancelled` if
+  /// the job was cancelled before the node was done
+  pub status: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub detail: Option<String>,
+  /// Every status the node went through, oldest first
+  pub transitions: Vec<NodeTransition>,
+}
+
+impl JobNode {
+  fn is_done(&self) -> bool {
+    matches!(
+      self.status.as_str(),
+      "succeeded" | "failed" | "unsupported" | "cancelled"
+    )
+  }
+
+  fn set_status(
+    &mut self,
+    status: &str,
+    detail: Option<String>,
+    at: DateTime<Utc>,
+  ) -> bool {
+    if self.status == status && self.detail == detail {
+      return false;
+    }
+
+    if self.status != status {
+      self.transitions.push(NodeTransition {
+        status: status.to_string(),
+        at,
+      });
+    }
+
+    self.status = status.to_string();
+    self.detail = detail;
+
+    true
+  }
+}
+
+/// Power action running in the background, see `GET /jobs/{id}`
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct PowerJob {
+  pub id: String,
+  pub site: String,
+  /// User who started the job
+  pub user: String,
+  pub action: PowerAction,
+  pub force: bool,
+  /// PCS transition, once created
+  pub transition_id: Option<String>,
+  pub status: JobStatus,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+  pub succeeded: usize,
+  /// Nodes that failed or do not support the action
+  pub failed: usize,
+  pub nodes: Vec<JobNode>,
+}
+
+impl PowerJob {
+  /// Copies the task status of every node out of a PCS transition
+  fn apply_transition(&mut self, transition: &Value) -> bool {
+    let now = Utc::now();
+    let mut changed = false;
+
+    let task_vec = transition
+      .get("tasks")
+      .and_then(Value::as_array)
+      .into_iter()
+      .flatten();
+
+    for task in task_vec {
+      let (Some(xname), Some(status)) =
+        (task["xname"].as_str(), task["taskStatus"].as_str())
+      else {
+        continue;
+      };
+
+      let detail = task["error"]
+        .as_str()
+        .filter(|error| !error.is_empty())
+        .or(task["taskStatusDescription"].as_str())
+        .map(str::to_string);
+
+      if let Some(node) = self.nodes.iter_mut().find(|node| node.xname == xname)
+      {
+        changed |= node.set_status(status, detail, now);
+      }
+    }
+
+    if changed {
+      self.count_nodes();
+      self.updated_at = now;
+    }
+
+    changed
+  }
+
+  fn count_nodes(&mut self) {
+    self.succeeded = self
+      .nodes
+      .iter()
+      .filter(|node| node.status == "succeeded")
+      .count();
+    self.failed = self
+      .nodes
+      .iter()
+      .filter(|node| node.status == "failed" || node.status == "unsupported")
+      .count();
+  }
+
+  /// Status of a job whose transition completed
+  fn outcome(&self) -> (JobStatus, Option<String>) {
+    if self.succeeded == self.nodes.len() {
+      (JobStatus::Completed, None)
+    } else {
+      (
+        JobStatus::Failed,
+        Some(format!(
+          "{} of {} nodes did not succeed",
+          self.nodes.len() - self.succeeded,
+          self.nodes.len()
+        )),
+      )
+    }
+  }
+}
+
+struct JobEntry {
+  sender: Arc<watch::Sender<PowerJob>>,
+  cancel: CancellationToken,
+  /// Token of the caller who cancelled the job, the transition is aborted
+  /// with it
+  cancel_token: Option<String>,
+}
+
+/// Power jobs of every site, newest `max_jobs` finished ones included.
+/// Lives across configuration reloads and is saved to `power_jobs.file`
+/// every time a job changes
+pub struct PowerJobStore {
+  jobs: Mutex<HashMap<String, JobEntry>>,
+  file: PathBuf,
+  /// Writes to `file` one at a time, the last one has the latest state
+  persist_lock: tokio::sync::Mutex<()>,
+}
+
+impl PowerJobStore {
+  /// Reads the jobs saved by the previous run. Jobs it did not finish are
+  /// marked interrupted
+  pub fn load(power_jobs: &PowerJobs) -> Self {
+    let file = power_jobs
+      .file
+      .as_ref()
+      .map(PathBuf::from)
+      .unwrap_or_else(config::get_default_power_jobs_file_path);
+
+    let job_vec: Vec<PowerJob> = match std::fs::read(&file) {
+      Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+        eprintln!(
+          "WARNING - Could not parse power jobs file '{}', past jobs are lost. Reason:\n{}",
+          file.display(),
+          e
+        );
+        Vec::new()
+      }),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+      Err(e) => {
+        eprintln!(
+          "WARNING - Could not read power jobs file '{}', past jobs are lost. Reason:\n{}",
+          file.display(),
+          e
+        );
+        Vec::new()
+      }
+    };
+
+    let jobs = job_vec
+      .into_iter()
+      .map(|mut job| {
+        // Without a transition there is nothing to follow, PCS may or may
+        // not have started one
+        if job.transition_id.is_none()
+          && (job.status.is_active() || job.status == JobStatus::Interrupted)
+        {
+          job.status = JobStatus::Failed;
+          job.error =
+            Some("Server stopped before the transition started".to_string());
+        } else if job.status.is_active() {
+          job.status = JobStatus::Interrupted;
+        }
+
+        (
+          job.id.clone(),
+          JobEntry {
+            sender: Arc::new(watch::Sender::new(job)),
+            cancel: CancellationToken::new(),
+            cancel_token: None,
+          },
+        )
+      })
+      .collect();
+
+    PowerJobStore {
+      jobs: Mutex::new(jobs),
+      file,
+      persist_lock: tokio::sync::Mutex::new(()),
+    }
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, JobEntry>> {
+    self
+      .jobs
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// Adds `job` and forgets the oldest finished jobs beyond `max_jobs`
+  fn insert(&self, job: PowerJob, max_jobs: usize) -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let mut jobs = self.lock();
+
+    jobs.insert(
+      job.id.clone(),
+      JobEntry {
+        sender: Arc::new(watch::Sender::new(job)),
+        cancel: cancel.clone(),
+        cancel_token: None,
+      },
+    );
+
+    while jobs.len() > max_jobs {
+      let oldest_id = jobs
+        .iter()
+        .filter(|(_, entry)| !entry.sender.borrow().status.is_active())
+        .min_by_key(|(_, entry)| entry.sender.borrow().created_at)
+        .map(|(id, _)| id.clone());
+
+      match oldest_id {
+        Some(oldest_id) => jobs.remove(&oldest_id),
+        None => break,
+      };
+    }
+
+    cancel
+  }
+
+  /// Every job, newest first
+  pub fn list(&self) -> Vec<PowerJob> {
+    let mut job_vec: Vec<PowerJob> = self
+      .lock()
+      .values()
+      .map(|entry| entry.sender.borrow().clone())
+      .collect();
+
+    job_vec.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+
+    job_vec
+  }
+
+  /// Job `id` of `site`. Jobs of other sites are not found
+  pub fn get(&self, site: &str, id: &str) -> Result<PowerJob, ApiError> {
+    self
+      .lock()
+      .get(id)
+      .map(|entry| entry.sender.borrow().clone())
+      .filter(|job| job.site == site)
+      .ok_or_else(|| ApiError::not_found(format!("Job '{}' not found", id)))
+  }
+
+  /// Receives the job every time it changes. Closed once the job is
+  /// forgotten
+  pub fn subscribe(&self, id: &str) -> Option<watch::Receiver<PowerJob>> {
+    self.lock().get(id).map(|entry| entry.sender.subscribe())
+  }
+
+  /// Asks the runner of the job to abort its transition with `auth_token`
+  fn cancel(&self, id: &str, auth_token: &str) {
+    if let Some(entry) = self.lock().get_mut(id) {
+      entry.cancel_token = Some(auth_token.to_string());
+      entry.cancel.cancel();
+    }
+  }
+
+  fn take_cancel_token(&self, id: &str) -> Option<String> {
+    self
+      .lock()
+      .get_mut(id)
+      .and_then(|entry| entry.cancel_token.take())
+  }
+
+  /// Marks an interrupted job running again, so a single caller resumes it
+  fn claim_interrupted(&self, id: &str) -> Option<CancellationToken> {
+    let jobs = self.lock();
+    let entry = jobs.get(id)?;
+
+    entry
+      .sender
+      .send_if_modified(|job| {
+        if job.status == JobStatus::Interrupted && job.transition_id.is_some() {
+          job.status = JobStatus::Running;
+          true
+        } else {
+          false
+        }
+      })
+      .then(|| entry.cancel.clone())
+  }
+
+  /// Applies `modify` to the job and saves all the jobs if it changed
+  async fn update(&self, id: &str, modify: impl FnOnce(&mut PowerJob) -> bool) {
+    let Some(sender) = self.lock().get(id).map(|entry| entry.sender.clone())
+    else {
+      return;
+    };
+
+    if sender.send_if_modified(modify) {
+      self.persist().await;
+    }
+  }
+
+  /// Writes a temporary file next to `file` and renames it, so a crash
+  /// never leaves a truncated file behind
+  async fn persist(&self) {
+    let _persist_guard = self.persist_lock.lock().await;
+
+    let content = match serde_json::to_vec(&self.list()) {
+      Ok(content) => content,
+      Err(e) => {
+        tracing::error!("Could not serialize power jobs: {}", e);
+        return;
+      }
+    };
+
+    let tmp_file = self.file.with_extension("json.tmp");
+
+    let persist_rslt = async {
+      if let Some(dir) = self.file.parent()
+        && !dir.as_os_str().is_empty()
+      {
+        tokio::fs::create_dir_all(dir).await?;
+      }
+      tokio::fs::write(&tmp_file, &content).await?;
+      tokio::fs::set_permissions(
+        &tmp_file,
+        std::fs::Permissions::from_mode(0o600),
+      )
+      .await?;
+      tokio::fs::rename(&tmp_file, &self.file).await
+    }
+    .await;
+
+    if let Err(e) = persist_rslt {
+      tracing::warn!(
+        "Could not save power jobs to '{}': {}",
+        self.file.display(),
+        e
+      );
+    }
+  }
+}
+
+/// `None` if the job is cancelled before PCS is asked to start the
+/// transition. Once asked, the call is not interrupted so the transition ID
+/// is never lost
+async fn start_transition(
+  site: &SiteContext,
+  auth_token: &str,
+  job: &PowerJob,
+  cancel: &CancellationToken,
+) -> Result<Option<String>, ApiError> {
+  let xname_vec: Vec<String> =
+    job.nodes.iter().map(|node| node.xname.clone()).collect();
+
+  let _backend_permit = tokio::select! {
+    biased;
+    _ = cancel.cancelled() => return Ok(None),
+    backend_permit = site.backend_limiter.acquire(1) => backend_permit?,
+  };
+
+  if cancel.is_cancelled() {
+    return Ok(None);
+  }
+
+  Ok(Some(
+    pcs::start_transition(
+      site,
+      auth_token,
+      job.action.operation(job.force),
+      &xname_vec,
+    )
+    .await?,
+  ))
+}
+
+async fn get_transition(
+  site: &SiteContext,
+  auth_token: &str,
+  transition_id: &str,
+) -> Result<Value, ApiError> {
+  let _backend_permit = site.backend_limiter.acquire(1).await?;
+
+  Ok(pcs::get_transition(site, auth_token, transition_id).await?)
+}
+
+async fn abort_transition(
+  site: &SiteContext,
+  auth_token: &str,
+  transition_id: &str,
+) -> Result<(), ApiError> {
+  let _backend_permit = site.backend_limiter.acquire(1).await?;
+
+  Ok(pcs::abort_transition(site, auth_token, transition_id).await?)
+}
+
+/// Token PCS is polled with, asked for before every call so it is renewed
+/// while the job runs. A token of the `power_jobs_service_account` of the
+/// site if set, otherwise the one the job was started or resumed with
+async fn get_poll_token(
+  site: &SiteContext,
+  auth_token: &str,
+) -> Result<String, ApiError> {
+  match &site.config.power_jobs_service_account {
+    Some(service_account) => {
+      site
+        .service_account_tokens
+        .get_token(site, service_account)
+        .await
+    }
+    None => Ok(auth_token.to_string()),
+  }
+}
+
+/// Sets the final status of a job. Nodes of a cancelled job not done yet
+/// are marked cancelled
+async fn finish(
+  state: &AppState,
+  site: &SiteContext,
+  id: &str,
+  status: JobStatus,
+  error: Option<String>,
+) {
+  let mut action = None;
+
+  state
+    .power_jobs
+    .update(id, |job| {
+      let now = Utc::now();
+
+      if status == JobStatus::Cancelled {
+        for node in job.nodes.iter_mut().filter(|node| !node.is_done()) {
+          node.set_status("cancelled", None, now);
+        }
+      }
+
+      job.status = status;
+      job.error = error;
+      job.updated_at = now;
+      action = Some(job.action);
+
+      true
+    })
+    .await;
+
+  // Power status of the nodes is part of the group details
+  state.response_cache.invalidate_power(&site.name);
+
+  if let Some(action) = action {
+    metrics::counter!(
+      "manta_power_jobs_total",
+      "action" => action.as_str(),
+      "status" => status.as_str(),
+    )
+    .increment(1);
+  }
+
+  tracing::info!("Power job '{}' {}", id, status.as_str());
+}
+
+/// Aborts the transition and records the nodes done before the abort
+async fn cancel_transition(
+  state: &AppState,
+  site: &SiteContext,
+  auth_token: &str,
+  id: &str,
+  transition_id: &str,
+) {
+  let error = abort_transition(site, auth_token, transition_id)
+    .await
+    .err()
+    .map(|e| format!("PCS could not abort the transition: {}", e.detail));
+
+  if let Ok(transition) = get_transition(site, auth_token, transition_id).await
+  {
+    state
+      .power_jobs
+      .update(id, |job| job.apply_transition(&transition))
+      .await;
+  }
+
+  finish(state, site, id, JobStatus::Cancelled, error).await;
+}
+
+/// Starts the PCS transition of the job, unless resuming it, and follows it
+/// until it completes, is cancelled or the server shuts down. The transition
+/// is started with the token of the user who started the job, and aborted
+/// with the one of the user who cancelled it
+async fn run_job(
+  state: AppState,
+  site: Arc<SiteContext>,
+  auth_token: String,
+  id: String,
+  cancel: CancellationToken,
+) {
+  let Ok(job) = state.power_jobs.get(&site.name, &id) else {
+    return;
+  };
+
+  let power_jobs = state
+    .context()
+    .configuration
+    .power_jobs
+    .clone()
+    .unwrap_or_default();
+
+  let transition_id = match job.transition_id.clone() {
+    Some(transition_id) => transition_id,
+    None => match start_transition(&site, &auth_token, &job, &cancel).await {
+      Ok(None) => {
+        finish(&state, &site, &id, JobStatus::Cancelled, None).await;
+        return;
+      }
+      Ok(Some(transition_id)) => {
+        state
+          .power_jobs
+          .update(&id, |job| {
+            job.transition_id = Some(transition_id.clone());
+            job.status = JobStatus::Running;
+            job.updated_at = Utc::now();
+            true
+          })
+          .await;
+        transition_id
+      }
+      Err(e) => {
+        let error = format!("Could not start transition: {}", e.detail);
+        finish(&state, &site, &id, JobStatus::Failed, Some(error)).await;
+        return;
+      }
+    },
+  };
+
+  let deadline = job.created_at
+    + TimeDelta::minutes(power_jobs.timeout_mins.min(i64::MAX as u64) as i64);
+  let poll_interval = Duration::from_secs(power_jobs.poll_interval_secs.max(1));
+  let mut poll_errors = 0;
+
+  loop {
+    tokio::select! {
+      biased;
+      _ = cancel.cancelled() => {
+        let cancel_token = state
+          .power_jobs
+          .take_cancel_token(&id)
+          .unwrap_or_else(|| auth_token.clone());
+        cancel_transition(&state, &site, &cancel_token, &id, &transition_id)
+          .await;
+        return;
+      }
+      // Saved as running, marked interrupted on the next start
+      _ = state.shutdown.triggered() => return,
+      _ = tokio::time::sleep(poll_interval) => {}
+    }
+
+    let transition_rslt = match get_poll_token(&site, &auth_token).await {
+      Ok(poll_token) => {
+        get_transition(&site, &poll_token, &transition_id).await
+      }
+      Err(e) => Err(e),
+    };
+
+    match transition_rslt {
+      Ok(transition) => {
+        poll_errors = 0;
+
+        state
+          .power_jobs
+          .update(&id, |job| job.apply_transition(&transition))
+          .await;
+
+        match transition["transitionStatus"].as_str() {
+          Some("completed") => {
+            let (status, error) = state
+              .power_jobs
+              .get(&site.name, &id)
+              .map(|job| job.outcome())
+              .unwrap_or((JobStatus::Completed, None));
+            finish(&state, &site, &id, status, error).await;
+            return;
+          }
+          Some("aborted") => {
+            let error = "Transition aborted in PCS".to_string();
+            finish(&state, &site, &id, JobStatus::Failed, Some(error)).await;
+            return;
+          }
+          _ => {}
+        }
+      }
+      Err(e) => {
+        poll_errors += 1;
+        tracing::warn!(
+          "Power job '{}': could not get transition '{}' ({}/{}): {}",
+          id,
+          transition_id,
+          poll_errors,
+          MAX_POLL_ERRORS,
+          e.detail
+        );
+
+        if poll_errors >= MAX_POLL_ERRORS {
+          let error = format!("Lost track of the transition: {}", e.detail);
+          finish(&state, &site, &id, JobStatus::Failed, Some(error)).await;
+          return;
+        }
+      }
+    }
+
+    if Utc::now() >= deadline {
+      let error = format!(
+        "Transition not done after {} minutes",
+        power_jobs.timeout_mins
+      );
+      finish(&state, &site, &id, JobStatus::Failed, Some(error)).await;
+      return;
+    }
+  }
+}
+
+/// Creates a job running `action` on the nodes in the background
+pub async fn start_job(
+  state: &AppState,
+  site: Arc<SiteContext>,
+  user: &str,
+  auth_token: &str,
+  action: PowerAction,
+  force: bool,
+  xname_vec: Vec<String>,
+) -> PowerJob {
+  let now = Utc::now();
+
+  let job = PowerJob {
+    id: Uuid::new_v4().to_string(),
+    site: site.name.clone(),
+    user: user.to_string(),
+    action,
+    force,
+    transition_id: None,
+    status: JobStatus::Pending,
+    error: None,
+    created_at: now,
+    updated_at: now,
+    succeeded: 0,
+    failed: 0,
+    nodes: xname_vec
+      .into_iter()
+      .map(|xname| JobNode {
+        xname,
+        status: "pending".to_string(),
+        detail: None,
+        transitions: vec![NodeTransition {
+          status: "pending".to_string(),
+          at: now,
+        }],
+      })
+      .collect(),
+  };
+
+  let max_jobs = state
+    .context()
+    .configuration
+    .power_jobs
+    .as_ref()
+    .map_or_else(default_max_jobs, |power_jobs| power_jobs.max_jobs)
+    .max(1);
+
+  let cancel = state.power_jobs.insert(job.clone(), max_jobs);
+  state.power_jobs.persist().await;
+
+  state.shutdown.spawn(run_job(
+    state.clone(),
+    site,
+    auth_token.to_string(),
+    job.id.clone(),
+    cancel,
+  ));
+
+  job
+}
+
+/// Follows an interrupted job again with `auth_token`. Does nothing for
+/// other jobs
+fn resume_job(
+  state: &AppState,
+  site: Arc<SiteContext>,
+  auth_token: &str,
+  id: &str,
+) {
+  if let Some(cancel) = state.power_jobs.claim_interrupted(id) {
+    tracing::info!("Resuming power job '{}'", id);
+
+    state.shutdown.spawn(run_job(
+      state.clone(),
+      site,
+      auth_token.to_string(),
+      id.to_string(),
+      cancel,
+    ));
+  }
+}
+
+/// Follows the jobs interrupted by the last stop again, with a token of the
+/// `power_jobs_service_account` of their site. Jobs of sites without one stay
+/// interrupted until cancelled
+pub async fn resume_interrupted_jobs(state: AppState) {
+  let context = state.context();
+
+  let job_vec = state
+    .power_jobs
+    .list()
+    .into_iter()
+    .filter(|job| job.status == JobStatus::Interrupted);
+
+  for job in job_vec {
+    let Some(site) = context.site(&job.site) else {
+      continue;
+    };
+    let Some(service_account) = &site.config.power_jobs_service_account else {
+      continue;
+    };
+
+    match site
+      .service_account_tokens
+      .get_token(&site, service_account)
+      .await
+    {
+      Ok(auth_token) => resume_job(&state, site.clone(), &auth_token, &job.id),
+      Err(e) => {
+        tracing::warn!("Could not resume power job '{}': {}", job.id, e.detail)
+      }
+    }
+  }
+}
+
+/// Cancels a running or interrupted job. The transition is aborted in PCS,
+/// nodes already done are not powered back
+pub async fn cancel_job(
+  state: &AppState,
+  site: Arc<SiteContext>,
+  auth_token: &str,
+  job: &PowerJob,
+) -> Result<(), ApiError> {
+  if !state.power_jobs.lock().contains_key(&job.id) {
+    return Err(ApiError::not_found(format!("Job '{}' not found", job.id)));
+  }
+
+  match job.status {
+    JobStatus::Pending | JobStatus::Running => {
+      state.power_jobs.cancel(&job.id, auth_token)
+    }
+    // Resumed only to abort the transition
+    JobStatus::Interrupted if job.transition_id.is_some() => {
+      state.power_jobs.cancel(&job.id, auth_token);
+      resume_job(state, site, auth_token, &job.id);
+    }
+    JobStatus::Interrupted => {
+      finish(state, &site, &job.id, JobStatus::Cancelled, None).await
+    }
+    status => {
+      return Err(ApiError::new(
+        StatusCode::CONFLICT,
+        ErrorCode::Conflict,
+        format!("Job '{}' is already {}", job.id, status.as_str()),
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn new_job(status: JobStatus, transition_id: Option<&str>) -> PowerJob {
+    let now = Utc::now();
+
+    PowerJob {
+      id: Uuid::new_v4().to_string(),
+      site: "alps".to_string(),
+      user: "jdoe".to_string(),
+      action: PowerAction::On,
+      force: false,
+      transition_id: transition_id.map(str::to_string),
+      status,
+      error: None,
+      created_at: now,
+      updated_at: now,
+      succeeded: 0,
+      failed: 0,
+      nodes: ["x1000c0s0b0n0", "x1000c0s0b0n1"]
+        .into_iter()
+        .map(|xname| JobNode {
+          xname: xname.to_string(),
+          status: "pending".to_string(),
+          detail: None,
+          transitions: vec![NodeTransition {
+            status: "pending".to_string(),
+            at: now,
+          }],
+        })
+        .collect(),
+    }
+  }
+
+  fn temp_jobs_file() -> PathBuf {
+    std::env::temp_dir()
+      .join(format!("manta-power-jobs-{}.json", Uuid::new_v4()))
+  }
+
+  fn new_store(file: &std::path::Path) -> PowerJobStore {
+    PowerJobStore::load(&PowerJobs {
+      file: Some(file.display().to_string()),
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn apply_transition_records_node_status() {
+    let mut job = new_job(JobStatus::Running, Some("t1"));
+
+    let transition = json!({
+      "tasks": [
+        { "xname": "x1000c0s0b0n0", "taskStatus": "succeeded" },
+        {
+          "xname": "x1000c0s0b0n1",
+          "taskStatus": "failed",
+          "error": "BMC not reachable"
+        },
+        { "xname": "x9000c0s0b0n0", "taskStatus": "succeeded" }
+      ]
+    });
+
+    assert!(job.apply_transition(&transition));
+    assert_eq!(job.succeeded, 1);
+    assert_eq!(job.failed, 1);
+    assert_eq!(job.nodes[1].detail.as_deref(), Some("BMC not reachable"));
+    assert_eq!(job.nodes[0].transitions.len(), 2);
+
+    // Polling the same transition again changes nothing
+    assert!(!job.apply_transition(&transition));
+    assert_eq!(job.nodes[0].transitions.len(), 2);
+  }
+
+  #[test]
+  fn outcome_completed_only_if_every_node_succeeded() {
+    let mut job = new_job(JobStatus::Running, Some("t1"));
+
+    job.apply_transition(&json!({
+      "tasks": [{ "xname": "x1000c0s0b0n0", "taskStatus": "succeeded" }]
+    }));
+    let (status, error) = job.outcome();
+    assert_eq!(status, JobStatus::Failed);
+    assert_eq!(error.as_deref(), Some("1 of 2 nodes did not succeed"));
+
+    job.apply_transition(&json!({
+      "tasks": [{ "xname": "x1000c0s0b0n1", "taskStatus": "succeeded" }]
+    }));
+    assert_eq!(job.outcome(), (JobStatus::Completed, None));
+  }
+
+  #[test]
+  fn cancel_hands_the_canceller_token_to_the_runner() {
+    let file = temp_jobs_file();
+    let store = new_store(&file);
+    let job = new_job(JobStatus::Running, Some("t1"));
+
+    let cancel = store.insert(job.clone(), 10);
+    assert!(!cancel.is_cancelled());
+    assert_eq!(store.take_cancel_token(&job.id), None);
+
+    store.cancel(&job.id, "canceller-token");
+    assert!(cancel.is_cancelled());
+    assert_eq!(
+      store.take_cancel_token(&job.id).as_deref(),
+      Some("canceller-token")
+    );
+    assert_eq!(store.take_cancel_token(&job.id), None);
+  }
+
+  #[test]
+  fn load_fails_jobs_without_transition_and_interrupts_the_others() {
+    let file = temp_jobs_file();
+
+    let pending = new_job(JobStatus::Pending, None);
+    let running = new_job(JobStatus::Running, Some("t1"));
+    let interrupted = new_job(JobStatus::Interrupted, None);
+    let completed = new_job(JobStatus::Completed, Some("t2"));
+
+    std::fs::write(
+      &file,
+      serde_json::to_vec(&[&pending, &running, &interrupted, &completed])
+        .unwrap(),
+    )
+    .unwrap();
+
+    let store = new_store(&file);
+    let status_of = |job: &PowerJob| store.get("alps", &job.id).unwrap().status;
+
+    assert_eq!(status_of(&pending), JobStatus::Failed);
+    assert_eq!(status_of(&running), JobStatus::Interrupted);
+    assert_eq!(status_of(&interrupted), JobStatus::Failed);
+    assert_eq!(status_of(&completed), JobStatus::Completed);
+
+    std::fs::remove_file(&file).unwrap();
+  }
+
+  #[tokio::test]
+  async fn persisted_jobs_are_loaded_again() {
+    let file = temp_jobs_file();
+    let store = new_store(&file);
+    let job = new_job(JobStatus::Running, Some("t1"));
+
+    store.insert(job.clone(), 10);
+    store
+      .update(&job.id, |job| {
+        job.status = JobStatus::Cancelled;
+        true
+      })
+      .await;
+
+    let loaded_job = new_store(&file).get("alps", &job.id).unwrap();
+    assert_eq!(loaded_job.status, JobStatus::Cancelled);
+    assert_eq!(loaded_job.nodes.len(), 2);
+    assert!(store.get("daint", &job.id).is_err());
+
+    std::fs::remove_file(&file).unwrap();
+  }
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use axum::{
+  Router,
+  extract::{MatchedPath, Request},
+  middleware::Next,
+  response::Response,
+  routing::get,
+};
+use metrics_exporter_prometheus::{
+  Matcher, PrometheusBuilder, PrometheusHandle,
+};
+
+/// Histogram buckets, in seconds. Backend calls and console sessions can be
+/// slow, hence the long tail
+const DURATION_BUCKETS: &[f64] = &[
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Histograms are drained into their buckets this often
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Installs the global recorder used by the `metrics` macros. Until then
+/// they do nothing
+pub fn install_recorder() -> Result<PrometheusHandle> {
+  let handle = PrometheusBuilder::new()
+    .set_buckets_for_metric(
+      Matcher::Suffix("duration_seconds".to_string()),
+      DURATION_BUCKETS,
+    )?
+    .install_recorder()?;
+
+  let upkeep_handle = handle.clone();
+  tokio::spawn(async move {
+    loop {
+      tokio::time::sleep(UPKEEP_INTERVAL).await;
+      upkeep_handle.run_upkeep();
+    }
+  });
+
+  Ok(handle)
+}
+
+/// `GET /metrics` in the Prometheus text format
+pub fn metrics_router(handle: PrometheusHandle) -> Router {
+  Router::new()
+    .route("/metrics", get(move || std::future::ready(handle.render())))
+}
+
+/// Counts requests and records their latency per route template, e.g.
+/// `/node/{node}/power-on`, so xnames do not end up in label values
+pub async fn track_request(request: Request, next: Next) -> Response {
+  let route = request
+    .extensions()
+    .get::<MatchedPath>()
+    .map(|matched_path| matched_path.as_str().to_string())
+    .unwrap_or_else(|| "unmatched".to_string());
+  let method = request.method().to_string();
+
+  let start = Instant::now();
+  let response = next.run(request).await;
+
+  let labels = [
+    ("method", method),
+    ("route", route),
+    ("status", response.status().as_u16().to_string()),
+  ];
+  metrics::counter!("manta_http_requests_total", &labels).increment(1);
+  metrics::histogram!("manta_http_request_duration_seconds", &labels)
+    .record(start.elapsed().as_secs_f64());
+
+  response
+}
+
+/// Open websocket of a given kind, counted in `manta_websockets_active` for
+/// as long as it is alive
+pub struct ActiveWebsocket(&'static str);
+
+impl ActiveWebsocket {
+  pub fn new(kind: &'static str) -> Self {
+    metrics::gauge!("manta_websockets_active", "kind" => kind).increment(1);
+    ActiveWebsocket(kind)
+  }
+}
+
+impl Drop for ActiveWebsocket {
+  fn drop(&mut self) {
+    metrics::gauge!("manta_websockets_active", "kind" => self.0).decrement(1);
+  }
+}
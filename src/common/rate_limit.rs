@@ -0,0 +1,369 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use axum::{
+  extract::{MatchedPath, Request, State},
+  http::Method,
+  middleware::Next,
+  response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::common::{
+  app_state::{AppState, SelectedSite},
+  jwks::Claims,
+};
+use crate::error::ApiError;
+
+/// Buckets left untouched this long are full again and can be forgotten
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default `max_concurrent_backend_calls` of a site
+pub const DEFAULT_MAX_CONCURRENT_BACKEND_CALLS: usize = 32;
+
+/// Default `backend_queue_timeout_secs` of a site
+pub const DEFAULT_BACKEND_QUEUE_TIMEOUT_SECS: u64 = 10;
+
+/// Token bucket refilled with `per_minute` tokens a minute, holding at most
+/// `burst`. A request takes one token. `per_minute = 0` disables the limit
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct TokenBucket {
+  pub per_minute: u32,
+  pub burst: u32,
+}
+
+/// `[rate_limits]`, per authenticated user and route class
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RateLimits {
+  /// `GET` requests
+  #[serde(default = "default_read")]
+  pub read: TokenBucket,
+  /// `POST`, `PUT` and `DELETE` requests
+  #[serde(default = "default_mutate")]
+  pub mutate: TokenBucket,
+  /// Websockets and server-sent events: consoles, CFS session logs and
+  /// power job progress
+  #[serde(default = "default_stream")]
+  pub stream: TokenBucket,
+}
+
+fn default_read() -> TokenBucket {
+  TokenBucket {
+    per_minute: 300,
+    burst: 60,
+  }
+}
+
+fn default_mutate() -> TokenBucket {
+  TokenBucket {
+    per_minute: 60,
+    burst: 10,
+  }
+}
+
+fn default_stream() -> TokenBucket {
+  TokenBucket {
+    per_minute: 20,
+    burst: 5,
+  }
+}
+
+impl Default for RateLimits {
+  fn default() -> Self {
+    RateLimits {
+      read: default_read(),
+      mutate: default_mutate(),
+      stream: default_stream(),
+    }
+  }
+}
+
+/// Websockets and server-sent events, they do not hold a backend call while
+/// they run
+const STREAM_ROUTES: &[&str] = &[
+  "/console/{xname}",
+  "/cfssession/{cfssession}/logs",
+  "/jobs/{id}/events",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+  Read,
+  Mutate,
+  Stream,
+}
+
+impl RouteClass {
+  /// Classified by the matched route, a client can not pick its class with
+  /// headers
+  fn of(request: &Request) -> Self {
+    let is_stream =
+      request
+        .extensions()
+        .get::<MatchedPath>()
+        .is_some_and(|matched_path| {
+          STREAM_ROUTES.contains(&matched_path.as_str())
+        });
+
+    if is_stream {
+      RouteClass::Stream
+    } else if request.method() == Method::GET
+      || request.method() == Method::HEAD
+    {
+      RouteClass::Read
+    } else {
+      RouteClass::Mutate
+    }
+  }
+
+  fn as_str(&self) -> &'static str {
+    match self {
+      RouteClass::Read => "read",
+      RouteClass::Mutate => "mutate",
+      RouteClass::Stream => "stream",
+    }
+  }
+
+  fn bucket(&self, rate_limits: &RateLimits) -> TokenBucket {
+    match self {
+      RouteClass::Read => rate_limits.read,
+      RouteClass::Mutate => rate_limits.mutate,
+      RouteClass::Stream => rate_limits.stream,
+    }
+  }
+}
+
+struct Bucket {
+  tokens: f64,
+  updated_at: Instant,
+}
+
+impl Bucket {
+  fn refill(&mut self, config: TokenBucket, now: Instant) {
+    let per_second = config.per_minute as f64 / 60.0;
+    let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+
+    self.tokens = (self.tokens + elapsed * per_second).min(config.burst as f64);
+    self.updated_at = now;
+  }
+}
+
+struct Buckets {
+  by_user: HashMap<(String, RouteClass), Bucket>,
+  pruned_at: Instant,
+}
+
+/// Token buckets of every user. Lives across configuration reloads, new
+/// limits apply from the next request on
+pub struct RateLimiter {
+  buckets: Mutex<Buckets>,
+}
+
+impl Default for RateLimiter {
+  fn default() -> Self {
+    RateLimiter {
+      buckets: Mutex::new(Buckets {
+        by_user: HashMap::new(),
+        pruned_at: Instant::now(),
+      }),
+    }
+  }
+}
+
+impl RateLimiter {
+  /// Takes a token from the bucket of `username` for `route_class`.
+  /// Returns how long to wait for the next token if it is empty
+  pub fn check(
+    &self,
+    username: &str,
+    route_class: RouteClass,
+    rate_limits: &RateLimits,
+  ) -> Result<(), Duration> {
+    let config = route_class.bucket(rate_limits);
+
+    if config.per_minute == 0 {
+      return Ok(());
+    }
+
+    let now = Instant::now();
+    let mut buckets = self
+      .buckets
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    // Full buckets are the same as missing ones
+    if now.duration_since(buckets.pruned_at) >= PRUNE_INTERVAL {
+      buckets.by_user.retain(|(_, route_class), bucket| {
+        let config = route_class.bucket(rate_limits);
+        bucket.refill(config, now);
+        bucket.tokens < config.burst as f64
+      });
+      buckets.pruned_at = now;
+    }
+
+    let bucket = buckets
+      .by_user
+      .entry((username.to_string(), route_class))
+      .or_insert(Bucket {
+        tokens: config.burst as f64,
+        updated_at: now,
+      });
+
+    bucket.refill(config, now);
+
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      Ok(())
+    } else {
+      let per_second = config.per_minute as f64 / 60.0;
+      Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+    }
+  }
+}
+
+/// Caps the calls in flight to the backend of a site, across all users.
+/// Callers wait `queue_timeout` for a free slot before giving up
+pub struct BackendLimiter {
+  site: String,
+  permits: Arc<Semaphore>,
+  max_permits: usize,
+  queue_timeout: Duration,
+}
+
+impl BackendLimiter {
+  pub fn new(site: &str, max_permits: usize, queue_timeout: Duration) -> Self {
+    let max_permits =
+      max_permits.clamp(1, Semaphore::MAX_PERMITS.min(u32::MAX as usize));
+
+    BackendLimiter {
+      site: site.to_string(),
+      permits: Arc::new(Semaphore::new(max_permits)),
+      max_permits,
+      queue_timeout,
+    }
+  }
+
+  /// `max_concurrent_backend_calls` of the site
+  pub fn max_permits(&self) -> usize {
+    self.max_permits
+  }
+
+  /// Reserves `n` backend calls, at most `max_concurrent_backend_calls`.
+  /// Released when the permit is dropped
+  pub async fn acquire(
+    &self,
+    n: usize,
+  ) -> Result<OwnedSemaphorePermit, ApiError> {
+    let n = n.min(self.max_permits) as u32;
+
+    match tokio::time::timeout(
+      self.queue_timeout,
+      self.permits.clone().acquire_many_owned(n),
+    )
+    .await
+    {
+      Ok(Ok(permit)) => Ok(permit),
+      Ok(Err(_)) => Err(ApiError::internal("Backend limiter closed")),
+      Err(_) => {
+        metrics::counter!(
+          "manta_backend_queue_timeouts_total",
+          "site" => self.site.clone(),
+        )
+        .increment(1);
+        Err(ApiError::too_many_requests(
+          format!(
+            "Too many concurrent calls to the backend of site '{}'",
+            self.site
+          ),
+          1,
+        ))
+      }
+    }
+  }
+}
+
+/// Backend call held by `limit_requests` for the request, available to
+/// handlers as a request extension
+#[derive(Clone, Default)]
+pub struct BackendPermit(Arc<Mutex<Option<OwnedSemaphorePermit>>>);
+
+impl BackendPermit {
+  fn set(&self, permit: Option<OwnedSemaphorePermit>) {
+    *self
+      .0
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner()) = permit;
+  }
+
+  /// Replaces the backend call of the request with `n` of them, for handlers
+  /// making calls in parallel. The held call is released first, waiting for
+  /// more while holding it deadlocks once all calls are held by requests
+  /// doing the same
+  pub async fn widen(
+    &self,
+    backend_limiter: &BackendLimiter,
+    n: usize,
+  ) -> Result<(), ApiError> {
+    self.set(None);
+    self.set(Some(backend_limiter.acquire(n).await?));
+
+    Ok(())
+  }
+}
+
+/// Middleware applying `[rate_limits]` to the caller, then holding a backend
+/// call of the selected site for the duration of the request. Websockets and
+/// event streams are long lived and do not hold one. Needs the claims verified by
+/// `require_valid_token`
+pub async fn limit_requests(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  mut request: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let route_class = RouteClass::of(&request);
+  let rate_limits = state
+    .context()
+    .configuration
+    .rate_limits
+    .clone()
+    .unwrap_or_default();
+
+  if let Err(retry_after) =
+    state
+      .rate_limiter
+      .check(claims.username(), route_class, &rate_limits)
+  {
+    metrics::counter!(
+      "manta_rate_limited_total",
+      "class" => route_class.as_str(),
+    )
+    .increment(1);
+
+    return Err(ApiError::too_many_requests(
+      format!(
+        "Rate limit of {} requests exceeded for user '{}'",
+        route_class.as_str(),
+        claims.username()
+      ),
+      retry_after.as_secs_f64().ceil() as u64,
+    ));
+  }
+
+  // Released once the response is ready, or widened by the handler
+  let backend_permit = BackendPermit::default();
+  if route_class != RouteClass::Stream {
+    backend_permit.set(Some(site.backend_limiter.acquire(1).await?));
+  }
+  request.extensions_mut().insert(backend_permit.clone());
+
+  let response = next.run(request).await;
+  backend_permit.set(None);
+
+  Ok(response)
+}
@@ -0,0 +1,460 @@
+use std::{
+  collections::HashMap,
+  hash::{DefaultHasher, Hash, Hasher},
+  sync::Mutex,
+  time::{Duration, Instant, SystemTime},
+};
+
+use axum::{
+  body::{Body, Bytes},
+  extract::{MatchedPath, Request, State},
+  http::{HeaderName, HeaderValue, Method, StatusCode, header},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use axum_extra::headers::{
+  CacheControl, ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+  app_state::{AppState, SelectedSite},
+  jwks::Claims,
+};
+use crate::error::ApiError;
+
+/// Tells whether a response came from the cache, `hit` or `miss`
+const CACHE_STATUS_HEADER: HeaderName = HeaderName::from_static("x-cache");
+
+/// `[response_cache]`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseCache {
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  /// Oldest responses are evicted first once full
+  #[serde(default = "default_max_entries")]
+  pub max_entries: usize,
+  #[serde(default)]
+  pub ttl_secs: CacheTtls,
+}
+
+fn default_enabled() -> bool {
+  true
+}
+
+fn default_max_entries() -> usize {
+  1000
+}
+
+impl Default for ResponseCache {
+  fn default() -> Self {
+    ResponseCache {
+      enabled: default_enabled(),
+      max_entries: default_max_entries(),
+      ttl_secs: CacheTtls::default(),
+    }
+  }
+}
+
+/// How long responses are cached, per endpoint, in seconds. `0` disables
+/// caching of an endpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheTtls {
+  /// `GET /group`
+  #[serde(default = "default_groups_ttl_secs")]
+  pub groups: u64,
+  /// `GET /group/{group}`, includes power and boot details of the members
+  #[serde(default = "default_group_ttl_secs")]
+  pub group: u64,
+  /// `GET /group/{group}/hardware`
+  #[serde(default = "default_hardware_ttl_secs")]
+  pub hardware: u64,
+  /// `GET /redfish` and `GET /redfish/{xname}`
+  #[serde(default = "default_redfish_ttl_secs")]
+  pub redfish: u64,
+}
+
+fn default_groups_ttl_secs() -> u64 {
+  60
+}
+
+fn default_group_ttl_secs() -> u64 {
+  15
+}
+
+fn default_hardware_ttl_secs() -> u64 {
+  600
+}
+
+fn default_redfish_ttl_secs() -> u64 {
+  300
+}
+
+impl Default for CacheTtls {
+  fn default() -> Self {
+    CacheTtls {
+      groups: default_groups_ttl_secs(),
+      group: default_group_ttl_secs(),
+      hardware: default_hardware_ttl_secs(),
+      redfish: default_redfish_ttl_secs(),
+    }
+  }
+}
+
+/// Backend data a cached response is built from. Requests changing it
+/// through manta-ws drop the responses depending on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resource {
+  Groups,
+  BootParameters,
+  Redfish,
+  Power,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Endpoint {
+  Groups,
+  Group,
+  Hardware,
+  Redfish,
+}
+
+impl Endpoint {
+  /// Cached `GET` routes
+  fn of(route: &str) -> Option<Self> {
+    match route {
+      "/group" => Some(Endpoint::Groups),
+      "/group/{group}" => Some(Endpoint::Group),
+      "/group/{group}/hardware" => Some(Endpoint::Hardware),
+      "/redfish" | "/redfish/{xname}" => Some(Endpoint::Redfish),
+      _ => None,
+    }
+  }
+
+  fn ttl(&self, ttls: &CacheTtls) -> Duration {
+    Duration::from_secs(match self {
+      Endpoint::Groups => ttls.groups,
+      Endpoint::Group => ttls.group,
+      Endpoint::Hardware => ttls.hardware,
+      Endpoint::Redfish => ttls.redfish,
+    })
+  }
+
+  fn depends_on(&self, resource: Resource) -> bool {
+    match self {
+      Endpoint::Groups | Endpoint::Hardware => resource == Resource::Groups,
+      Endpoint::Group => matches!(
+        resource,
+        Resource::Groups | Resource::BootParameters | Resource::Power
+      ),
+      Endpoint::Redfish => resource == Resource::Redfish,
+    }
+  }
+}
+
+/// Backend data changed by a request, if any
+fn changed_resource(method: &Method, route: &str) -> Option<Resource> {
+  match (method, route) {
+    (&Method::PUT, "/node-migration/target/{target}/parent/{parent}") => {
+      Some(Resource::Groups)
+    }
+    (&Method::POST | &Method::DELETE, "/bss/boot-parameters") => {
+      Some(Resource::BootParameters)
+    }
+    (&Method::POST, "/redfish") | (&Method::DELETE, "/redfish/{xname}") => {
+      Some(Resource::Redfish)
+    }
+    (&Method::POST, "/power/on" | "/power/off" | "/power/reset") => {
+      Some(Resource::Power)
+    }
+    _ => None,
+  }
+}
+
+/// Callers with the same roles and groups are allowed to see the same data,
+/// so they share cached responses
+fn authorization_scope(claims: &Claims) -> u64 {
+  let mut roles = claims.realm_access.roles.clone();
+  roles.sort();
+  let mut groups = claims.groups.clone();
+  groups.sort();
+
+  let mut hasher = DefaultHasher::new();
+  (roles, groups).hash(&mut hasher);
+  hasher.finish()
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+  site: String,
+  /// Path and query string
+  uri: String,
+  scope: u64,
+}
+
+struct CachedResponse {
+  endpoint: Endpoint,
+  body: Bytes,
+  content_type: Option<HeaderValue>,
+  etag: ETag,
+  /// Last time the body changed
+  last_modified: SystemTime,
+  stored_at: Instant,
+}
+
+/// Responses of the read heavy inventory endpoints. Lives across
+/// configuration reloads
+#[derive(Default)]
+pub struct ResponseCacheStore {
+  responses: Mutex<HashMap<CacheKey, CachedResponse>>,
+}
+
+impl ResponseCacheStore {
+  fn get(
+    &self,
+    key: &CacheKey,
+    ttl: Duration,
+  ) -> Option<(Bytes, Option<HeaderValue>, ETag, SystemTime)> {
+    let responses = self
+      .responses
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    responses
+      .get(key)
+      .filter(|cached| cached.stored_at.elapsed() < ttl)
+      .map(|cached| {
+        (
+          cached.body.clone(),
+          cached.content_type.clone(),
+          cached.etag.clone(),
+          cached.last_modified,
+        )
+      })
+  }
+
+  /// Stores `body` and returns its `Last-Modified`, which only moves when
+  /// `etag` differs from the one cached before
+  fn insert(
+    &self,
+    key: CacheKey,
+    endpoint: Endpoint,
+    body: Bytes,
+    content_type: Option<HeaderValue>,
+    etag: ETag,
+    max_entries: usize,
+  ) -> SystemTime {
+    let mut responses = self
+      .responses
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let last_modified = match responses.get(&key) {
+      Some(cached) if cached.etag == etag => cached.last_modified,
+      _ => SystemTime::now(),
+    };
+
+    if !responses.contains_key(&key) && responses.len() >= max_entries {
+      let oldest_key = responses
+        .iter()
+        .min_by_key(|(_, cached)| cached.stored_at)
+        .map(|(key, _)| key.clone());
+      if let Some(oldest_key) = oldest_key {
+        responses.remove(&oldest_key);
+      }
+    }
+
+    responses.insert(
+      key,
+      CachedResponse {
+        endpoint,
+        body,
+        content_type,
+        etag,
+        last_modified,
+        stored_at: Instant::now(),
+      },
+    );
+
+    last_modified
+  }
+
+  /// Drops the responses of `site` built from `resource`
+  fn invalidate(&self, site: &str, resource: Resource) {
+    self
+      .responses
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      .retain(|key, cached| {
+        key.site != site || !cached.endpoint.depends_on(resource)
+      });
+  }
+
+  /// Drops the responses of `site` showing power status, for changes made
+  /// after the request that started them returned, e.g. by power jobs
+  pub fn invalidate_power(&self, site: &str) {
+    self.invalidate(site, Resource::Power);
+  }
+}
+
+fn etag_of(body: &[u8]) -> Result<ETag, ApiError> {
+  let mut hasher = DefaultHasher::new();
+  body.hash(&mut hasher);
+
+  format!("\"{:016x}\"", hasher.finish())
+    .parse()
+    .map_err(|e| ApiError::internal(format!("Could not build ETag: {}", e)))
+}
+
+/// `304` if the client already has this version, the full response otherwise
+fn respond(
+  request_headers: &axum::http::HeaderMap,
+  body: Bytes,
+  content_type: Option<HeaderValue>,
+  etag: ETag,
+  last_modified: SystemTime,
+  cache_status: &'static str,
+) -> Response {
+  let is_not_modified = match request_headers.typed_get::<IfNoneMatch>() {
+    Some(if_none_match) => !if_none_match.precondition_passes(&etag),
+    None => request_headers.typed_get::<IfModifiedSince>().is_some_and(
+      |if_modified_since| !if_modified_since.is_modified(last_modified),
+    ),
+  };
+
+  let mut response = if is_not_modified {
+    StatusCode::NOT_MODIFIED.into_response()
+  } else {
+    let mut response = Response::new(Body::from(body));
+    if let Some(content_type) = content_type {
+      response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+  };
+
+  let headers = response.headers_mut();
+  headers.typed_insert(etag);
+  headers.typed_insert(LastModified::from(last_modified));
+  // Browsers keep the response but ask again every time, with the
+  // validators above
+  headers.typed_insert(CacheControl::new().with_private().with_no_cache());
+  headers.insert(CACHE_STATUS_HEADER, HeaderValue::from_static(cache_status));
+
+  response
+}
+
+/// Middleware serving the inventory endpoints from cache and dropping cached
+/// responses when a request changes the data they are built from. Clients
+/// sending `Cache-Control: no-cache` get a fresh response. Needs the claims
+/// verified by `require_valid_token`
+pub async fn cache_response(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  matched_path: Option<MatchedPath>,
+  claims: Claims,
+  request: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let config = state
+    .context()
+    .configuration
+    .response_cache
+    .clone()
+    .unwrap_or_default();
+  let route = matched_path
+    .as_ref()
+    .map(|matched_path| matched_path.as_str())
+    .unwrap_or_default();
+
+  if let Some(resource) = changed_resource(request.method(), route) {
+    let response = next.run(request).await;
+
+    // Failed requests may still have changed something in the backend,
+    // except if rejected before reaching it
+    if !response.status().is_client_error() {
+      state.response_cache.invalidate(&site.name, resource);
+    }
+
+    return Ok(response);
+  }
+
+  let endpoint = match Endpoint::of(route) {
+    Some(endpoint) if request.method() == Method::GET => endpoint,
+    _ => return Ok(next.run(request).await),
+  };
+
+  let ttl = endpoint.ttl(&config.ttl_secs);
+
+  if !config.enabled || ttl.is_zero() {
+    return Ok(next.run(request).await);
+  }
+
+  let key = CacheKey {
+    site: site.name.clone(),
+    uri: request
+      .uri()
+      .path_and_query()
+      .map(|path_and_query| path_and_query.to_string())
+      .unwrap_or_default(),
+    scope: authorization_scope(&claims),
+  };
+
+  let request_headers = request.headers().clone();
+
+  let no_cache = request_headers
+    .typed_get::<CacheControl>()
+    .is_some_and(|cache_control| cache_control.no_cache());
+
+  if !no_cache
+    && let Some((body, content_type, etag, last_modified)) =
+      state.response_cache.get(&key, ttl)
+  {
+    metrics::counter!("manta_response_cache_total", "result" => "hit")
+      .increment(1);
+    return Ok(respond(
+      &request_headers,
+      body,
+      content_type,
+      etag,
+      last_modified,
+      "hit",
+    ));
+  }
+
+  metrics::counter!("manta_response_cache_total", "result" => "miss")
+    .increment(1);
+
+  let response = next.run(request).await;
+
+  if response.status() != StatusCode::OK {
+    return Ok(response);
+  }
+
+  let (parts, body) = response.into_parts();
+  let body = axum::body::to_bytes(body, usize::MAX).await.map_err(|e| {
+    ApiError::internal(format!("Could not read response: {}", e))
+  })?;
+  let content_type = parts.headers.get(header::CONTENT_TYPE).cloned();
+
+  let etag = etag_of(&body)?;
+
+  let last_modified = state.response_cache.insert(
+    key,
+    endpoint,
+    body.clone(),
+    content_type.clone(),
+    etag.clone(),
+    config.max_entries.max(1),
+  );
+
+  Ok(respond(
+    &request_headers,
+    body,
+    content_type,
+    etag,
+    last_modified,
+    "miss",
+  ))
+}
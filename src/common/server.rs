@@ -0,0 +1,281 @@
+use std::{
+  fmt,
+  net::{IpAddr, Ipv4Addr, SocketAddr},
+  os::unix::fs::FileTypeExt,
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::Arc,
+  time::Duration,
+};
+
+use anyhow::Result;
+use axum::{Router, extract::ConnectInfo};
+use hyper::{body::Incoming, service::service_fn};
+use hyper_util::{
+  rt::{TokioExecutor, TokioIo},
+  server::conn::auto,
+};
+use tokio::{
+  io::{AsyncRead, AsyncWrite},
+  net::{TcpListener, UnixListener, UnixStream},
+};
+use tower::Service;
+
+use super::{
+  client_identity::ClientCertificate, shutdown::Shutdown, tls::TlsServerConfig,
+};
+
+/// Used when no `--listen` nor `server.listen` is given
+pub const DEFAULT_LISTEN_ADDRESS: SocketAddr =
+  SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 3000);
+
+/// Clients not done with the TLS handshake by then are disconnected
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `host:port` or `unix:/path/to/socket`
+#[derive(Debug, Clone)]
+pub enum ListenAddress {
+  Tcp(SocketAddr),
+  Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+  type Err = String;
+
+  fn from_str(address: &str) -> Result<Self, Self::Err> {
+    match address.strip_prefix("unix:") {
+      Some("") => Err("Unix socket path missing".to_string()),
+      Some(path) => Ok(ListenAddress::Unix(PathBuf::from(path))),
+      None => address.parse().map(ListenAddress::Tcp).map_err(|e| {
+        format!(
+          "'{}' is not 'ip:port' nor 'unix:/path/to/socket': {}",
+          address, e
+        )
+      }),
+    }
+  }
+}
+
+impl fmt::Display for ListenAddress {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ListenAddress::Tcp(address) => write!(f, "{}", address),
+      ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+    }
+  }
+}
+
+pub enum Listener {
+  Tcp(TcpListener),
+  Unix(UnixListener),
+}
+
+impl Listener {
+  pub async fn bind(listen_address: &ListenAddress) -> Result<Self> {
+    match listen_address {
+      ListenAddress::Tcp(address) => {
+        Ok(Listener::Tcp(TcpListener::bind(address).await?))
+      }
+      ListenAddress::Unix(path) => {
+        remove_stale_socket(path).await?;
+        Ok(Listener::Unix(UnixListener::bind(path)?))
+      }
+    }
+  }
+
+  /// Unix socket peers have no address, they get the unspecified one
+  async fn accept(&self) -> std::io::Result<(Connection, SocketAddr)> {
+    match self {
+      Listener::Tcp(listener) => listener
+        .accept()
+        .await
+        .map(|(stream, address)| (Connection::Tcp(stream), address)),
+      Listener::Unix(listener) => listener.accept().await.map(|(stream, _)| {
+        (
+          Connection::Unix(stream),
+          SocketAddr::from(([0, 0, 0, 0], 0)),
+        )
+      }),
+    }
+  }
+}
+
+/// Removes the socket at `path` left behind by a process that did not exit
+/// cleanly. Anything else at `path`, or a socket some process still listens
+/// on, is kept and binding fails
+async fn remove_stale_socket(path: &Path) -> Result<()> {
+  let metadata = match std::fs::symlink_metadata(path) {
+    Ok(metadata) => metadata,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+    Err(e) => return Err(e.into()),
+  };
+
+  if !metadata.file_type().is_socket() {
+    anyhow::bail!("'{}' exists and is not a socket", path.display());
+  }
+
+  if UnixStream::connect(path).await.is_ok() {
+    anyhow::bail!("'{}' is in use by another process", path.display());
+  }
+
+  std::fs::remove_file(path)?;
+
+  Ok(())
+}
+
+enum Connection {
+  Tcp(tokio::net::TcpStream),
+  Unix(UnixStream),
+}
+
+/// Serves `app` on `listener`, over HTTPS if `tls_server_config` is given.
+/// Connection info is added to every request so handlers can use
+/// `ConnectInfo<SocketAddr>` as with `axum::serve`.
+///
+/// Returns once `shutdown` is triggered. Open connections finish their
+/// in-flight requests in tasks tracked by `shutdown`
+pub async fn serve(
+  listener: Listener,
+  app: Router,
+  tls_server_config: Option<Arc<TlsServerConfig>>,
+  shutdown: Shutdown,
+) -> Result<()> {
+  loop {
+    let accept_rslt = tokio::select! {
+      accept_rslt = listener.accept() => accept_rslt,
+      _ = shutdown.triggered() => break,
+    };
+
+    let (connection, remote_addr) = match accept_rslt {
+      Ok(connection) => connection,
+      Err(e) => {
+        // e.g. too many open files, do not spin on it
+        tracing::error!("Could not accept connection: {}", e);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        continue;
+      }
+    };
+
+    let app = app.clone();
+    let tls_server_config = tls_server_config.clone();
+    let connection_shutdown = shutdown.clone();
+
+    shutdown.spawn(async move {
+      match connection {
+        Connection::Tcp(stream) => {
+          serve_stream(
+            stream,
+            remote_addr,
+            app,
+            tls_server_config,
+            connection_shutdown,
+          )
+          .await
+        }
+        Connection::Unix(stream) => {
+          serve_stream(
+            stream,
+            remote_addr,
+            app,
+            tls_server_config,
+            connection_shutdown,
+          )
+          .await
+        }
+      }
+    });
+  }
+
+  // Unix socket files are not removed when the listener is dropped
+  if let Listener::Unix(listener) = &listener
+    && let Ok(address) = listener.local_addr()
+    && let Some(path) = address.as_pathname()
+  {
+    let _ = std::fs::remove_file(path);
+  }
+
+  Ok(())
+}
+
+async fn serve_stream<I>(
+  stream: I,
+  remote_addr: SocketAddr,
+  app: Router,
+  tls_server_config: Option<Arc<TlsServerConfig>>,
+  shutdown: Shutdown,
+) where
+  I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+  let Some(tls_server_config) = tls_server_config else {
+    serve_connection(stream, remote_addr, None, app, shutdown).await;
+    return;
+  };
+
+  let tls_stream = match tokio::time::timeout(
+    HANDSHAKE_TIMEOUT,
+    tls_server_config.acceptor().accept(stream),
+  )
+  .await
+  {
+    Ok(Ok(tls_stream)) => tls_stream,
+    Ok(Err(e)) => {
+      tracing::debug!("TLS handshake with {} failed: {}", remote_addr, e);
+      return;
+    }
+    Err(_) => {
+      tracing::debug!("TLS handshake with {} timed out", remote_addr);
+      return;
+    }
+  };
+
+  // Verified by rustls against 'client_ca_file', leaf first
+  let client_certificate = tls_stream
+    .get_ref()
+    .1
+    .peer_certificates()
+    .and_then(|certificate_chain| certificate_chain.first())
+    .and_then(ClientCertificate::from_der);
+
+  serve_connection(tls_stream, remote_addr, client_certificate, app, shutdown)
+    .await;
+}
+
+async fn serve_connection<I>(
+  stream: I,
+  remote_addr: SocketAddr,
+  client_certificate: Option<ClientCertificate>,
+  app: Router,
+  shutdown: Shutdown,
+) where
+  I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+  let service = service_fn(move |mut request: hyper::Request<Incoming>| {
+    request.extensions_mut().insert(ConnectInfo(remote_addr));
+    if let Some(client_certificate) = &client_certificate {
+      request.extensions_mut().insert(client_certificate.clone());
+    }
+    app.clone().call(request)
+  });
+
+  // Websockets over HTTP/2 use extended CONNECT (RFC 8441)
+  let mut builder = auto::Builder::new(TokioExecutor::new());
+  builder.http2().enable_connect_protocol();
+
+  let connection =
+    builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+  tokio::pin!(connection);
+
+  // On shutdown idle keep-alive connections are closed right away, busy ones
+  // once their current request is answered
+  let connection_rslt = tokio::select! {
+    connection_rslt = connection.as_mut() => connection_rslt,
+    _ = shutdown.triggered() => {
+      connection.as_mut().graceful_shutdown();
+      connection.await
+    }
+  };
+
+  if let Err(e) = connection_rslt {
+    tracing::debug!("Connection with {} closed: {}", remote_addr, e);
+  }
+}
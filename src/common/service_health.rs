@@ -0,0 +1,365 @@
+use std::{
+  collections::{BTreeMap, HashMap, VecDeque},
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::app_state::{AppState, SiteContext};
+
+/// A service not answering by then is reported down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `[service_health]`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceHealth {
+  /// Results younger than this are served from cache, and sites with a
+  /// `health_service_account` are checked this often in the background
+  #[serde(default = "default_poll_interval_secs")]
+  pub poll_interval_secs: u64,
+  /// Checks kept per site
+  #[serde(default = "default_history_size")]
+  pub history_size: usize,
+}
+
+fn default_poll_interval_secs() -> u64 {
+  60
+}
+
+fn default_history_size() -> usize {
+  60
+}
+
+impl Default for ServiceHealth {
+  fn default() -> Self {
+    ServiceHealth {
+      poll_interval_secs: default_poll_interval_secs(),
+      history_size: default_history_size(),
+    }
+  }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceStatus {
+  Up,
+  Down,
+  /// The service rejected the token, which says nothing about its health
+  Unknown,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverallStatus {
+  /// Every service is up
+  Ok,
+  /// Some services are down
+  Degraded,
+  /// Every service is down
+  Down,
+  /// No service accepted the token
+  Unknown,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct ServiceCheck {
+  /// e.g. `hsm`, `bss`, `pcs`, `ims`, `cfs`, `bos`
+  pub name: String,
+  pub status: ServiceStatus,
+  /// Missing if the service did not answer
+  pub http_status: Option<u16>,
+  pub latency_ms: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct ServiceHealthReport {
+  pub checked_at: DateTime<Utc>,
+  pub status: OverallStatus,
+  pub services: Vec<ServiceCheck>,
+}
+
+/// One past check, one column per service
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct ServiceHealthHistoryEntry {
+  pub checked_at: DateTime<Utc>,
+  pub status: OverallStatus,
+  pub services: BTreeMap<String, ServiceStatus>,
+}
+
+/// Body of `GET /health/services`
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct SiteServiceHealth {
+  pub site: String,
+  /// `csm` or `ochami`, decides which services are checked
+  pub backend: String,
+  pub latest: ServiceHealthReport,
+  /// Newest first, `latest` included
+  pub history: Vec<ServiceHealthHistoryEntry>,
+}
+
+#[derive(Default)]
+struct SiteHistory {
+  checked_at: Option<Instant>,
+  reports: VecDeque<ServiceHealthReport>,
+}
+
+/// Latest results and short history of the service checks of every site.
+/// Lives across configuration reloads
+#[derive(Default)]
+pub struct ServiceHealthMonitor {
+  sites: Mutex<HashMap<String, Arc<tokio::sync::Mutex<SiteHistory>>>>,
+}
+
+impl ServiceHealthMonitor {
+  fn site_history(
+    &self,
+    site_name: &str,
+  ) -> Arc<tokio::sync::Mutex<SiteHistory>> {
+    self
+      .sites
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      .entry(site_name.to_string())
+      .or_default()
+      .clone()
+  }
+
+  /// Checks the services of `site` unless the last check is younger than
+  /// `max_age`. Concurrent callers wait for the check in progress instead of
+  /// starting their own
+  pub async fn get(
+    &self,
+    site: &SiteContext,
+    auth_token: &str,
+    max_age: Duration,
+    history_size: usize,
+  ) -> SiteServiceHealth {
+    let site_history = self.site_history(&site.name);
+    let mut site_history = site_history.lock().await;
+
+    let is_fresh = site_history
+      .checked_at
+      .is_some_and(|checked_at| checked_at.elapsed() < max_age);
+
+    // Only reports every service answered are served to other callers, a
+    // token the services reject must not make them look down for everyone
+    let mut unshared_report = None;
+
+    if !is_fresh || site_history.reports.is_empty() {
+      let report = check_services(site, auth_token).await;
+
+      if report
+        .services
+        .iter()
+        .any(|service| service.status == ServiceStatus::Unknown)
+      {
+        unshared_report = Some(report);
+      } else {
+        site_history.checked_at = Some(Instant::now());
+        site_history.reports.push_front(report);
+      }
+    }
+
+    site_history.reports.truncate(history_size.max(1));
+
+    let report_vec: Vec<&ServiceHealthReport> = unshared_report
+      .iter()
+      .chain(site_history.reports.iter())
+      .take(history_size.max(1))
+      .collect();
+
+    SiteServiceHealth {
+      site: site.name.clone(),
+      backend: site.config.backend.clone(),
+      latest: report_vec[0].clone(),
+      history: report_vec
+        .iter()
+        .map(|report| ServiceHealthHistoryEntry {
+          checked_at: report.checked_at,
+          status: report.status,
+          services: report
+            .services
+            .iter()
+            .map(|service| (service.name.clone(), service.status))
+            .collect(),
+        })
+        .collect(),
+    }
+  }
+}
+
+async fn check_service(
+  http_client: &reqwest::Client,
+  base_url: &str,
+  auth_token: &str,
+  name: &str,
+  path: &str,
+) -> ServiceCheck {
+  let start = Instant::now();
+
+  let response_rslt = http_client
+    .get(format!("{}{}", base_url.trim_end_matches('/'), path))
+    .bearer_auth(auth_token)
+    .send()
+    .await;
+
+  let (status, http_status, error) = match response_rslt {
+    Ok(response) if response.status().is_success() => {
+      (ServiceStatus::Up, Some(response.status().as_u16()), None)
+    }
+    Ok(response)
+      if response.status() == reqwest::StatusCode::UNAUTHORIZED
+        || response.status() == reqwest::StatusCode::FORBIDDEN =>
+    {
+      (
+        ServiceStatus::Unknown,
+        Some(response.status().as_u16()),
+        Some(format!("{} rejected the token", path)),
+      )
+    }
+    Ok(response) => (
+      ServiceStatus::Down,
+      Some(response.status().as_u16()),
+      Some(format!(
+        "{} answered with status {}",
+        path,
+        response.status()
+      )),
+    ),
+    Err(e) => (ServiceStatus::Down, None, Some(e.to_string())),
+  };
+
+  ServiceCheck {
+    name: name.to_string(),
+    status,
+    http_status,
+    latency_ms: start.elapsed().as_millis() as u64,
+    error,
+  }
+}
+
+/// Checks in parallel every service the backend of the site provides
+async fn check_services(
+  site: &SiteContext,
+  auth_token: &str,
+) -> ServiceHealthReport {
+  let checked_at = Utc::now();
+  let service_vec = site.backend.service_health_paths();
+
+  let services = match site.http_client(CHECK_TIMEOUT) {
+    Ok(http_client) => {
+      futures::future::join_all(service_vec.iter().map(|(name, path)| {
+        check_service(
+          &http_client,
+          &site.config.shasta_base_url,
+          auth_token,
+          name,
+          path,
+        )
+      }))
+      .await
+    }
+    Err(e) => service_vec
+      .iter()
+      .map(|(name, _)| ServiceCheck {
+        name: name.to_string(),
+        status: ServiceStatus::Down,
+        http_status: None,
+        latency_ms: 0,
+        error: Some(e.to_string()),
+      })
+      .collect(),
+  };
+
+  let count = |status: ServiceStatus| {
+    services
+      .iter()
+      .filter(|service| service.status == status)
+      .count()
+  };
+  let (up, down) = (count(ServiceStatus::Up), count(ServiceStatus::Down));
+
+  // Services with an unknown status are left out
+  let status = if up == 0 && down == 0 {
+    OverallStatus::Unknown
+  } else if down == 0 {
+    OverallStatus::Ok
+  } else if up == 0 {
+    OverallStatus::Down
+  } else {
+    OverallStatus::Degraded
+  };
+
+  ServiceHealthReport {
+    checked_at,
+    status,
+    services,
+  }
+}
+
+/// Checks the services of the sites with a `health_service_account` every
+/// `service_health.poll_interval_secs`, so `GET /health/services` is served
+/// from cache
+pub async fn poll_service_health(state: AppState) {
+  loop {
+    let context = state.context();
+    let service_health = context
+      .configuration
+      .service_health
+      .clone()
+      .unwrap_or_default();
+
+    let site_vec: Vec<Arc<SiteContext>> = context
+      .sites
+      .values()
+      .filter(|site| site.config.health_service_account.is_some())
+      .cloned()
+      .collect();
+
+    futures::future::join_all(site_vec.iter().map(|site| async {
+      let Some(service_account) = &site.config.health_service_account else {
+        return;
+      };
+
+      match site
+        .service_account_tokens
+        .get_token(site, service_account)
+        .await
+      {
+        Ok(auth_token) => {
+          state
+            .service_health
+            .get(
+              site,
+              &auth_token,
+              Duration::ZERO,
+              service_health.history_size,
+            )
+            .await;
+        }
+        Err(e) => tracing::warn!(
+          "Site '{}': could not get token to check service health: {}",
+          site.name,
+          e.detail
+        ),
+      }
+    }))
+    .await;
+
+    // Do not hold on to the configuration between polls
+    drop(site_vec);
+    drop(context);
+
+    let poll_interval = Duration::from_secs(service_health.poll_interval_secs);
+
+    tokio::select! {
+      _ = tokio::time::sleep(poll_interval) => {}
+      _ = state.shutdown.triggered() => return,
+    }
+  }
+}
@@ -0,0 +1,77 @@
+use std::future::Future;
+
+use tokio::signal::unix::{SignalKind, signal};
+use tokio_util::{
+  sync::CancellationToken,
+  task::{TaskTracker, task_tracker::TrackedFuture},
+};
+
+/// Reason sent in the close frame of websockets open at shutdown
+pub const CLOSE_REASON: &str = "server restarting";
+
+/// Tells connections, websockets and background tasks the server is shutting
+/// down, and lets `main` wait for them to finish
+#[derive(Clone, Default)]
+pub struct Shutdown {
+  token: CancellationToken,
+  tracker: TaskTracker,
+}
+
+impl Shutdown {
+  pub fn trigger(&self) {
+    self.token.cancel();
+  }
+
+  pub fn is_triggered(&self) -> bool {
+    self.token.is_cancelled()
+  }
+
+  /// Completes once the shutdown has been triggered
+  pub async fn triggered(&self) {
+    self.token.cancelled().await
+  }
+
+  /// Spawns a task `wait` waits for
+  pub fn spawn<F>(&self, task: F)
+  where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+  {
+    self.tracker.spawn(task);
+  }
+
+  /// Makes `wait` wait for a future spawned by someone else, e.g. a websocket
+  /// handler spawned by axum
+  pub fn track<F: Future>(&self, future: F) -> TrackedFuture<F> {
+    self.tracker.track_future(future)
+  }
+
+  /// Completes once all the tracked tasks are done
+  pub async fn wait(&self) {
+    self.tracker.close();
+    self.tracker.wait().await
+  }
+
+  pub fn pending(&self) -> usize {
+    self.tracker.len()
+  }
+}
+
+/// Completes on SIGTERM (systemd, Kubernetes) or SIGINT (Ctrl+C)
+pub async fn wait_for_signal() {
+  let (mut sigterm, mut sigint) = match (
+    signal(SignalKind::terminate()),
+    signal(SignalKind::interrupt()),
+  ) {
+    (Ok(sigterm), Ok(sigint)) => (sigterm, sigint),
+    (Err(e), _) | (_, Err(e)) => {
+      tracing::error!("Could not install shutdown signal handlers: {}", e);
+      return std::future::pending().await;
+    }
+  };
+
+  tokio::select! {
+    _ = sigterm.recv() => tracing::info!("SIGTERM received, shutting down"),
+    _ = sigint.recv() => tracing::info!("SIGINT received, shutting down"),
+  }
+}
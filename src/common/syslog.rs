@@ -0,0 +1,291 @@
+use std::{
+  net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+  sync::Arc,
+  time::Duration,
+};
+
+use anyhow::Result;
+use rustls::{
+  ClientConfig, RootCertStore,
+  pki_types::{CertificateDer, ServerName, pem::PemObject},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+  io::{AsyncWrite, AsyncWriteExt},
+  net::{TcpStream, UdpSocket, lookup_host},
+  sync::Mutex,
+};
+use tokio_rustls::TlsConnector;
+
+use super::audit::{Audit, AuditEvent};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// A server that stops reading must not block the other audit events
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Syslog {
+  pub server: String,
+  /// Defaults to 514, or 6514 for TLS
+  pub port: Option<u16>,
+  #[serde(default)]
+  pub protocol: SyslogProtocol,
+  /// kern, user, daemon, auth, authpriv, local0 ... local7
+  #[serde(default = "default_facility")]
+  pub facility: String,
+  #[serde(default = "default_app_name")]
+  pub app_name: String,
+  /// CA used to verify the server with TLS. Defaults to the system CAs
+  pub ca_cert_file: Option<String>,
+}
+
+fn default_facility() -> String {
+  "authpriv".to_string()
+}
+
+fn default_app_name() -> String {
+  "manta-ws".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogProtocol {
+  #[default]
+  Udp,
+  Tcp,
+  Tls,
+}
+
+fn get_facility_code(facility: &str) -> Option<u8> {
+  let code = match facility {
+    "kern" => 0,
+    "user" => 1,
+    "mail" => 2,
+    "daemon" => 3,
+    "auth" => 4,
+    "syslog" => 5,
+    "lpr" => 6,
+    "news" => 7,
+    "uucp" => 8,
+    "cron" => 9,
+    "authpriv" => 10,
+    "ftp" => 11,
+    "local0" => 16,
+    "local1" => 17,
+    "local2" => 18,
+    "local3" => 19,
+    "local4" => 20,
+    "local5" => 21,
+    "local6" => 22,
+    "local7" => 23,
+    _ => return None,
+  };
+
+  Some(code)
+}
+
+type SyslogStream = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Sends audit events as RFC 5424 messages. TCP and TLS connections are kept
+/// open and framed with octet counting (RFC 6587)
+pub struct SyslogSender {
+  config: Syslog,
+  facility_code: u8,
+  hostname: String,
+  tls_connector: Option<TlsConnector>,
+  stream: Mutex<Option<SyslogStream>>,
+}
+
+impl SyslogSender {
+  pub fn new(config: &Syslog) -> Result<Arc<Self>> {
+    let facility_code =
+      get_facility_code(&config.facility).ok_or_else(|| {
+        anyhow::anyhow!("Syslog facility '{}' not valid", config.facility)
+      })?;
+
+    let tls_connector = if config.protocol == SyslogProtocol::Tls {
+      Some(get_tls_connector(config.ca_cert_file.as_deref())?)
+    } else {
+      None
+    };
+
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+      .map(|hostname| hostname.trim().to_string())
+      .unwrap_or_else(|_| "-".to_string());
+
+    Ok(Arc::new(SyslogSender {
+      config: config.clone(),
+      facility_code,
+      hostname,
+      tls_connector,
+      stream: Mutex::new(None),
+    }))
+  }
+
+  pub async fn send_event(&self, event: &AuditEvent) {
+    let message = match self.format_message(event) {
+      Ok(message) => message,
+      Err(e) => {
+        tracing::error!("Could not serialize audit event: {}", e);
+        return;
+      }
+    };
+
+    if let Err(e) = self.produce_message(Some(event.action), &message).await {
+      tracing::error!("Could not send audit event to syslog: {}", e);
+      metrics::counter!("manta_audit_syslog_failures_total").increment(1);
+    }
+  }
+
+  /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID - MSG`, the audit event
+  /// in JSON goes in MSG
+  fn format_message(&self, event: &AuditEvent) -> Result<Vec<u8>> {
+    // notice for successful requests, warning otherwise
+    let severity = if event.status < 400 { 5 } else { 4 };
+
+    let header = format!(
+      "<{}>1 {} {} {} {} {} - ",
+      self.facility_code * 8 + severity,
+      event.timestamp,
+      self.hostname,
+      self.config.app_name,
+      std::process::id(),
+      event.action
+    );
+
+    let mut message = header.into_bytes();
+    serde_json::to_writer(&mut message, event)?;
+
+    Ok(message)
+  }
+
+  fn get_port(&self) -> u16 {
+    self.config.port.unwrap_or(match self.config.protocol {
+      SyslogProtocol::Tls => 6514,
+      SyslogProtocol::Udp | SyslogProtocol::Tcp => 514,
+    })
+  }
+
+  async fn connect(&self) -> Result<SyslogStream> {
+    let address = (self.config.server.as_str(), self.get_port());
+
+    let tcp_stream =
+      tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await??;
+
+    match &self.tls_connector {
+      Some(tls_connector) => {
+        let server_name = ServerName::try_from(self.config.server.clone())?;
+        let tls_stream = tokio::time::timeout(
+          CONNECT_TIMEOUT,
+          tls_connector.connect(server_name, tcp_stream),
+        )
+        .await??;
+
+        Ok(Box::new(tls_stream))
+      }
+      None => Ok(Box::new(tcp_stream)),
+    }
+  }
+
+  async fn send_udp(&self, message: &[u8]) -> Result<()> {
+    let Some(address) =
+      lookup_host((self.config.server.as_str(), self.get_port()))
+        .await?
+        .next()
+    else {
+      anyhow::bail!("Could not resolve '{}'", self.config.server);
+    };
+
+    // Local address of the same family as the server
+    let local_address: SocketAddr = match address {
+      SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+      SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let socket = UdpSocket::bind(local_address).await?;
+    socket.send_to(message, address).await?;
+
+    Ok(())
+  }
+
+  async fn send_stream(&self, message: &[u8]) -> Result<()> {
+    let mut frame = format!("{} ", message.len()).into_bytes();
+    frame.extend_from_slice(message);
+
+    let mut stream = self.stream.lock().await;
+
+    // Connection may have been closed by the server since the last message,
+    // reconnect once before giving up
+    for attempt in 0..2 {
+      if stream.is_none() {
+        *stream = Some(self.connect().await?);
+      }
+
+      if let Some(connection) = stream.as_mut() {
+        let write = async {
+          connection.write_all(&frame).await?;
+          connection.flush().await
+        };
+
+        match tokio::time::timeout(WRITE_TIMEOUT, write).await {
+          Ok(Ok(())) => return Ok(()),
+          // Part of the frame may have been written, the connection can not
+          // be used any more
+          Err(_) => {
+            *stream = None;
+            anyhow::bail!("Timed out writing to syslog server");
+          }
+          Ok(Err(e)) if attempt == 0 => {
+            tracing::debug!("Syslog connection lost, reconnecting: {}", e);
+            *stream = None;
+          }
+          Ok(Err(e)) => {
+            *stream = None;
+            return Err(e.into());
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Audit for SyslogSender {
+  async fn produce_message(
+    &self,
+    _key: Option<&str>,
+    data: &[u8],
+  ) -> Result<()> {
+    match self.config.protocol {
+      SyslogProtocol::Udp => self.send_udp(data).await,
+      SyslogProtocol::Tcp | SyslogProtocol::Tls => self.send_stream(data).await,
+    }
+  }
+}
+
+fn get_tls_connector(ca_cert_file: Option<&str>) -> Result<TlsConnector> {
+  let mut root_cert_store = RootCertStore::empty();
+
+  match ca_cert_file {
+    Some(ca_cert_file) => {
+      for cert in CertificateDer::pem_file_iter(ca_cert_file)? {
+        root_cert_store.add(cert?)?;
+      }
+    }
+    None => {
+      let native_certs = rustls_native_certs::load_native_certs();
+      root_cert_store.add_parsable_certificates(native_certs.certs);
+    }
+  }
+
+  let client_config = ClientConfig::builder_with_provider(Arc::new(
+    rustls::crypto::ring::default_provider(),
+  ))
+  .with_safe_default_protocol_versions()?
+  .with_root_certificates(root_cert_store)
+  .with_no_client_auth();
+
+  Ok(TlsConnector::from(Arc::new(client_config)))
+}
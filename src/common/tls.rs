@@ -0,0 +1,192 @@
+use std::{
+  sync::{Arc, RwLock},
+  time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use rustls::{
+  RootCertStore, ServerConfig,
+  pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+  server::WebPkiClientVerifier,
+};
+use serde::{Deserialize, Serialize};
+use tokio_rustls::TlsAcceptor;
+
+/// HTTPS settings, plain HTTP is served when missing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tls {
+  /// PEM certificate chain, server certificate first
+  pub cert_file: String,
+  /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+  pub key_file: String,
+  /// CA used to verify client certificates. Clients are not asked for a
+  /// certificate when missing. See `client_identities`
+  pub client_ca_file: Option<String>,
+  /// Reject clients without a certificate signed by `client_ca_file`
+  #[serde(default)]
+  pub require_client_cert: bool,
+  /// How often the files above are checked for changes, in seconds
+  #[serde(default = "default_reload_interval_secs")]
+  pub reload_interval_secs: u64,
+}
+
+fn default_reload_interval_secs() -> u64 {
+  60
+}
+
+/// TLS configuration in use. Built again from disk when the certificate, key
+/// or client CA files change, new connections pick up the new configuration
+pub struct TlsServerConfig {
+  config: Tls,
+  server_config: RwLock<Arc<ServerConfig>>,
+  modified: RwLock<Vec<Option<SystemTime>>>,
+}
+
+impl TlsServerConfig {
+  pub fn new(config: &Tls) -> Result<Arc<Self>> {
+    let server_config = get_server_config(config)?;
+
+    let tls_server_config = Arc::new(TlsServerConfig {
+      config: config.clone(),
+      server_config: RwLock::new(Arc::new(server_config)),
+      modified: RwLock::new(get_modified_vec(config)),
+    });
+
+    tokio::spawn(reload_periodically(tls_server_config.clone()));
+
+    Ok(tls_server_config)
+  }
+
+  pub fn acceptor(&self) -> TlsAcceptor {
+    TlsAcceptor::from(
+      self
+        .server_config
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone(),
+    )
+  }
+
+  /// Swaps the configuration in if any file changed. The previous
+  /// configuration stays in place if the new files can not be loaded, e.g.
+  /// because the certificate was replaced but not the key yet
+  fn reload_if_modified(&self) {
+    let modified_vec = get_modified_vec(&self.config);
+
+    if *self
+      .modified
+      .read()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      == modified_vec
+    {
+      return;
+    }
+
+    match get_server_config(&self.config) {
+      Ok(server_config) => {
+        *self
+          .server_config
+          .write()
+          .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+          Arc::new(server_config);
+        *self
+          .modified
+          .write()
+          .unwrap_or_else(|poisoned| poisoned.into_inner()) = modified_vec;
+        tracing::info!(
+          "TLS certificate reloaded from {}",
+          self.config.cert_file
+        );
+      }
+      Err(e) => {
+        tracing::error!(
+          "Could not reload TLS certificate, keeping the current one: {}",
+          e
+        );
+      }
+    }
+  }
+}
+
+fn get_modified_vec(config: &Tls) -> Vec<Option<SystemTime>> {
+  [
+    Some(&config.cert_file),
+    Some(&config.key_file),
+    config.client_ca_file.as_ref(),
+  ]
+  .into_iter()
+  .flatten()
+  .map(|path| {
+    std::fs::metadata(path)
+      .and_then(|metadata| metadata.modified())
+      .ok()
+  })
+  .collect()
+}
+
+fn get_server_config(config: &Tls) -> Result<ServerConfig> {
+  let cert_chain = CertificateDer::pem_file_iter(&config.cert_file)
+    .and_then(|cert_iter| cert_iter.collect::<Result<Vec<_>, _>>())
+    .map_err(|e| {
+      anyhow::anyhow!(
+        "Could not read TLS certificate '{}': {}",
+        config.cert_file,
+        e
+      )
+    })?;
+
+  if cert_chain.is_empty() {
+    return Err(anyhow::anyhow!(
+      "No certificate found in '{}'",
+      config.cert_file
+    ));
+  }
+
+  let key = PrivateKeyDer::from_pem_file(&config.key_file).map_err(|e| {
+    anyhow::anyhow!("Could not read TLS key '{}': {}", config.key_file, e)
+  })?;
+
+  let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+  let builder = ServerConfig::builder_with_provider(provider.clone())
+    .with_safe_default_protocol_versions()?;
+
+  let builder = match &config.client_ca_file {
+    Some(client_ca_file) => {
+      let mut root_cert_store = RootCertStore::empty();
+      for cert in CertificateDer::pem_file_iter(client_ca_file)? {
+        root_cert_store.add(cert?)?;
+      }
+
+      let verifier_builder = WebPkiClientVerifier::builder_with_provider(
+        Arc::new(root_cert_store),
+        provider,
+      );
+
+      // Clients without certificate still authenticate with a token
+      let verifier = if config.require_client_cert {
+        verifier_builder.build()?
+      } else {
+        verifier_builder.allow_unauthenticated().build()?
+      };
+
+      builder.with_client_cert_verifier(verifier)
+    }
+    None => builder.with_no_client_auth(),
+  };
+
+  let mut server_config = builder.with_single_cert(cert_chain, key)?;
+  server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+  Ok(server_config)
+}
+
+async fn reload_periodically(tls_server_config: Arc<TlsServerConfig>) {
+  let interval =
+    Duration::from_secs(tls_server_config.config.reload_interval_secs.max(1));
+
+  loop {
+    tokio::time::sleep(interval).await;
+    tls_server_config.reload_if_modified();
+  }
+}
@@ -1,28 +1,331 @@
+use std::any::Any;
 
-// struct Error(manta_backend_dispatcher::error::Error);
+use axum::{
+  Json,
+  http::{HeaderValue, StatusCode, header},
+  response::{IntoResponse, Response},
+};
+use manta_backend_dispatcher::error::Error;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
 
-/* impl IntoResponse for manta_backend_dispatcher::error::Error {
-  fn into_response(self) -> Response {
-    // self.to_string()
-    let status = StatusCode::INTERNAL_SERVER_ERROR;
-    let body = self.to_string();
-    Response::builder()
-      .status(status)
-      .header(header::CONTENT_TYPE, "text/plain")
-      .body(Body::from(body))
-      .unwrap()
-  }
-} */
-
-/* impl IntoResponse for axum::Error {
+use crate::log::*;
+
+/// Stable, machine readable error codes returned in the `code` member of
+/// every problem document. Clients should match on these instead of on the
+/// human readable `detail`
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+  BadRequest,
+  Unauthorized,
+  Forbidden,
+  NotFound,
+  MethodNotAllowed,
+  Conflict,
+  TooManyRequests,
+  BackendError,
+  BackendUnavailable,
+  BackendTimeout,
+  InternalError,
+}
+
+impl ErrorCode {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ErrorCode::BadRequest => "bad_request",
+      ErrorCode::Unauthorized => "unauthorized",
+      ErrorCode::Forbidden => "forbidden",
+      ErrorCode::NotFound => "not_found",
+      ErrorCode::MethodNotAllowed => "method_not_allowed",
+      ErrorCode::Conflict => "conflict",
+      ErrorCode::TooManyRequests => "too_many_requests",
+      ErrorCode::BackendError => "backend_error",
+      ErrorCode::BackendUnavailable => "backend_unavailable",
+      ErrorCode::BackendTimeout => "backend_timeout",
+      ErrorCode::InternalError => "internal_error",
+    }
+  }
+
+  /// Error code used when the status code is all we know about a failure
+  pub fn from_status(status: StatusCode) -> ErrorCode {
+    match status {
+      StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+        ErrorCode::BadRequest
+      }
+      StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+      StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+      StatusCode::NOT_FOUND => ErrorCode::NotFound,
+      StatusCode::METHOD_NOT_ALLOWED => ErrorCode::MethodNotAllowed,
+      StatusCode::CONFLICT => ErrorCode::Conflict,
+      StatusCode::TOO_MANY_REQUESTS => ErrorCode::TooManyRequests,
+      StatusCode::BAD_GATEWAY => ErrorCode::BackendUnavailable,
+      StatusCode::GATEWAY_TIMEOUT => ErrorCode::BackendTimeout,
+      status if status.is_client_error() => ErrorCode::BadRequest,
+      _ => ErrorCode::InternalError,
+    }
+  }
+}
+
+/// Error returned by the HTTP handlers. Rendered as an RFC 7807
+/// `application/problem+json` document
+#[derive(Debug)]
+pub struct ApiError {
+  pub status: StatusCode,
+  pub code: ErrorCode,
+  pub detail: String,
+  /// Seconds sent in `Retry-After`
+  pub retry_after: Option<u64>,
+}
+
+/// RFC 7807 problem document, body of every error response
+#[derive(Serialize, ToSchema)]
+pub struct ProblemDetails<'a> {
+  /// e.g. `urn:manta-ws:error:not_found`, see `code`
+  #[serde(rename = "type")]
+  problem_type: String,
+  /// Reason phrase of the status code
+  title: &'a str,
+  status: u16,
+  detail: &'a str,
+  code: ErrorCode,
+}
+
+impl ApiError {
+  pub fn new(status: StatusCode, code: ErrorCode, detail: String) -> Self {
+    ApiError {
+      status,
+      code,
+      detail,
+      retry_after: None,
+    }
+  }
+
+  pub fn bad_request(detail: impl Into<String>) -> Self {
+    Self::new(
+      StatusCode::BAD_REQUEST,
+      ErrorCode::BadRequest,
+      detail.into(),
+    )
+  }
+
+  pub fn unauthorized(detail: impl Into<String>) -> Self {
+    Self::new(
+      StatusCode::UNAUTHORIZED,
+      ErrorCode::Unauthorized,
+      detail.into(),
+    )
+  }
+
+  pub fn forbidden(detail: impl Into<String>) -> Self {
+    Self::new(StatusCode::FORBIDDEN, ErrorCode::Forbidden, detail.into())
+  }
+
+  pub fn not_found(detail: impl Into<String>) -> Self {
+    Self::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, detail.into())
+  }
+
+  /// `429` telling the client to come back in `retry_after` seconds
+  pub fn too_many_requests(
+    detail: impl Into<String>,
+    retry_after: u64,
+  ) -> Self {
+    ApiError {
+      retry_after: Some(retry_after),
+      ..Self::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::TooManyRequests,
+        detail.into(),
+      )
+    }
+  }
+
+  pub fn internal(detail: impl Into<String>) -> Self {
+    Self::new(
+      StatusCode::INTERNAL_SERVER_ERROR,
+      ErrorCode::InternalError,
+      detail.into(),
+    )
+  }
+
+  /// Maps the status code returned by CSM/OCHAMI to the one we return to the
+  /// client. Server side errors in the backend become a 502 since the
+  /// problem is not in manta-ws
+  fn from_backend_status(status: StatusCode, detail: String) -> Self {
+    match status {
+      StatusCode::BAD_REQUEST
+      | StatusCode::UNAUTHORIZED
+      | StatusCode::FORBIDDEN
+      | StatusCode::NOT_FOUND
+      | StatusCode::CONFLICT => {
+        Self::new(status, ErrorCode::from_status(status), detail)
+      }
+      StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => Self::new(
+        StatusCode::GATEWAY_TIMEOUT,
+        ErrorCode::BackendTimeout,
+        detail,
+      ),
+      status if status.is_client_error() => {
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::BadRequest, detail)
+      }
+      _ => Self::new(StatusCode::BAD_GATEWAY, ErrorCode::BackendError, detail),
+    }
+  }
+
+  fn from_http_client_error(error: &reqwest::Error, detail: String) -> Self {
+    if error.is_timeout() {
+      Self::new(
+        StatusCode::GATEWAY_TIMEOUT,
+        ErrorCode::BackendTimeout,
+        detail,
+      )
+    } else if let Some(status) = error.status() {
+      Self::from_backend_status(status, detail)
+    } else {
+      // Connection refused, DNS, TLS handshake, ...
+      Self::new(
+        StatusCode::BAD_GATEWAY,
+        ErrorCode::BackendUnavailable,
+        detail,
+      )
+    }
+  }
+
+  /// CSM APIs answer errors with a problem document, reuse its status
+  fn from_backend_payload(payload: &Value, detail: String) -> Self {
+    match payload
+      .get("status")
+      .and_then(Value::as_u64)
+      .and_then(|status| StatusCode::from_u16(status as u16).ok())
+    {
+      Some(status) => Self::from_backend_status(status, detail),
+      None => {
+        Self::new(StatusCode::BAD_GATEWAY, ErrorCode::BackendError, detail)
+      }
+    }
+  }
+
+  /// Backends report missing resources with plain messages
+  fn from_message(detail: String) -> Self {
+    let lowercase_detail = detail.to_lowercase();
+
+    if lowercase_detail.contains("not found")
+      || lowercase_detail.contains("does not exist")
+    {
+      Self::not_found(detail)
+    } else if lowercase_detail.contains("already exists") {
+      Self::new(StatusCode::CONFLICT, ErrorCode::Conflict, detail)
+    } else {
+      Self::internal(detail)
+    }
+  }
+}
+
+impl From<Error> for ApiError {
+  fn from(error: Error) -> Self {
+    let detail = error.to_string();
+
+    match &error {
+      Error::AuthenticationTokenNotFound(_) => Self::unauthorized(detail),
+      Error::ConfigurationNotFound(_) => Self::not_found(detail),
+      Error::ConfigurationAlreadyExistsError(_) => {
+        Self::new(StatusCode::CONFLICT, ErrorCode::Conflict, detail)
+      }
+      Error::NetError(e) => Self::from_http_client_error(e, detail),
+      Error::RequestError { response, .. } => {
+        Self::from_http_client_error(response, detail)
+      }
+      Error::CsmError(payload) => Self::from_backend_payload(payload, detail),
+      Error::SerdeError(_) | Error::ConsoleError(_) => {
+        Self::new(StatusCode::BAD_GATEWAY, ErrorCode::BackendError, detail)
+      }
+      Error::IoError(_) => Self::internal(detail),
+      Error::Message(_) => Self::from_message(detail),
+    }
+  }
+}
+
+impl From<csm_rs::error::Error> for ApiError {
+  fn from(error: csm_rs::error::Error) -> Self {
+    use csm_rs::error::Error as CsmError;
+
+    let detail = error.to_string();
+
+    match &error {
+      CsmError::GroupNotFound(_)
+      | CsmError::ImageNotFound(_)
+      | CsmError::ConfigurationDerivativesNotFound(_) => {
+        Self::not_found(detail)
+      }
+      CsmError::ConfigurationAlreadyExists(_)
+      | CsmError::ConfigurationUsedAsRuntimeConfigurationOrUsedToBuildBootImageUsed => {
+        Self::new(StatusCode::CONFLICT, ErrorCode::Conflict, detail)
+      }
+      CsmError::NetError(e) => Self::from_http_client_error(e, detail),
+      CsmError::RequestError { response, .. } => {
+        Self::from_http_client_error(response, detail)
+      }
+      CsmError::CsmError(payload) => {
+        Self::from_backend_payload(payload, detail)
+      }
+      CsmError::SerdeError(_)
+      | CsmError::ConsoleError(_)
+      | CsmError::K8sError(_) => {
+        Self::new(StatusCode::BAD_GATEWAY, ErrorCode::BackendError, detail)
+      }
+      CsmError::IoError(_) => Self::internal(detail),
+      CsmError::Message(_) => Self::from_message(detail),
+    }
+  }
+}
+
+/// Used by `CatchPanicLayer` so a bug in one handler ends up as a 500 for
+/// that request only instead of taking the whole server down
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+  let detail = if let Some(message) = panic.downcast_ref::<String>() {
+    message.as_str()
+  } else if let Some(message) = panic.downcast_ref::<&str>() {
+    message
+  } else {
+    "unknown panic"
+  };
+
+  ApiError::internal(format!("Request handler panicked: {}", detail))
+    .into_response()
+}
+
+impl IntoResponse for ApiError {
   fn into_response(self) -> Response {
-    // self.to_string()
-    let status = StatusCode::INTERNAL_SERVER_ERROR;
-    let body = self.to_string();
-    Response::builder()
-      .status(status)
-      .header(header::CONTENT_TYPE, "text/plain")
-      .body(Body::from(body))
-      .unwrap()
-  }
-} */
+    let title = self.status.canonical_reason().unwrap_or("Unknown error");
+
+    let problem = ProblemDetails {
+      problem_type: format!("urn:manta-ws:error:{}", self.code.as_str()),
+      title,
+      status: self.status.as_u16(),
+      detail: &self.detail,
+      code: self.code,
+    };
+
+    log(format!(
+      "ERROR {} {}: {}",
+      self.status.as_u16(),
+      self.code.as_str(),
+      self.detail
+    ));
+
+    let mut response = (self.status, Json(problem)).into_response();
+    response.headers_mut().insert(
+      header::CONTENT_TYPE,
+      HeaderValue::from_static("application/problem+json"),
+    );
+
+    if let Some(retry_after) = self.retry_after {
+      response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+
+    response
+  }
+}
@@ -1,3 +1,23 @@
-mod get_kernel_parameters;
+pub mod delete_job;
+pub mod get_audit;
+pub mod get_group_power_status;
+pub mod get_health_services;
+pub mod get_job;
+pub mod get_job_events;
+pub mod get_jobs;
+pub mod get_kernel_parameters;
+pub mod get_readyz;
+pub mod post_power;
 
+pub use crate::handlers::delete_job::delete_job;
+pub use crate::handlers::get_audit::get_audit;
+pub use crate::handlers::get_group_power_status::get_group_power_status;
+pub use crate::handlers::get_health_services::get_health_services;
+pub use crate::handlers::get_job::get_job;
+pub use crate::handlers::get_job_events::get_job_events;
+pub use crate::handlers::get_jobs::get_jobs;
 pub use crate::handlers::get_kernel_parameters::get_kernel_parameters;
+pub use crate::handlers::get_readyz::get_readyz;
+pub use crate::handlers::post_power::{
+  post_power_off, post_power_on, post_power_reset,
+};
@@ -0,0 +1,55 @@
+use axum::{
+  Json,
+  extract::{Path, State},
+  http::StatusCode,
+};
+
+use crate::{
+  common::{
+    app_state::{AppState, SelectedSite},
+    authorization::authorize_job,
+    jwks::Claims,
+    power_jobs::{self, PowerJob},
+  },
+  error::ApiError,
+  jwt_utils::AuthToken,
+  openapi::SiteHeader,
+};
+
+/// Cancels a power job. The PCS transition is aborted, nodes already done
+/// keep their new power state
+#[utoipa::path(
+    delete,
+    path = "/jobs/{id}",
+    tag = "power",
+    params(
+        SiteHeader,
+        ("id" = String, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 202, description = "Cancellation requested, the job ends up 'cancelled'", body = PowerJob),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller did not start the job and is not an admin"),
+        (status = 404, description = "Job not found in the selected site"),
+        (status = 409, description = "Job already finished")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn delete_job(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
+  Path(id): Path<String>,
+) -> Result<(StatusCode, Json<PowerJob>), ApiError> {
+  let job = state.power_jobs.get(&site.name, &id)?;
+
+  authorize_job(&site, &claims, &job)?;
+
+  power_jobs::cancel_job(&state, site.clone(), &auth_token, &job).await?;
+
+  Ok((
+    StatusCode::ACCEPTED,
+    Json(state.power_jobs.get(&site.name, &id)?),
+  ))
+}
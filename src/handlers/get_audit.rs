@@ -0,0 +1,157 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use chrono::{DateTime, FixedOffset};
+use manta_backend_dispatcher::interfaces::hsm::group::GroupTrait;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+  common::{
+    app_state::{AppState, SelectedSite},
+    audit_file::AuditRecord,
+    authorization::authorize_audit,
+    jwks::Claims,
+  },
+  error::ApiError,
+  jwt_utils::AuthToken,
+  openapi::SiteHeader,
+};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+/// Filters of `GET /audit`. All of them must match
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+  user: Option<String>,
+  /// Event operates on this xname
+  xname: Option<String>,
+  /// Event operates on a member of this HSM group of the selected site
+  group: Option<String>,
+  action: Option<String>,
+  status: Option<u16>,
+  /// RFC 3339 timestamps, `from` inclusive and `to` exclusive
+  from: Option<String>,
+  to: Option<String>,
+  #[serde(default)]
+  offset: usize,
+  limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditPage {
+  total: usize,
+  offset: usize,
+  limit: usize,
+  /// Newest first
+  events: Vec<AuditRecord>,
+}
+
+fn parse_timestamp(
+  name: &str,
+  timestamp: Option<&str>,
+) -> Result<Option<DateTime<FixedOffset>>, ApiError> {
+  timestamp
+    .map(|timestamp| {
+      DateTime::parse_from_rfc3339(timestamp).map_err(|e| {
+        ApiError::bad_request(format!(
+          "'{}' is not a RFC 3339 timestamp: {}",
+          name, e
+        ))
+      })
+    })
+    .transpose()
+}
+
+/// Audit events recorded in the local audit file. Restricted to the
+/// `auditor_roles` of the selected site
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(SiteHeader, AuditQuery),
+    responses(
+        (status = 200, description = "Audit events, newest first", body = AuditPage),
+        (status = 400, description = "'from' or 'to' is not a RFC 3339 timestamp"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller has none of the 'auditor_roles' of the site")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_audit(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
+  Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, ApiError> {
+  authorize_audit(&site, &claims)?;
+
+  let from = parse_timestamp("from", query.from.as_deref())?;
+  let to = parse_timestamp("to", query.to.as_deref())?;
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+  // Group membership is specific to a site
+  let group_member_vec = match &query.group {
+    Some(group) => Some(
+      site
+        .backend
+        .get_member_vec_from_group_name_vec(&auth_token, &[group.as_str()])
+        .await?,
+    ),
+    None => None,
+  };
+
+  // Auditors of one site must not see the events of the others
+  let site_name = site.name.clone();
+
+  let context = state.context();
+  let Some(audit_file) = &context.audit.file else {
+    return Err(ApiError::not_found("Audit file not configured"));
+  };
+
+  let AuditQuery {
+    user,
+    xname,
+    action,
+    status,
+    ..
+  } = query;
+
+  let (total, record_vec) = audit_file
+    .read_records(
+      move |record| {
+        let timestamp = DateTime::parse_from_rfc3339(&record.timestamp).ok();
+
+        user.as_ref().is_none_or(|user| &record.user == user)
+          && xname
+            .as_ref()
+            .is_none_or(|xname| record.xnames.contains(xname))
+          && action
+            .as_ref()
+            .is_none_or(|action| &record.action == action)
+          && status.is_none_or(|status| record.status == status)
+          && from.is_none_or(|from| timestamp.is_some_and(|ts| ts >= from))
+          && to.is_none_or(|to| timestamp.is_some_and(|ts| ts < to))
+          && record.site == site_name
+          && group_member_vec.as_ref().is_none_or(|member_vec| {
+            record.xnames.iter().any(|xname| member_vec.contains(xname))
+          })
+      },
+      query.offset.saturating_add(limit),
+    )
+    .await
+    .map_err(|e| {
+      ApiError::internal(format!("Could not read audit file: {}", e))
+    })?;
+
+  Ok(Json(AuditPage {
+    total,
+    offset: query.offset,
+    limit,
+    events: record_vec.into_iter().skip(query.offset).collect(),
+  }))
+}
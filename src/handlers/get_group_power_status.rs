@@ -0,0 +1,186 @@
+use axum::{
+  Extension, Json,
+  extract::{Path, Query},
+};
+use futures::{StreamExt, TryStreamExt};
+use manta_backend_dispatcher::{
+  interfaces::{hsm::group::GroupTrait, pcs::PCSTrait},
+  types::pcs::power_status::types::{ManagementState, PowerState, PowerStatus},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+  common::{app_state::SelectedSite, rate_limit::BackendPermit},
+  error::ApiError,
+  jwt_utils::AuthToken,
+  openapi::{PowerStatusSchema, SiteHeader},
+};
+
+/// Xnames per PCS call, keeps the query string of each call short
+const POWER_STATUS_BATCH_SIZE: usize = 200;
+
+/// PCS calls in flight per `get_group_power_status` request
+const POWER_STATUS_CONCURRENCY: usize = 4;
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct PowerStatusQueryParams {
+  /// `on`, `off` or `undefined`
+  pub power_state_filter: Option<String>,
+  /// `available` or `unavailable`
+  pub management_state_filter: Option<String>,
+}
+
+impl PowerStatusQueryParams {
+  /// PCS rejects unknown filters with a less helpful message
+  fn validate(&self) -> Result<(), ApiError> {
+    if let Some(power_state) = &self.power_state_filter
+      && !["on", "off", "undefined"].contains(&power_state.as_str())
+    {
+      return Err(ApiError::bad_request(format!(
+        "'power_state_filter' must be 'on', 'off' or 'undefined', not '{}'",
+        power_state
+      )));
+    }
+
+    if let Some(management_state) = &self.management_state_filter
+      && !["available", "unavailable"].contains(&management_state.as_str())
+    {
+      return Err(ApiError::bad_request(format!(
+        "'management_state_filter' must be 'available' or 'unavailable', not '{}'",
+        management_state
+      )));
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Serialize, ToSchema, Debug, Default)]
+pub struct PowerStateCounts {
+  pub on: usize,
+  pub off: usize,
+  /// PCS could not tell, e.g. the BMC did not answer
+  pub undefined: usize,
+}
+
+#[derive(Serialize, ToSchema, Debug, Default)]
+pub struct ManagementStateCounts {
+  pub available: usize,
+  /// Includes nodes without management state
+  pub unavailable: usize,
+}
+
+/// Body of `GET /group/{group}/power-status`
+#[derive(Serialize, ToSchema, Debug)]
+pub struct GroupPowerStatus {
+  pub group: String,
+  /// Members of the group
+  pub members: usize,
+  /// Nodes matching the filters, i.e. the length of `nodes`
+  pub total: usize,
+  pub power_states: PowerStateCounts,
+  pub management_states: ManagementStateCounts,
+  #[schema(value_type = Vec<PowerStatusSchema>)]
+  pub nodes: Vec<PowerStatus>,
+}
+
+/// Power status of the members of an HSM group, with the number of nodes in
+/// each power and management state. Filters apply to both
+#[utoipa::path(
+    get,
+    path = "/group/{group}/power-status",
+    tag = "power",
+    params(
+        SiteHeader,
+        ("group" = String, Path, description = "HSM group name"),
+        PowerStatusQueryParams
+    ),
+    responses(
+        (status = 200, description = "Power status of the group members", body = GroupPowerStatus),
+        (status = 400, description = "Filter not valid"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 404, description = "HSM group not found"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_group_power_status(
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+  Extension(backend_permit): Extension<BackendPermit>,
+  Path(group): Path<String>,
+  Query(query): Query<PowerStatusQueryParams>,
+) -> Result<Json<GroupPowerStatus>, ApiError> {
+  query.validate()?;
+
+  let backend = &site.backend;
+
+  let member_vec = backend.get_group(&auth_token, &group).await?.get_members();
+
+  let backend_calls = POWER_STATUS_CONCURRENCY
+    .min(site.backend_limiter.max_permits())
+    .min(member_vec.len().div_ceil(POWER_STATUS_BATCH_SIZE).max(1));
+  if backend_calls > 1 {
+    backend_permit
+      .widen(&site.backend_limiter, backend_calls)
+      .await?;
+  }
+
+  let auth_token = auth_token.as_str();
+  let query = &query;
+
+  // Owned batches, borrowed ones trip the `Send` check of axum handlers
+  let batch_vec: Vec<Vec<String>> = member_vec
+    .chunks(POWER_STATUS_BATCH_SIZE)
+    .map(<[String]>::to_vec)
+    .collect();
+
+  let batch_status_vec: Vec<Vec<PowerStatus>> =
+    futures::stream::iter(batch_vec)
+      .map(|xname_vec| async move {
+        backend
+          .power_status(
+            auth_token,
+            &xname_vec,
+            query.power_state_filter.as_deref(),
+            query.management_state_filter.as_deref(),
+          )
+          .await
+          .map(|power_status_all| power_status_all.status)
+      })
+      .buffered(backend_calls)
+      .try_collect()
+      .await?;
+
+  let nodes: Vec<PowerStatus> =
+    batch_status_vec.into_iter().flatten().collect();
+
+  let mut power_states = PowerStateCounts::default();
+  let mut management_states = ManagementStateCounts::default();
+
+  for node in &nodes {
+    match node.power_state {
+      Some(PowerState::On) => power_states.on += 1,
+      Some(PowerState::Off) => power_states.off += 1,
+      Some(PowerState::Undefined) | None => power_states.undefined += 1,
+    }
+
+    match node.management_state {
+      Some(ManagementState::Available) => management_states.available += 1,
+      Some(ManagementState::Unavailable) | None => {
+        management_states.unavailable += 1
+      }
+    }
+  }
+
+  Ok(Json(GroupPowerStatus {
+    group,
+    members: member_vec.len(),
+    total: nodes.len(),
+    power_states,
+    management_states,
+    nodes,
+  }))
+}
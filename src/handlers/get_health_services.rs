@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use axum::{Json, extract::State};
+
+use crate::{
+  common::{
+    app_state::{AppState, SelectedSite},
+    service_health::SiteServiceHealth,
+  },
+  jwt_utils::AuthToken,
+  openapi::SiteHeader,
+};
+
+/// Status of every service the backend of the selected site provides: HSM,
+/// BSS and PCS on OCHAMI, plus IMS, CFS and BOS on CSM. Served from cache if
+/// the last check is younger than `service_health.poll_interval_secs`,
+/// otherwise checked with the caller's token. Checks some service rejected
+/// the token for are not cached
+#[utoipa::path(
+    get,
+    path = "/health/services",
+    tag = "health",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Latest check and history of the site, even if services are down", body = SiteServiceHealth),
+        (status = 401, description = "Token missing or not valid")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_health_services(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+) -> Json<SiteServiceHealth> {
+  let service_health = state
+    .context()
+    .configuration
+    .service_health
+    .clone()
+    .unwrap_or_default();
+
+  Json(
+    state
+      .service_health
+      .get(
+        &site,
+        &auth_token,
+        Duration::from_secs(service_health.poll_interval_secs),
+        service_health.history_size,
+      )
+      .await,
+  )
+}
@@ -0,0 +1,45 @@
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+
+use crate::{
+  common::{
+    app_state::{AppState, SelectedSite},
+    authorization::authorize_job,
+    jwks::Claims,
+    power_jobs::PowerJob,
+  },
+  error::ApiError,
+  openapi::SiteHeader,
+};
+
+/// Power job with the status history of every node
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "power",
+    params(
+        SiteHeader,
+        ("id" = String, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "Job", body = PowerJob),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller did not start the job and is not an admin"),
+        (status = 404, description = "Job not found in the selected site")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_job(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  Path(id): Path<String>,
+) -> Result<Json<PowerJob>, ApiError> {
+  let job = state.power_jobs.get(&site.name, &id)?;
+
+  authorize_job(&site, &claims, &job)?;
+
+  Ok(Json(job))
+}
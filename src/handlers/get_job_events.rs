@@ -0,0 +1,82 @@
+use std::convert::Infallible;
+
+use axum::{
+  extract::{Path, State},
+  response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+
+use crate::{
+  common::{
+    app_state::{AppState, SelectedSite},
+    authorization::authorize_job,
+    jwks::Claims,
+    power_jobs::PowerJob,
+  },
+  error::ApiError,
+  openapi::SiteHeader,
+};
+
+/// Server-sent events with the whole job, first as it is and then every
+/// time it changes. The stream ends once the job is no longer running
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}/events",
+    tag = "power",
+    params(
+        SiteHeader,
+        ("id" = String, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "'job' events, the data of each is the job", content_type = "text/event-stream", body = PowerJob),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller did not start the job and is not an admin"),
+        (status = 404, description = "Job not found in the selected site")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_job_events(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+  let job = state.power_jobs.get(&site.name, &id)?;
+
+  authorize_job(&site, &claims, &job)?;
+
+  let Some(mut receiver) = state.power_jobs.subscribe(&id) else {
+    return Err(ApiError::not_found(format!("Job '{}' not found", id)));
+  };
+
+  // Sends the current state first
+  receiver.mark_changed();
+
+  let shutdown = state.shutdown.clone();
+
+  let stream = futures::stream::unfold(Some(receiver), move |receiver| {
+    let shutdown = shutdown.clone();
+
+    async move {
+      let mut receiver = receiver?;
+
+      tokio::select! {
+        changed = receiver.changed() => changed.ok()?,
+        _ = shutdown.triggered() => return None,
+      }
+
+      let job = receiver.borrow_and_update().clone();
+
+      let event = Event::default()
+        .event("job")
+        .json_data(&job)
+        .unwrap_or_else(|e| {
+          Event::default().event("error").data(e.to_string())
+        });
+
+      Some((Ok(event), job.status.is_active().then_some(receiver)))
+    }
+  });
+
+  Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,42 @@
+use axum::{Json, extract::State};
+
+use crate::{
+  common::{
+    app_state::{AppState, SelectedSite},
+    authorization::authorize_job,
+    jwks::Claims,
+    power_jobs::PowerJob,
+  },
+  error::ApiError,
+  openapi::SiteHeader,
+};
+
+/// Power jobs of the selected site the caller may see: the ones they
+/// started, or all of them for admins of the site
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    tag = "power",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Jobs, newest first", body = [PowerJob]),
+        (status = 401, description = "Token missing or not valid")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn get_jobs(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+) -> Result<Json<Vec<PowerJob>>, ApiError> {
+  Ok(Json(
+    state
+      .power_jobs
+      .list()
+      .into_iter()
+      .filter(|job| {
+        job.site == site.name && authorize_job(&site, &claims, job).is_ok()
+      })
+      .collect(),
+  ))
+}
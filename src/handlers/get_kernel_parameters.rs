@@ -1,5 +1,5 @@
 use axum::{
-  extract::Query,
+  extract::{Query, State},
   http::{HeaderMap, StatusCode},
   response::{IntoResponse, Response},
 };
@@ -7,9 +7,30 @@ use axum::{
 use std::collections::HashMap;
 
 use crate::backend_api::*;
+use crate::common::app_state::AppState;
 use crate::http_response::*;
 
+/// Kernel parameters of the given nodes. The site is picked with `dc` instead
+/// of the `X-Manta-Site` header
+#[utoipa::path(
+    get,
+    path = "/kernel-parameters",
+    tag = "bss",
+    params(
+        ("dc" = String, Query, description = "Site"),
+        ("node" = Vec<String>, Query, description = "Xname, may be repeated")
+    ),
+    responses(
+        (status = 200, description = "Kernel parameters by xname", body = HashMap<String, String>),
+        (status = 400, description = "'dc' or 'node' missing, or unknown query parameter"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 404, description = "Site or nodes not found"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []))
+)]
 pub async fn get_kernel_parameters(
+  State(state): State<AppState>,
   headers: HeaderMap,
   Query(params): Query<Vec<(String, String)>>,
 ) -> Response {
@@ -18,16 +39,28 @@ pub async fn get_kernel_parameters(
     Err(e) => return e,
   };
 
-  let dc = input_map.get("dc").unwrap().first().unwrap();
-  let xnames = input_map.get("node").unwrap();
+  // Presence of both keys is checked by compute_get_entries
+  let (Some(dc), Some(xnames)) = (
+    input_map.get("dc").and_then(|dc_vec| dc_vec.first()),
+    input_map.get("node"),
+  ) else {
+    return error_respond(
+      StatusCode::BAD_REQUEST,
+      "\"dc\" and \"node\" must be specified!".to_string(),
+    );
+  };
 
-  let cfg = match get_req_cfg(&headers, dc.to_string()) {
+  let cfg = match get_req_cfg(&state.context(), &headers, dc.to_string()).await
+  {
     Ok(good) => good,
     Err(e) => return e,
   };
 
-  let kernel_params =
-    get_kernel_parameters_from_mesa(cfg, &xnames).await.unwrap();
+  let kernel_params = match get_kernel_parameters_from_mesa(cfg, &xnames).await
+  {
+    Ok(good) => good,
+    Err((code, e)) => return error_respond(code, e),
+  };
 
   if kernel_params.len() != xnames.len() {
     let difference = xnames.len() - kernel_params.len();
@@ -52,8 +85,10 @@ pub async fn get_kernel_parameters(
     );
   };
 
-  let json = serde_json::to_string_pretty(&kernel_params).unwrap();
-  json.into_response()
+  match serde_json::to_string_pretty(&kernel_params) {
+    Ok(json) => json.into_response(),
+    Err(e) => error_respond(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+  }
 }
 
 fn compute_get_entries(
@@ -0,0 +1,256 @@
+use std::{
+  future::Future,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use axum::{Json, extract::State, http::StatusCode};
+use futures::{FutureExt, future::BoxFuture};
+use manta_backend_dispatcher::types::K8sAuth;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::common::{
+  app_state::{AppState, SiteContext},
+  config::{self, types::MantaConfiguration},
+};
+
+/// Each probe gives up after this long, so the whole check stays below the
+/// usual Kubernetes probe timeout
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The route needs no token, probe results are reused for this long so
+/// callers can not flood the backends
+const PROBE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Results of the last probes and when they finished
+static PROBE_CACHE: Mutex<Option<(Instant, Vec<ComponentStatus>)>> =
+  Mutex::const_new(None);
+
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessStatus {
+  Ready,
+  NotReady,
+  /// Shutdown started, the load balancer should stop sending requests
+  ShuttingDown,
+}
+
+#[derive(Serialize, ToSchema, Clone)]
+pub struct ComponentStatus {
+  /// e.g. `config`, `root_ca:alps`, `backend:alps`, `vault:alps`, `kafka`
+  name: String,
+  ok: bool,
+  /// Whether the server is ready only if this component is ok. Backends
+  /// and Vault of a site are informational, a site being down must not take
+  /// the other sites out of the load balancer
+  required: bool,
+  latency_ms: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+  status: ReadinessStatus,
+  components: Vec<ComponentStatus>,
+}
+
+/// Errors are only logged, the route is public
+async fn probe<F>(name: String, required: bool, check: F) -> ComponentStatus
+where
+  F: Future<Output = Result<(), String>>,
+{
+  let start = Instant::now();
+
+  let check_rslt = match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+    Ok(check_rslt) => check_rslt,
+    Err(_) => Err(format!("No answer after {}s", PROBE_TIMEOUT.as_secs())),
+  };
+
+  if let Err(e) = &check_rslt {
+    tracing::warn!("Readiness probe '{}' failed: {}", name, e);
+  }
+
+  ComponentStatus {
+    name,
+    ok: check_rslt.is_ok(),
+    required,
+    latency_ms: start.elapsed().as_millis() as u64,
+  }
+}
+
+/// The configuration file on disk still parses, so a reload or a restart
+/// would succeed
+async fn check_config() -> Result<(), String> {
+  config::get_configuration()
+    .await
+    .map_err(|e| e.to_string())?
+    .try_deserialize::<MantaConfiguration>()
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+async fn check_root_ca(site: Arc<SiteContext>) -> Result<(), String> {
+  let root_cert =
+    config::get_csm_root_cert_content(&site.config.root_ca_cert_file)
+      .map_err(|e| format!("'{}': {}", site.config.root_ca_cert_file, e))?;
+
+  reqwest::Certificate::from_pem(&root_cert)
+    .map(|_| ())
+    .map_err(|e| format!("'{}': {}", site.config.root_ca_cert_file, e))
+}
+
+/// Any HTTP answer counts, the probe has no token to call the API with
+async fn check_backend(site: Arc<SiteContext>) -> Result<(), String> {
+  site
+    .http_client(PROBE_TIMEOUT)
+    .map_err(|e| e.to_string())?
+    .get(&site.config.shasta_base_url)
+    .send()
+    .await
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// Vault answers `/v1/sys/health` with 200 when active, 429 or 473 when
+/// standby, 501 when not initialized and 503 when sealed
+async fn check_vault(
+  site: Arc<SiteContext>,
+  vault_base_url: String,
+) -> Result<(), String> {
+  let response = site
+    .http_client(PROBE_TIMEOUT)
+    .map_err(|e| e.to_string())?
+    .get(format!(
+      "{}/v1/sys/health",
+      vault_base_url.trim_end_matches('/')
+    ))
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match response.status().as_u16() {
+    200 | 429 | 473 => Ok(()),
+    501 => Err("Vault is not initialized".to_string()),
+    503 => Err("Vault is sealed".to_string()),
+    status => Err(format!("Vault answered with status {}", status)),
+  }
+}
+
+/// Vault used to fetch the k8s credentials of the site, if any
+fn get_vault_base_url(site: &SiteContext) -> Option<String> {
+  match site.config.k8s.as_ref().map(|k8s| &k8s.authentication) {
+    Some(K8sAuth::Vault { base_url }) => Some(base_url.clone()),
+    _ => site.config.vault_base_url.clone(),
+  }
+}
+
+/// Probes every component in parallel
+async fn probe_components(state: &AppState) -> Vec<ComponentStatus> {
+  let context = state.context();
+
+  let mut site_vec: Vec<Arc<SiteContext>> =
+    context.sites.values().cloned().collect();
+  site_vec.sort_by(|a, b| a.name.cmp(&b.name));
+
+  let mut probes: Vec<BoxFuture<ComponentStatus>> =
+    vec![probe("config".to_string(), true, check_config()).boxed()];
+
+  for site in site_vec {
+    probes.push(
+      probe(
+        format!("root_ca:{}", site.name),
+        true,
+        check_root_ca(site.clone()),
+      )
+      .boxed(),
+    );
+
+    probes.push(
+      probe(
+        format!("backend:{}", site.name),
+        false,
+        check_backend(site.clone()),
+      )
+      .boxed(),
+    );
+
+    if let Some(vault_base_url) = get_vault_base_url(&site) {
+      probes.push(
+        probe(
+          format!("vault:{}", site.name),
+          false,
+          check_vault(site.clone(), vault_base_url),
+        )
+        .boxed(),
+      );
+    }
+  }
+
+  if let Some(kafka) = context.audit.kafka.clone() {
+    probes.push(
+      probe("kafka".to_string(), true, async move {
+        kafka.check_brokers(PROBE_TIMEOUT).await
+      })
+      .boxed(),
+    );
+  }
+
+  futures::future::join_all(probes).await
+}
+
+/// Checks the dependencies needed to serve requests: configuration file,
+/// root CA of every site, and the Kafka brokers when audit events are sent to
+/// Kafka. Backend and Vault of every site are reported but do not make the
+/// server not ready. Results are cached for a few seconds
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every required component is ok", body = Readiness),
+        (status = 503, description = "Some required component is not ok, or shutdown started", body = Readiness)
+    )
+)]
+pub async fn get_readyz(
+  State(state): State<AppState>,
+) -> (StatusCode, Json<Readiness>) {
+  if state.shutdown.is_triggered() {
+    return (
+      StatusCode::SERVICE_UNAVAILABLE,
+      Json(Readiness {
+        status: ReadinessStatus::ShuttingDown,
+        components: Vec::new(),
+      }),
+    );
+  }
+
+  let components = {
+    // Concurrent callers wait for the probes in progress instead of starting
+    // their own
+    let mut probe_cache = PROBE_CACHE.lock().await;
+
+    match probe_cache.as_ref() {
+      Some((probed_at, components))
+        if probed_at.elapsed() < PROBE_CACHE_TTL =>
+      {
+        components.clone()
+      }
+      _ => {
+        let components = probe_components(&state).await;
+        *probe_cache = Some((Instant::now(), components.clone()));
+        components
+      }
+    }
+  };
+
+  let (status_code, status) = if components
+    .iter()
+    .all(|component| component.ok || !component.required)
+  {
+    (StatusCode::OK, ReadinessStatus::Ready)
+  } else {
+    (StatusCode::SERVICE_UNAVAILABLE, ReadinessStatus::NotReady)
+  };
+
+  (status_code, Json(Readiness { status, components }))
+}
@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use axum::{
+  Json,
+  extract::State,
+  http::{StatusCode, header},
+  response::{IntoResponse, Response},
+};
+use manta_backend_dispatcher::interfaces::hsm::{
+  component::ComponentTrait, group::GroupTrait,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::common::{
+  app_state::{AppState, SelectedSite, SiteContext},
+  audit::AuditedXnames,
+  authorization::authorize_xnames,
+  jwks::Claims,
+  power_jobs::{self, PowerAction, PowerJob},
+};
+use crate::error::ApiError;
+use crate::jwt_utils::AuthToken;
+use crate::openapi::SiteHeader;
+
+/// Nodes a power action applies to. Exactly one of `xnames`, `hostlist` and
+/// `group` must be set
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct PowerRequest {
+  /// e.g. `["x1000c0s0b0n0", "x1000c0s0b0n1"]`
+  pub xnames: Option<Vec<String>>,
+  /// Hostlist expression of xnames or NIDs, e.g. `x1000c0s0b0n[0-1]` or
+  /// `nid[000001-000004]`
+  pub hostlist: Option<String>,
+  /// HSM group, the action applies to all its members
+  pub group: Option<String>,
+  /// Hard power off or restart instead of a graceful one. Ignored by `on`
+  #[serde(default)]
+  pub force: bool,
+}
+
+/// Expands the target of `request` to a list of xnames, without duplicates
+pub async fn resolve_xnames(
+  site: &SiteContext,
+  auth_token: &str,
+  request: &PowerRequest,
+) -> Result<Vec<String>, ApiError> {
+  let xname_vec = match (&request.xnames, &request.hostlist, &request.group) {
+    (Some(xname_vec), None, None) => xname_vec
+      .iter()
+      .map(|xname| xname.trim().to_string())
+      .collect(),
+    (None, Some(hostlist), None) => {
+      let hostlist = hostlist.trim();
+
+      if hostlist.to_lowercase().starts_with("nid") {
+        site
+          .backend
+          .nid_to_xname(auth_token, hostlist, false)
+          .await?
+      } else {
+        hostlist_parser::parse(hostlist).map_err(|e| {
+          ApiError::bad_request(format!(
+            "Hostlist '{}' not valid: {}",
+            hostlist, e
+          ))
+        })?
+      }
+    }
+    (None, None, Some(group)) => site
+      .backend
+      .get_group(auth_token, group)
+      .await?
+      .get_members(),
+    _ => {
+      return Err(ApiError::bad_request(
+        "Exactly one of 'xnames', 'hostlist' and 'group' must be set",
+      ));
+    }
+  };
+
+  let mut unique_xname_vec: Vec<String> = Vec::new();
+  for xname in xname_vec {
+    if !xname.is_empty() && !unique_xname_vec.contains(&xname) {
+      unique_xname_vec.push(xname);
+    }
+  }
+
+  if unique_xname_vec.is_empty() {
+    return Err(ApiError::bad_request("No nodes to operate on"));
+  }
+
+  Ok(unique_xname_vec)
+}
+
+/// Checks the caller manages every node of `request`, then starts a job
+/// running `action` on them. Answers `202` with the job right away
+async fn start_power_job(
+  state: &AppState,
+  site: Arc<SiteContext>,
+  claims: &Claims,
+  auth_token: &str,
+  action: PowerAction,
+  request: &PowerRequest,
+) -> Result<Response, ApiError> {
+  let xname_vec = resolve_xnames(&site, auth_token, request).await?;
+
+  // Audited even if the caller may not operate on them
+  let audited_xnames = AuditedXnames(xname_vec.clone());
+
+  let mut response =
+    match authorize_xnames(&site, claims, auth_token, &xname_vec).await {
+      Ok(()) => {
+        tracing::info!(
+          "Power {:?} (force: {}) nodes {:?}",
+          action,
+          request.force,
+          xname_vec
+        );
+
+        let job = power_jobs::start_job(
+          state,
+          site,
+          claims.username(),
+          auth_token,
+          action,
+          request.force,
+          xname_vec,
+        )
+        .await;
+
+        (
+          StatusCode::ACCEPTED,
+          [(header::LOCATION, format!("/jobs/{}", job.id))],
+          Json(job),
+        )
+          .into_response()
+      }
+      Err(e) => e.into_response(),
+    };
+
+  response.extensions_mut().insert(audited_xnames);
+
+  Ok(response)
+}
+
+/// Powers on nodes in the background, see `GET /jobs/{id}`
+#[utoipa::path(
+    post,
+    path = "/power/on",
+    tag = "power",
+    params(SiteHeader),
+    request_body = PowerRequest,
+    responses(
+        (status = 202, description = "Job started, 'Location' points to it", body = PowerJob),
+        (status = 400, description = "Target missing, ambiguous or not valid"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage some of the nodes"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn post_power_on(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
+  Json(request): Json<PowerRequest>,
+) -> Result<Response, ApiError> {
+  start_power_job(
+    &state,
+    site,
+    &claims,
+    &auth_token,
+    PowerAction::On,
+    &request,
+  )
+  .await
+}
+
+/// Powers off nodes in the background, gracefully unless `force` is set.
+/// See `GET /jobs/{id}`
+#[utoipa::path(
+    post,
+    path = "/power/off",
+    tag = "power",
+    params(SiteHeader),
+    request_body = PowerRequest,
+    responses(
+        (status = 202, description = "Job started, 'Location' points to it", body = PowerJob),
+        (status = 400, description = "Target missing, ambiguous or not valid"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage some of the nodes"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn post_power_off(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
+  Json(request): Json<PowerRequest>,
+) -> Result<Response, ApiError> {
+  start_power_job(
+    &state,
+    site,
+    &claims,
+    &auth_token,
+    PowerAction::Off,
+    &request,
+  )
+  .await
+}
+
+/// Restarts nodes in the background, gracefully unless `force` is set.
+/// See `GET /jobs/{id}`
+#[utoipa::path(
+    post,
+    path = "/power/reset",
+    tag = "power",
+    params(SiteHeader),
+    request_body = PowerRequest,
+    responses(
+        (status = 202, description = "Job started, 'Location' points to it", body = PowerJob),
+        (status = 400, description = "Target missing, ambiguous or not valid"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage some of the nodes"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+pub async fn post_power_reset(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
+  Json(request): Json<PowerRequest>,
+) -> Result<Response, ApiError> {
+  start_power_job(
+    &state,
+    site,
+    &claims,
+    &auth_token,
+    PowerAction::Reset,
+    &request,
+  )
+  .await
+}
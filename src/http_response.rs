@@ -2,9 +2,8 @@ use axum::{
   http::StatusCode,
   response::{IntoResponse, Response},
 };
-use std::collections::HashMap;
 
-use crate::log::*;
+use crate::error::{ApiError, ErrorCode};
 
 pub struct ErrorResponse {
   pub code: axum::http::StatusCode,
@@ -20,18 +19,9 @@ impl ErrorResponse {
   }
 
   pub fn respond(self) -> Response {
-    let mut message = HashMap::new();
-    message.insert("error", self.reason.to_owned());
+    let code = ErrorCode::from_status(self.code);
 
-    let json_message;
-    match serde_json::to_string(&message) {
-      Ok(good_message) => json_message = good_message,
-      Err(_) => json_message = "{ error: \"Unknown error\" }".to_string(),
-    }
-
-    log(format!("ERROR {}", self.reason));
-
-    (self.code, json_message).into_response()
+    ApiError::new(self.code, code, self.reason).into_response()
   }
 }
 
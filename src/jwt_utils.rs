@@ -1,15 +1,112 @@
-use std::error::Error;
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  http::{HeaderMap, HeaderValue, header::AUTHORIZATION, request::Parts},
+  middleware::Next,
+  response::Response,
+};
 
-use base64::decode;
-use serde_json::Value;
+use crate::common::{
+  app_state::{AppState, SelectedSite},
+  client_identity::{ClientCertificate, find_client_identity},
+  jwks::Claims,
+};
+use crate::error::ApiError;
 
-pub fn get_claims_from_jwt_token(token: &str) -> Result<Value, Box<dyn Error>> {
-  let base64_claims = token.split(".").nth(1).expect("JWT Token not valid");
+/// Token sent by the client in the `Authorization: Bearer <token>` header
+pub struct AuthToken(pub String);
 
-  let claims_u8 = decode(base64_claims).unwrap();
+impl<S: Send + Sync> FromRequestParts<S> for AuthToken {
+  type Rejection = ApiError;
 
-  Ok(
-    serde_json::from_str::<Value>(std::str::from_utf8(&claims_u8).unwrap())
-      .unwrap(),
-  )
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    get_auth_token(&parts.headers).map(AuthToken)
+  }
+}
+
+pub fn get_auth_token(headers: &HeaderMap) -> Result<String, ApiError> {
+  let auth_header = headers
+    .get("authorization")
+    .ok_or_else(|| ApiError::unauthorized("Authentication header missing"))?
+    .to_str()
+    .map_err(|_| ApiError::unauthorized("Authentication header not valid"))?;
+
+  match auth_header.split_once(" ") {
+    Some((_, auth_token)) if !auth_token.trim().is_empty() => {
+      Ok(auth_token.trim().to_string())
+    }
+    _ => Err(ApiError::unauthorized("Authentication token missing")),
+  }
+}
+
+/// Claims verified by `require_valid_token`
+impl<S: Send + Sync> FromRequestParts<S> for Claims {
+  type Rejection = ApiError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    parts
+      .extensions
+      .get::<Claims>()
+      .cloned()
+      .ok_or_else(|| ApiError::unauthorized("JWT token not verified"))
+  }
+}
+
+/// Middleware rejecting requests which token is not signed by the Keycloak of
+/// the selected site. Verified claims are stored in the request extensions.
+///
+/// Requests without token are accepted if the client presented a certificate
+/// mapped to one of the `client_identities`. The token of the identity's
+/// service account is then added to the request for the backend calls
+pub async fn require_valid_token(
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  mut request: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let claims = match get_auth_token(request.headers()) {
+    Ok(auth_token) => site.jwt_verifier.verify(&auth_token).await?,
+    Err(e) => {
+      let Some(client_certificate) =
+        request.extensions().get::<ClientCertificate>().cloned()
+      else {
+        return Err(e);
+      };
+
+      let context = state.context();
+      let client_identity = find_client_identity(
+        &context.configuration.client_identities,
+        &client_certificate,
+      )
+      .ok_or_else(|| {
+        ApiError::unauthorized(format!(
+          "Client certificate '{}' not mapped to any identity",
+          client_certificate.subject
+        ))
+      })?;
+
+      let auth_token = site
+        .service_account_tokens
+        .get_token(&site, &client_identity.name)
+        .await?;
+
+      let authorization_header =
+        HeaderValue::from_str(&format!("Bearer {}", auth_token))
+          .map_err(|_| ApiError::internal("Service account token not valid"))?;
+      request
+        .headers_mut()
+        .insert(AUTHORIZATION, authorization_header);
+
+      client_identity.claims(&client_certificate)
+    }
+  };
+
+  request.extensions_mut().insert(claims);
+
+  Ok(next.run(request).await)
 }
@@ -1,4 +1,5 @@
 mod backend_api;
+mod cli;
 mod commands;
 mod common;
 mod error;
@@ -7,6 +8,7 @@ mod http_response;
 mod jwt_utils;
 mod log;
 mod manta_backend_dispatcher;
+mod openapi;
 
 use ::manta_backend_dispatcher::{
   interfaces::{
@@ -16,34 +18,34 @@ use ::manta_backend_dispatcher::{
   types::{K8sAuth, K8sDetails, bss::BootParameters},
 };
 use axum::{
-  Json, Router, debug_handler,
+  Extension, Json, Router,
   extract::{
-    ConnectInfo, Path, Query, WebSocketUpgrade,
-    ws::{Message, Utf8Bytes, WebSocket},
+    ConnectInfo, Path, Query, State, WebSocketUpgrade,
+    ws::{CloseFrame, Message, Utf8Bytes, WebSocket, close_code},
   },
-  http::{HeaderMap, StatusCode},
+  http::{HeaderMap, HeaderValue, StatusCode, header},
+  middleware,
   response::{IntoResponse, Response},
-  routing::{delete, get, post, put},
+  routing::{any, delete, get, post, put},
 };
 use axum_extra::{TypedHeader, headers};
 use bytes::Bytes;
-use common::config::types::MantaConfiguration;
-use config::Config;
+use common::app_state::{
+  AppContext, AppState, SelectedSite, SiteContext, reload_on_sighup,
+};
 use csm_rs::{
   common::vault::http_client::fetch_shasta_k8s_secrets_from_vault,
   hsm::hw_inventory::hw_component::types::NodeSummary,
 };
-use directories::ProjectDirs;
 use futures::{AsyncBufReadExt, SinkExt, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
-  fs::File, io::Read, net::SocketAddr, ops::ControlFlow, path::PathBuf,
-  sync::Arc,
+  net::SocketAddr, ops::ControlFlow, path::PathBuf, sync::Arc, time::Duration,
 };
 use tokio::{io::AsyncWriteExt, sync::Semaphore};
 use tower_http::{
-  cors::CorsLayer,
+  catch_panic::CatchPanicLayer,
   services::ServeDir,
   trace::{DefaultMakeSpan, TraceLayer},
 };
@@ -51,59 +53,197 @@ use tracing_subscriber::{
   prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt,
 };
 
-use crate::jwt_utils::get_claims_from_jwt_token;
-
+use crate::cli::{Cli, Command};
+use crate::common::audit::audit_request;
+use crate::common::authorization::{
+  authorize_boot_parameters, authorize_group, authorize_xnames,
+};
+use crate::common::config;
+use crate::common::cors;
+use crate::common::jwks::Claims;
+use crate::common::power_jobs;
+use crate::common::prometheus::{self, ActiveWebsocket, track_request};
+use crate::common::rate_limit::{BackendPermit, limit_requests};
+use crate::common::response_cache::cache_response;
+use crate::common::server::{self, ListenAddress, Listener};
+use crate::common::service_health;
+use crate::common::shutdown::{self, Shutdown};
+use crate::common::tls::TlsServerConfig;
+use crate::error::{ApiError, ErrorCode, panic_response};
+use crate::http_response::error_respond;
+use crate::jwt_utils::{AuthToken, get_auth_token, require_valid_token};
+use clap::Parser;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
 use tokio_util::io::ReaderStream;
 
-use anyhow::{Result, bail};
+use anyhow::Result;
 
+use crate::handlers::get_group_power_status::PowerStatusQueryParams;
 use crate::handlers::*;
 
-use manta_backend_dispatcher::StaticBackendDispatcher;
-
 use commands::{delete_redfish, get_all_redfish, get_redfish, post_redfish};
-use utoipa::{OpenApi, ToSchema, openapi::OpenApi as OpenApiDoc};
-
-#[derive(OpenApi)]
-#[openapi(
-  info(
-    title = "Manta API",
-    description = "API for managing Manta services",
-    version = "0.1.2"
-  ),
-  paths(root, test_ping, test_whoami, get_openapi, get_version, create_user,)
-)]
-pub struct ApiDoc;
+use openapi::{
+  ApiDoc, BootParametersSchema, CfsSessionSchema, GroupSchema,
+  NodeDetailsSchema, NodeSummarySchema, PowerStatusAllSchema, SiteHeader,
+};
+use utoipa::{IntoParams, OpenApi, ToSchema, openapi::OpenApi as OpenApiDoc};
+use utoipa_swagger_ui::SwaggerUi;
+
+const DEFAULT_SHUTDOWN_GRACE_PERIOD_SECS: u64 = 30;
 
 #[tokio::main]
 async fn main() {
+  let cli = Cli::parse();
+
+  if let Some(config_file_path) = &cli.config {
+    config::set_config_file_path(config_file_path.clone());
+  }
+
+  // Audit sinks and power jobs are only set up once `check-config` is ruled
+  // out
+  let (configuration, sites) = match AppContext::load_sites().await {
+    Ok(configuration_and_sites) => configuration_and_sites,
+    Err(e) => {
+      eprintln!("ERROR - Could not load configuration. Reason:\n{}", e);
+      std::process::exit(1);
+    }
+  };
+
+  let server = configuration.server.clone().unwrap_or_default();
+
   // initialize tracing
+  let env_filter = match cli.log_level.as_ref().or(server.log_level.as_ref()) {
+    Some(log_level) => tracing_subscriber::EnvFilter::try_new(log_level)
+      .unwrap_or_else(|e| {
+        eprintln!(
+          "ERROR - Log level '{}' not valid. Reason:\n{}",
+          log_level, e
+        );
+        std::process::exit(1);
+      }),
+    None => tracing_subscriber::EnvFilter::try_from_default_env()
+      .unwrap_or_else(|_| "example_websockets=debug,tower_http=debug".into()),
+  };
+
   tracing_subscriber::registry()
-    .with(
-      tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| "example_websockets=debug,tower_http=debug".into()),
-    )
+    .with(env_filter)
     .with(tracing_subscriber::fmt::layer())
     .init();
 
+  let listen_address_vec: Vec<ListenAddress> = if !cli.listen.is_empty() {
+    cli.listen.clone()
+  } else if !server.listen.is_empty() {
+    match server
+      .listen
+      .iter()
+      .map(|address| address.parse())
+      .collect::<Result<Vec<ListenAddress>, String>>()
+    {
+      Ok(listen_address_vec) => listen_address_vec,
+      Err(e) => {
+        eprintln!("ERROR - 'server.listen' not valid. Reason:\n{}", e);
+        std::process::exit(1);
+      }
+    }
+  } else {
+    vec![ListenAddress::Tcp(server::DEFAULT_LISTEN_ADDRESS)]
+  };
+
+  // TLS settings are read once, certificates are reloaded when they change
+  let tls_server_config =
+    match configuration.tls.as_ref().map(TlsServerConfig::new) {
+      Some(Ok(tls_server_config)) => Some(tls_server_config),
+      Some(Err(e)) => {
+        eprintln!("ERROR - Could not set up TLS. Reason:\n{}", e);
+        std::process::exit(1);
+      }
+      None => None,
+    };
+
+  // Same-origin only unless `[cors]` says otherwise
+  let cors_config = configuration.cors.clone().unwrap_or_default();
+
+  let cors_layer = match cors::cors_layer(&cors_config) {
+    Ok(cors_layer) => cors_layer,
+    Err(e) => {
+      eprintln!("ERROR - 'cors' not valid. Reason:\n{}", e);
+      std::process::exit(1);
+    }
+  };
+
+  if cors_config.permissive {
+    tracing::warn!(
+      "CORS is permissive, any web origin can call the API with credentials"
+    );
+  }
+
+  let metrics_listen_address =
+    match (&cli.metrics_listen, &server.metrics_listen) {
+      (Some(address), _) => Some(address.clone()),
+      (None, Some(address)) => match address.parse::<ListenAddress>() {
+        Ok(address) => Some(address),
+        Err(e) => {
+          eprintln!(
+            "ERROR - 'server.metrics_listen' not valid. Reason:\n{}",
+            e
+          );
+          std::process::exit(1);
+        }
+      },
+      (None, None) => None,
+    };
+
+  if let Some(Command::CheckConfig) = cli.command {
+    println!(
+      "Configuration '{}' is valid",
+      config::get_config_file_path().await.display()
+    );
+    return;
+  }
+
+  // Configuration is loaded once and swapped on SIGHUP
+  let app_state = match AppContext::new(configuration, sites, None) {
+    Ok(context) => AppState::new(context),
+    Err(e) => {
+      eprintln!("ERROR - Could not load configuration. Reason:\n{}", e);
+      std::process::exit(1);
+    }
+  };
+
+  // Metrics are only recorded when someone can scrape them
+  let metrics_handle = if metrics_listen_address.is_some() {
+    match prometheus::install_recorder() {
+      Ok(metrics_handle) => {
+        if let Some(kafka) = &app_state.context().audit.kafka {
+          kafka.report_spool_depth();
+        }
+
+        Some(metrics_handle)
+      }
+      Err(e) => {
+        eprintln!("ERROR - Could not set up metrics. Reason:\n{}", e);
+        std::process::exit(1);
+      }
+    }
+  } else {
+    None
+  };
+
+  tokio::spawn(reload_on_sighup(app_state.clone()));
+  tokio::spawn(service_health::poll_service_health(app_state.clone()));
+  tokio::spawn(power_jobs::resume_interrupted_jobs(app_state.clone()));
+
   let assets_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
 
-  // build our application with a route
-  let app = Router::new()
-    .fallback_service(
-      ServeDir::new(assets_dir).append_index_html_on_directories(true),
-    )
-    // `GET /` goes to `root`
-    .route("/", get(root))
+  // Routes forwarding the caller's token to the backend. The token must be
+  // signed by the Keycloak of the selected site
+  let authenticated_routes = Router::new()
     .route("/test/whoami", get(test_whoami))
-    .route("/test/ping", get(test_ping))
-    .route("/test/ws", get(test_ws))
-    .route("/openapi", get(get_openapi))
-    .route("/version", get(get_version))
-    .route("/users", post(create_user))
+    .route("/audit", get(get_audit))
     .route("/cfs/health", get(get_cfs_health_check))
     .route("/bos/health", get(get_bos_health_check))
-    .route("/kernel-parameters", get(get_kernel_parameters))
+    .route("/health/services", get(get_health_services))
     .route("/bss/boot-parameters", get(get_all_bss_boot_parameters))
     .route("/bss/boot-parameters/{xname}", get(get_bss_boot_parameters))
     .route("/bss/boot-parameters", post(post_bss_boot_parameters))
@@ -112,22 +252,74 @@ async fn main() {
     .route("/redfish/{xname}", get(get_redfish))
     .route("/redfish", post(post_redfish))
     .route("/redfish/{xname}", delete(delete_redfish))
-    .route("/authenticate", get(authenticate))
     .route("/console/{xname}", get(ws_console))
     .route("/cfssession/{cfssession}", get(get_cfs_session))
     .route("/cfssession/{cfssession}/logs", get(ws_cfs_session_logs))
     .route("/group", get(get_all_groups))
     .route("/group/{group}", get(get_group_details))
     .route("/group/{group}/hardware", get(get_hsm_hardware))
-    .route("/node/{node}/power-off", get(power_off_node))
-    .route("/node/{node}/power-on", get(power_on_node))
-    .route("/node/{node}/power-reset", get(power_reset_node))
+    .route("/group/{group}/power-status", get(get_group_power_status))
+    .route("/power/on", post(post_power_on))
+    .route("/power/off", post(post_power_off))
+    .route("/power/reset", post(post_power_reset))
+    .route("/jobs", get(get_jobs))
+    .route("/jobs/{id}", get(get_job))
+    .route("/jobs/{id}", delete(delete_job))
+    .route("/jobs/{id}/events", get(get_job_events))
     .route("/node/{node}/power-status", get(power_status_node))
     .route(
       "/node-migration/target/{target}/parent/{parent}",
       put(node_migration),
     )
-    .layer(CorsLayer::very_permissive())
+    // Layers run bottom up: the token is verified first, requests are audited
+    // even when rate limited, cached responses are not rate limited
+    .route_layer(middleware::from_fn_with_state(
+      app_state.clone(),
+      limit_requests,
+    ))
+    .route_layer(middleware::from_fn_with_state(
+      app_state.clone(),
+      cache_response,
+    ))
+    .route_layer(middleware::from_fn_with_state(
+      app_state.clone(),
+      audit_request,
+    ))
+    .route_layer(middleware::from_fn_with_state(
+      app_state.clone(),
+      require_valid_token,
+    ));
+
+  // build our application with a route
+  let app = Router::new()
+    .fallback_service(
+      ServeDir::new(assets_dir).append_index_html_on_directories(true),
+    )
+    // `GET /` goes to `root`
+    .route("/", get(root))
+    .route("/test/ping", get(test_ping))
+    .route("/test/ws", get(test_ws))
+    .route("/openapi", get(get_openapi))
+    .merge(SwaggerUi::new("/docs").url("/docs/openapi.json", ApiDoc::openapi()))
+    .route("/version", get(get_version))
+    .route("/healthz", get(get_healthz))
+    .route("/readyz", get(get_readyz))
+    .route("/users", post(create_user))
+    // Answer 405 to any method, without needing a token
+    .route("/node/{node}/power-on", any(power_node_moved))
+    .route("/node/{node}/power-off", any(power_node_moved))
+    .route("/node/{node}/power-reset", any(power_node_moved))
+    // Site is picked with the 'dc' query parameter, token verified by the
+    // handler
+    .route("/kernel-parameters", get(get_kernel_parameters))
+    // Basic authentication, exchanges credentials for a token
+    .route("/authenticate", get(authenticate))
+    .merge(authenticated_routes)
+    // Applies to matched routes only, static assets are not counted
+    .route_layer(middleware::from_fn(track_request))
+    .with_state(app_state.clone())
+    .layer(CatchPanicLayer::custom(panic_response))
+    .layer(cors_layer)
     .layer(
       TraceLayer::new_for_http()
         .make_span_with(DefaultMakeSpan::default().include_headers(true)),
@@ -135,15 +327,104 @@ async fn main() {
 
   // run our app with hyper
   // `axum::Server` is a re-export of `hyper::Server`
-  let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-  println!("listening on {}", addr);
-  //    axum::Server::bind(&addr)
-  //        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-  //        .await
-  //        .unwrap();
-  axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app)
+  let scheme = if tls_server_config.is_some() {
+    "https"
+  } else {
+    "http"
+  };
+
+  // Bind everything first so a wrong address is reported before serving
+  let mut listener_vec = Vec::new();
+  for listen_address in &listen_address_vec {
+    match Listener::bind(listen_address).await {
+      Ok(listener) => {
+        println!("listening on {} ({})", listen_address, scheme);
+        listener_vec.push(listener);
+      }
+      Err(e) => {
+        eprintln!(
+          "ERROR - Could not listen on {}. Reason:\n{}",
+          listen_address, e
+        );
+        std::process::exit(1);
+      }
+    }
+  }
+
+  // Plain HTTP, meant for a private interface or a unix socket
+  if let (Some(metrics_listen_address), Some(metrics_handle)) =
+    (&metrics_listen_address, metrics_handle)
+  {
+    match Listener::bind(metrics_listen_address).await {
+      Ok(listener) => {
+        println!("metrics on {} (http)", metrics_listen_address);
+        let shutdown = app_state.shutdown.clone();
+        tokio::spawn(async move {
+          if let Err(e) = server::serve(
+            listener,
+            prometheus::metrics_router(metrics_handle),
+            None,
+            shutdown,
+          )
+          .await
+          {
+            tracing::error!("Metrics server stopped: {}", e);
+          }
+        });
+      }
+      Err(e) => {
+        eprintln!(
+          "ERROR - Could not listen on {}. Reason:\n{}",
+          metrics_listen_address, e
+        );
+        std::process::exit(1);
+      }
+    }
+  }
+
+  let shutdown = app_state.shutdown.clone();
+  tokio::spawn(async move {
+    shutdown::wait_for_signal().await;
+    shutdown.trigger();
+  });
+
+  // Returns once the shutdown is triggered and no new connection is accepted
+  let serve_rslt =
+    futures::future::try_join_all(listener_vec.into_iter().map(|listener| {
+      server::serve(
+        listener,
+        app.clone(),
+        tls_server_config.clone(),
+        app_state.shutdown.clone(),
+      )
+    }))
+    .await;
+
+  if let Err(e) = serve_rslt {
+    eprintln!("ERROR - Server stopped. Reason:\n{}", e);
+    std::process::exit(1);
+  }
+
+  // In-flight requests, websockets and audit events
+  let grace_period = Duration::from_secs(
+    server
+      .shutdown_grace_period_secs
+      .unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD_SECS),
+  );
+
+  if tokio::time::timeout(grace_period, app_state.shutdown.wait())
     .await
-    .unwrap()
+    .is_err()
+  {
+    tracing::warn!(
+      "Grace period expired, {} connections or tasks still running",
+      app_state.shutdown.pending()
+    );
+  }
+
+  app_state.context().audit.flush().await;
+
+  tracing::info!("Shutdown complete");
 }
 
 // the input to our `create_user` handler
@@ -162,6 +443,7 @@ struct User {
 #[utoipa::path(
     get,
     path = "/",
+    tag = "meta",
     responses(
         (status = 200, description = "Hello world message", body = String)
     )
@@ -174,6 +456,7 @@ async fn root() -> &'static str {
 #[utoipa::path(
     get,
     path = "/version",
+    tag = "meta",
     responses(
         (status = 200, description = "Get manta-ws version", body = String)
     )
@@ -182,35 +465,43 @@ async fn get_version() -> &'static str {
   env!("CARGO_PKG_VERSION")
 }
 
+/// Process is up. Does not check any dependency, see `/readyz`
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Process is up", body = String)
+    )
+)]
+async fn get_healthz() -> &'static str {
+  "ok"
+}
+
 #[utoipa::path(
     get,
     path = "/test/whoami",
+    tag = "meta",
+    params(SiteHeader),
     responses(
         (status = 200, description = "Test current user", body = String),
         (status = UNAUTHORIZED, description = "Authentication header/token missing")
-    )
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
 )]
-async fn test_whoami(headers: HeaderMap) -> Result<String, StatusCode> {
-  // Get auth token
-  let auth_token = if let Some(auth_header) = headers.get("authorization") {
-    auth_header.to_str().unwrap().split(" ").nth(1).unwrap()
-  } else {
-    return Err(StatusCode::UNAUTHORIZED);
-  };
-
-  let claims_json = get_claims_from_jwt_token(auth_token).unwrap();
-
-  Ok(format!(
+async fn test_whoami(claims: Claims) -> String {
+  format!(
     "Hello {}!!!",
-    claims_json["name"].as_str().unwrap()
-  ))
+    claims.name.as_deref().unwrap_or(claims.username())
+  )
 }
 
 #[utoipa::path(
     get,
     path = "/openapi",
+    tag = "meta",
     responses(
-        (status = 200, description = "Get openapi json", body = String)
+        (status = 200, description = "Get openapi json", body = Object)
     )
 )]
 async fn get_openapi() -> impl IntoResponse {
@@ -221,6 +512,7 @@ async fn get_openapi() -> impl IntoResponse {
 #[utoipa::path(
     get,
     path = "/test/ping",
+    tag = "meta",
     responses(
         (status = 200, description = "Ping health endpoint", body = String)
     )
@@ -232,17 +524,38 @@ async fn test_ping() -> &'static str {
 #[utoipa::path(
     get,
     path = "/test/ws",
+    tag = "meta",
     responses(
-        (status = 200, description = "Websocket test endpoint", body = String)
+        (status = 101, description = "Websocket echoing back every message")
     )
 )]
-async fn test_ws(ws: WebSocketUpgrade) -> axum::response::Response {
+async fn test_ws(
+  State(state): State<AppState>,
+  ws: WebSocketUpgrade,
+) -> axum::response::Response {
   println!("Websocket test endpoint");
-  ws.on_upgrade(handle_socket_test_ws)
+  let shutdown = state.shutdown.clone();
+  ws.on_upgrade(move |socket| {
+    state
+      .shutdown
+      .track(handle_socket_test_ws(socket, shutdown))
+  })
 }
 
-async fn handle_socket_test_ws(mut socket: WebSocket) {
-  while let Some(msg) = socket.recv().await {
+async fn handle_socket_test_ws(mut socket: WebSocket, shutdown: Shutdown) {
+  loop {
+    let msg = tokio::select! {
+      msg = socket.recv() => msg,
+      _ = shutdown.triggered() => {
+        let _ = socket.send(server_restarting()).await;
+        return;
+      }
+    };
+
+    let Some(msg) = msg else {
+      return;
+    };
+
     let msg = if let Ok(msg) = msg {
       println!("Received message: {:?}", msg);
       msg
@@ -261,6 +574,7 @@ async fn handle_socket_test_ws(mut socket: WebSocket) {
 #[utoipa::path(
     post,
     path = "/users",
+    tag = "meta",
     request_body = CreateUser,
     responses(
         (status = 201, description = "User created", body = User),
@@ -283,50 +597,32 @@ async fn create_user(
   (StatusCode::CREATED, Json(user))
 }
 
+/// CFS session, if it targets groups available to the caller
+#[utoipa::path(
+    get,
+    path = "/cfssession/{cfssession}",
+    tag = "cfs",
+    params(SiteHeader, ("cfssession" = String, Path, description = "CFS session name")),
+    responses(
+        (status = 200, description = "Matching CFS sessions", body = Vec<CfsSessionSchema>),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn get_cfs_session(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Path(cfs_session_name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      eprintln!("ERROR - Site '{}' not found in configuration", site_name);
-      return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
+) -> Result<Json<Value>, ApiError> {
+  let shasta_base_url = &site.config.shasta_base_url;
+  let shasta_root_cert = &site.shasta_root_cert;
 
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_token = if let Some(auth_header) = headers.get("authorization") {
-    auth_header.to_str().unwrap().split(" ").nth(1).unwrap()
-  } else {
-    return Err(StatusCode::UNAUTHORIZED);
-  };
+  let backend = &site.backend;
 
   let hsm_group_available_vec: Vec<String> =
-    backend.get_group_name_available(&auth_token).await.unwrap();
+    backend.get_group_name_available(&auth_token).await?;
 
   let cfs_session_vec_rslt = backend
     .get_and_filter_sessions(
@@ -345,62 +641,51 @@ async fn get_cfs_session(
     )
     .await;
 
-  let cfs_session_vec = match cfs_session_vec_rslt {
-    Ok(cfs_session_vec) => cfs_session_vec,
-    Err(e) => {
-      eprintln!("ERROR - Failed to get CFS sessions. Reason:\n{:#?}", e);
-      return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-  };
+  let cfs_session_vec = cfs_session_vec_rslt?;
 
-  Ok(Json(serde_json::to_value(cfs_session_vec).unwrap()))
+  serde_json::to_value(cfs_session_vec)
+    .map(Json)
+    .map_err(|e| ApiError::internal(e.to_string()))
 }
 
+/// Streams the logs of a CFS session over a websocket, one text message per
+/// line
+#[utoipa::path(
+    get,
+    path = "/cfssession/{cfssession}/logs",
+    tag = "cfs",
+    params(SiteHeader, ("cfssession" = String, Path, description = "CFS session name")),
+    responses(
+        (status = 101, description = "Websocket streaming the session logs"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 404, description = "CFS session not found"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn ws_cfs_session_logs(
-  headers: HeaderMap,
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Path(cfs_session_name): Path<String>,
   ws: WebSocketUpgrade,
   user_agent: Option<TypedHeader<headers::UserAgent>>,
   ConnectInfo(addr): ConnectInfo<SocketAddr>,
-) -> impl IntoResponse {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site.clone();
-  let site_detail_value_opt = configuration.sites.get(&site_name).cloned();
-
-  let site = site_detail_value_opt.unwrap();
-
-  let k8s_details = site
-    .k8s
-    .expect("ERROR - k8s section not found in configuration");
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
-  // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
+) -> Result<Response, ApiError> {
+  let k8s_details = get_k8s_details(&site)?;
 
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap();
-  let auth_token = auth_header
-    .to_str()
-    .unwrap()
-    .split(" ")
-    .nth(1)
-    .unwrap()
-    .to_string();
+  // Open the log stream before upgrading so backend errors reach the client
+  // as a proper HTTP error
+  let logs_stream = site
+    .backend
+    .get_session_logs_stream(
+      &auth_token,
+      &site.name,
+      &cfs_session_name,
+      false,
+      k8s_details,
+    )
+    .await?;
 
   let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
     user_agent.to_string()
@@ -411,42 +696,50 @@ async fn ws_cfs_session_logs(
   println!("`{user_agent}` at {addr} connected.");
   // finalize the upgrade process by returning upgrade callback.
   // we can customize the callback by sending additional info such as address.
-  ws.on_upgrade(move |socket| {
-    get_cfs_session_logs(
+  let shutdown = state.shutdown.clone();
+  Ok(ws.on_upgrade(move |socket| {
+    state.shutdown.track(get_cfs_session_logs(
       socket,
       addr,
-      backend,
-      auth_token,
-      site_name,
-      cfs_session_name,
-      k8s_details,
-    )
-  })
+      logs_stream,
+      shutdown,
+    ))
+  }))
 }
 
 async fn get_cfs_session_logs(
   mut socket: WebSocket,
-  _who: SocketAddr,
-  backend: StaticBackendDispatcher,
-  shasta_token: String,
-  site_name: String,
-  cfs_session_name: String,
-  k8s_details: K8sDetails,
+  who: SocketAddr,
+  logs_stream: impl futures::AsyncBufRead + Unpin,
+  shutdown: Shutdown,
 ) {
-  let logs_stream = backend
-    .get_session_logs_stream(
-      &shasta_token,
-      &site_name,
-      &cfs_session_name,
-      false,
-      &k8s_details,
-    )
-    .await
-    .unwrap();
-
+  let _active_websocket = ActiveWebsocket::new("cfs_session_logs");
   let mut lines = logs_stream.lines();
 
-  while let Some(line) = lines.try_next().await.unwrap() {
+  loop {
+    let next_line = tokio::select! {
+      next_line = lines.try_next() => next_line,
+      _ = shutdown.triggered() => {
+        let _ = socket.send(server_restarting()).await;
+        break;
+      }
+    };
+
+    let line = match next_line {
+      Ok(Some(line)) => line,
+      Ok(None) => break,
+      Err(e) => {
+        eprintln!("ERROR - CFS session logs stream to {} failed: {}", who, e);
+        let _ = socket
+          .send(Message::Close(Some(CloseFrame {
+            code: close_code::ERROR,
+            reason: Utf8Bytes::from("log stream interrupted"),
+          })))
+          .await;
+        break;
+      }
+    };
+
     if line.is_empty() {
       // FIXME: This is a hack to make sure that the logs are displayed properly
       // because for some reason websocat stops displaying logs if an empty line is
@@ -458,100 +751,49 @@ async fn get_cfs_session_logs(
   }
 }
 
-pub fn get_configuration_file_path() -> PathBuf {
-  // XDG Base Directory Specification
-  let project_dirs = ProjectDirs::from(
-    "local", /*qualifier*/
-    "cscs",  /*organization*/
-    "manta", /*application*/
-  );
-
-  PathBuf::from(project_dirs.unwrap().config_dir())
-}
-
-/// Reads configuration parameters related to manta from environment variables or file. If both
-/// defiend, then environment variables takes preference
-pub fn get_configuration() -> Config {
-  let mut config_path = get_configuration_file_path();
-  config_path.push("config.toml"); // ~/.config/manta/config is the file
-
-  ::config::Config::builder()
-    .add_source(::config::File::from(config_path))
-    .add_source(
-      ::config::Environment::with_prefix("MANTA")
-        .try_parsing(true)
-        .prefix_separator("_"),
-    )
-    .build()
-    .unwrap()
-}
-
-pub fn get_csm_root_cert_content(site: &str) -> Result<Vec<u8>> {
-  let mut config_path = get_configuration_file_path();
-  config_path.push(site.to_string() + "_root_cert.pem");
-
-  let mut buf = Vec::new();
-  let mut root_cert_file = File::open(config_path)?;
-
-  let _ = root_cert_file.read_to_end(&mut buf);
-
-  /* let _ = match root_cert_file_rslt {
-    Ok(mut file) => file.read_to_end(&mut buf),
-    Err(_) => {
-      return Err("Root cert file for CSM not found".into());
-    }
-  }; */
-
-  Ok(buf)
-}
-
-async fn authenticate(headers: HeaderMap) -> Result<String, StatusCode> {
-  let settings = get_configuration();
-
-  let site_detail_hashmap = settings.get_table("sites").unwrap();
-  let site_detail_value = site_detail_hashmap
-    .get("alps")
-    .unwrap()
-    .clone()
-    .into_table()
-    .unwrap();
-
-  /* let shasta_base_url = site_detail_value
-  .get("shasta_base_url")
-  .unwrap()
-  .to_string(); */
-  let keycloak_base_url = site_detail_value
-    .get("keycloak_base_url")
-    .unwrap()
-    .to_string();
-  // let k8s_api_url = site_detail_value.get("k8s_api_url").unwrap().to_string();
-
-  // let settings_hsm_group_name_opt = settings.get_string("hsm_group").ok();
-
-  let shasta_root_cert = get_csm_root_cert_content("alps").map_err(|e| {
-    eprintln!(
-      "ERROR - Unable to get CSM root cert content. Reason:\n{:#?}",
-      e
-    );
-    StatusCode::INTERNAL_SERVER_ERROR
-  })?;
-
-  let base64_user_credentials =
-    if let Some(usercredentials) = headers.get("authorization") {
-      usercredentials.to_str().unwrap()
-    } else {
-      return Err(StatusCode::UNAUTHORIZED);
-    };
-
-  let user_credentials_raw = String::from_utf8(
-    base64::decode(base64_user_credentials.split(" ").nth(1).unwrap()).unwrap(),
-  )
-  .unwrap();
-
-  let mut user_credentials = user_credentials_raw.split(":");
-
-  let username = user_credentials.next().unwrap();
-  let password = user_credentials.next().unwrap();
+/// Exchanges Keycloak credentials for a token of the selected site
+#[utoipa::path(
+    get,
+    path = "/authenticate",
+    tag = "auth",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Token", body = String),
+        (status = 400, description = "Authorization header is not 'Basic base64(username:password)'"),
+        (status = 401, description = "Credentials not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("basic" = []))
+)]
+async fn authenticate(
+  SelectedSite(site): SelectedSite,
+  headers: HeaderMap,
+) -> Result<String, ApiError> {
+  let keycloak_base_url =
+    site.config.keycloak_base_url.as_ref().ok_or_else(|| {
+      ApiError::internal(format!(
+        "keycloak_base_url for site '{}' not found in configuration",
+        site.name
+      ))
+    })?;
+
+  let shasta_root_cert = &site.shasta_root_cert;
+
+  // Basic authentication, 'Authorization: Basic base64(username:password)'
+  let base64_user_credentials = get_auth_token(&headers)?;
+
+  let user_credentials_raw = STANDARD
+    .decode(&base64_user_credentials)
+    .ok()
+    .and_then(|user_credentials| String::from_utf8(user_credentials).ok())
+    .ok_or_else(|| {
+      ApiError::bad_request("Authentication header is not valid base64")
+    })?;
+
+  let (username, password) =
+    user_credentials_raw.split_once(":").ok_or_else(|| {
+      ApiError::bad_request("Authentication header must be 'username:password'")
+    })?;
 
   let auth_token_result =
     csm_rs::common::authentication::get_token_from_shasta_endpoint(
@@ -562,15 +804,7 @@ async fn authenticate(headers: HeaderMap) -> Result<String, StatusCode> {
     )
     .await;
 
-  println!("DEBUG - TEST 2");
-
-  match auth_token_result {
-    Ok(auth_token) => Ok(auth_token),
-    Err(error) => {
-      eprintln!("ERROR - Authentication failed. Reason:\n{:#?}", error);
-      Err(StatusCode::FORBIDDEN)
-    }
-  }
+  auth_token_result.map_err(ApiError::from)
 }
 
 /// The handler for the HTTP request (this gets called when the HTTP GET lands at the start
@@ -578,59 +812,27 @@ async fn authenticate(headers: HeaderMap) -> Result<String, StatusCode> {
 /// websocket protocol will occur.
 /// This is the last point where we can extract TCP/IP metadata such as IP address of the client
 /// as well as things from HTTP headers such as user-agent of the browser etc.
+#[utoipa::path(
+    get,
+    path = "/console/{xname}",
+    tag = "console",
+    params(SiteHeader, ("xname" = String, Path, description = "Node xname")),
+    responses(
+        (status = 101, description = "Websocket attached to the node console. Binary and text messages are written to the console, its output is sent back as text"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn ws_console(
-  headers: HeaderMap,
+  State(state): State<AppState>,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Path(xname): Path<String>,
   ws: WebSocketUpgrade,
   user_agent: Option<TypedHeader<headers::UserAgent>>,
-) -> impl IntoResponse {
-  let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
-    user_agent.to_string()
-  } else {
-    String::from("Unknown browser")
-  };
-
-  println!("`{user_agent}` connected.");
-  // finalize the upgrade process by returning upgrade callback.
-  // we can customize the callback by sending additional info such as address.
-  ws.on_upgrade(move |socket| handle_socket(headers, socket, xname))
-}
-
-/// Actual websocket statemachine (one will be spawned per connection)
-async fn handle_socket(headers: HeaderMap, socket: WebSocket, xname: String) {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = site_detail_value_opt.unwrap();
-
-  let k8s_details = site
-    .k8s
-    .as_ref()
-    .expect("ERROR - k8s section not found in configuration");
-
-  // let backend_tech = &site.backend;
-  // let shasta_base_url = &site.shasta_base_url;
-
-  // let root_ca_cert_file = &site.root_ca_cert_file;
-
-  /* let shasta_root_cert =
-  common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap(); */
-
-  // Backend
-  /* let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  ); */
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap().to_string();
+) -> Result<Response, ApiError> {
+  let k8s_details = get_k8s_details(&site)?;
 
   let shasta_k8s_secrets = match &k8s_details.authentication {
     K8sAuth::Native {
@@ -642,33 +844,76 @@ async fn handle_socket(headers: HeaderMap, socket: WebSocket, xname: String) {
     }
     K8sAuth::Vault {
       base_url: vault_base_url,
-    } => fetch_shasta_k8s_secrets_from_vault(
-      &vault_base_url,
-      &auth_token,
-      &site_name,
-    )
-    .await
-    .unwrap(),
+    } => {
+      fetch_shasta_k8s_secrets_from_vault(
+        vault_base_url,
+        &auth_token,
+        &site.name,
+      )
+      .await?
+    }
   };
 
-  // By splitting socket we can send and receive at the same time. In this example we will send
-  // unsolicited messages to client based on some sort of server's internal event (i.e .timer).
-  let (mut sender, mut receiver) = socket.split();
-
-  // CONSOLE
-
+  // Attach to the console before upgrading so failures reach the client as a
+  // proper HTTP error instead of a dropped websocket
   let mut attached = csm_rs::node::console::get_container_attachment_to_conman(
-    &xname.to_string(),
+    &xname,
     &k8s_details.api_url,
     shasta_k8s_secrets,
   )
-  .await
-  .expect("ERROR - Unable to attach to container");
+  .await?;
+
+  let (Some(stdout), Some(stdin)) = (attached.stdout(), attached.stdin())
+  else {
+    return Err(ApiError::new(
+      StatusCode::BAD_GATEWAY,
+      ErrorCode::BackendError,
+      format!("Console for '{}' did not provide stdin/stdout", xname),
+    ));
+  };
 
-  // Hook stream from k8s conman container to the websocket
-  let stdout_stream = ReaderStream::new(attached.stdout().unwrap());
+  let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
+    user_agent.to_string()
+  } else {
+    String::from("Unknown browser")
+  };
+
+  println!("`{user_agent}` connected.");
+  // finalize the upgrade process by returning upgrade callback.
+  // we can customize the callback by sending additional info such as address.
+  let shutdown = state.shutdown.clone();
+  Ok(ws.on_upgrade(move |socket| {
+    state.shutdown.track(async move {
+      let _active_websocket = ActiveWebsocket::new("console");
+      handle_socket(socket, xname, stdout, stdin, shutdown).await;
+      // Keep the attachment alive for as long as the websocket is open
+      drop(attached);
+    })
+  }))
+}
+
+/// Close frame sent to websocket clients on shutdown
+fn server_restarting() -> Message {
+  Message::Close(Some(CloseFrame {
+    code: close_code::AWAY,
+    reason: Utf8Bytes::from_static(shutdown::CLOSE_REASON),
+  }))
+}
 
-  let mut stdin_writer = attached.stdin().unwrap();
+/// Actual websocket statemachine (one will be spawned per connection)
+async fn handle_socket(
+  socket: WebSocket,
+  xname: String,
+  stdout: impl tokio::io::AsyncRead + Unpin + Send + 'static,
+  mut stdin_writer: impl tokio::io::AsyncWrite + Unpin + Send + 'static,
+  shutdown: Shutdown,
+) {
+  // By splitting socket we can send and receive at the same time. In this example we will send
+  // unsolicited messages to client based on some sort of server's internal event (i.e .timer).
+  let (mut sender, mut receiver) = socket.split();
+
+  // Hook stream from k8s conman container to the websocket
+  let stdout_stream = ReaderStream::new(stdout);
 
   // This task will receive messages from the conman container and send them to the client
   let _send_task = tokio::spawn(async move {
@@ -685,35 +930,49 @@ async fn handle_socket(headers: HeaderMap, socket: WebSocket, xname: String) {
       )))
       .await;
 
-    let _ = stdout_stream
-      .map(|bytes: Result<Bytes, _>| {
-        let bytes = bytes.unwrap(); // Handle error properly in production
-        //let vec = &bytes.to_vec().unwrap();
-        let text = std::str::from_utf8(&bytes).unwrap(); // Convert Bytes to &str
-        Ok(Message::Text(Utf8Bytes::from(text)))
+    let forward = stdout_stream
+      .map(|bytes: Result<Bytes, std::io::Error>| {
+        bytes
+          .map(|bytes| {
+            // Console output may split multi byte characters across reads
+            Message::Text(Utf8Bytes::from(
+              String::from_utf8_lossy(&bytes).into_owned(),
+            ))
+          })
+          .map_err(axum::Error::new)
       })
-      .forward(sender)
-      .await;
+      .forward(&mut sender);
+
+    // The client answers the close frame, which ends the receive task below
+    tokio::select! {
+      _ = forward => {}
+      _ = shutdown.triggered() => {
+        let _ = sender.send(server_restarting()).await;
+      }
+    }
   });
 
   // This second task will receive messages from client and print them on server console
   let _recv_task = tokio::spawn(async move {
     while let Some(message) = receiver.next().await {
-      match message.as_ref() {
-        Ok(Message::Close(_)) => {
-          println!("Client sent CLOSE message:\n{:?}", message.unwrap());
+      let input = match message {
+        Ok(Message::Close(close_frame)) => {
+          println!("Client sent CLOSE message:\n{:?}", close_frame);
           break;
         }
         Err(e) => {
           println!("Connection interrupted:\n{:?}", e);
           break;
         }
-        _ => {
-          let msg = message.unwrap();
-          let value = msg.to_text().unwrap();
-          println!("Message from xterm web client:\n{:?}", value);
-          let _ = stdin_writer.write_all(value.as_bytes()).await;
-        }
+        Ok(Message::Text(text)) => Bytes::from(text),
+        Ok(Message::Binary(bytes)) => bytes,
+        Ok(Message::Ping(_) | Message::Pong(_)) => continue,
+      };
+
+      // Keystrokes may contain passwords, they are never logged
+      tracing::trace!("{} bytes from xterm web client", input.len());
+      if stdin_writer.write_all(&input).await.is_err() {
+        break;
       }
     }
   })
@@ -754,49 +1013,27 @@ fn process_message(msg: Message, who: SocketAddr) -> ControlFlow<(), ()> {
   ControlFlow::Continue(())
 }
 
+fn get_k8s_details(site: &SiteContext) -> Result<&K8sDetails, ApiError> {
+  site.config.k8s.as_ref().ok_or_else(|| {
+    ApiError::internal(format!(
+      "k8s section for site '{}' not found in configuration",
+      site.name
+    ))
+  })
+}
+
 async fn get_service_health(
-  headers: HeaderMap,
+  site: Arc<SiteContext>,
+  auth_token: &str,
   service: &str,
-) -> Result<Json<serde_json::Value>> {
-  // Configuration
-  let settings = common::config::get_configuration().await?;
-
-  let configuration: MantaConfiguration = settings.try_deserialize()?;
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => bail!("ERROR - Site '{}' not found in configuration", site_name),
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file)?;
-
-  // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap().to_string();
+) -> Result<Json<serde_json::Value>, ApiError> {
+  let shasta_base_url = &site.config.shasta_base_url;
+  let shasta_root_cert = &site.shasta_root_cert;
 
   let response: Value = match service {
-    // NOTE: sending always 500 error is a BAD practice, we
-    // should do proper error handling by making mesa to return the right error code,
-    // then create the right HTTP status code based on it
     "cfs" => {
       csm_rs::cfs::common::health_check(
-        &auth_token,
+        auth_token,
         &shasta_base_url,
         &shasta_root_cert,
       )
@@ -804,351 +1041,265 @@ async fn get_service_health(
     }
     "bos" => {
       csm_rs::bos::health_check::get(
-        &auth_token,
+        auth_token,
         &shasta_base_url,
         &shasta_root_cert,
       )
       .await?
     }
-    _ => bail!("Invalid service name"),
+    _ => {
+      return Err(ApiError::not_found(format!(
+        "Service '{}' not supported",
+        service
+      )));
+    }
   };
 
   Ok(Json(response))
 }
 
-async fn get_cfs_health_check(headers: HeaderMap) -> Response {
-  let response_rslt = get_service_health(headers, "cfs").await;
+#[utoipa::path(
+    get,
+    path = "/cfs/health",
+    tag = "health",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Health of CFS as reported by the backend", body = Object),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+async fn get_cfs_health_check(
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+) -> Response {
+  let response_rslt = get_service_health(site, &auth_token, "cfs").await;
 
   match response_rslt {
     Ok(response) => return response.into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return e.into_response(),
   }
 }
 
-async fn get_bos_health_check(headers: HeaderMap) -> Response {
-  let response_rslt = get_service_health(headers, "bos").await;
+#[utoipa::path(
+    get,
+    path = "/bos/health",
+    tag = "health",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Health of BOS as reported by the backend", body = Object),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+async fn get_bos_health_check(
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+) -> Response {
+  let response_rslt = get_service_health(site, &auth_token, "bos").await;
 
   match response_rslt {
     Ok(response) => return response.into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return e.into_response(),
   }
 }
 
-async fn get_all_bss_boot_parameters(headers: HeaderMap) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      eprintln!("ERROR - Site '{}' not found in configuration", site_name);
-      return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
+#[utoipa::path(
+    get,
+    path = "/bss/boot-parameters",
+    tag = "bss",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "Boot parameters of all nodes", body = Vec<BootParametersSchema>),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+async fn get_all_bss_boot_parameters(
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+) -> Response {
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
   dbg!("0");
-  let boot_parameters_rslt = backend.get_all_bootparameters(auth_token).await;
+  let boot_parameters_rslt = backend.get_all_bootparameters(&auth_token).await;
 
   match boot_parameters_rslt {
     Ok(boot_parameters_vec) => {
       return (StatusCode::OK, Json(boot_parameters_vec)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
+#[utoipa::path(
+    get,
+    path = "/bss/boot-parameters/{xname}",
+    tag = "bss",
+    params(SiteHeader, ("xname" = String, Path, description = "Node xname")),
+    responses(
+        (status = 200, description = "Boot parameters of the node", body = Vec<BootParametersSchema>),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 404, description = "Node not found"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn get_bss_boot_parameters(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Path(xname): Path<String>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
   let boot_parameters_rslt =
-    backend.get_bootparameters(auth_token, &[xname]).await;
+    backend.get_bootparameters(&auth_token, &[xname]).await;
 
   match boot_parameters_rslt {
     Ok(response) => {
       return (StatusCode::OK, Json(response)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
+/// Adds boot parameters. The caller must manage all the `hosts`, only
+/// admins may select nodes by `macs` or `nids`
+#[utoipa::path(
+    post,
+    path = "/bss/boot-parameters",
+    tag = "bss",
+    params(SiteHeader),
+    request_body = BootParametersSchema,
+    responses(
+        (status = 200, description = "Boot parameters added"),
+        (status = 400, description = "No hosts, MACs or NIDs given"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage some of the hosts, or is not an admin and gave MACs or NIDs"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn post_bss_boot_parameters(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
   Json(boot_parameters): Json<BootParameters>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
+  let backend = &site.backend;
 
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  if let Err(e) =
+    authorize_boot_parameters(&site, &claims, &auth_token, &boot_parameters)
+      .await
+  {
+    return e.into_response();
+  }
 
   let bss_boot_parameters_rslt = backend
-    .add_bootparameters(auth_token, &boot_parameters)
+    .add_bootparameters(&auth_token, &boot_parameters)
     .await;
 
   match bss_boot_parameters_rslt {
     Ok(response) => return (StatusCode::OK, Json(response)).into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
+/// Deletes boot parameters. The caller must manage all the `hosts`, only
+/// admins may select nodes by `macs` or `nids`
+#[utoipa::path(
+    delete,
+    path = "/bss/boot-parameters",
+    tag = "bss",
+    params(SiteHeader),
+    request_body = BootParametersSchema,
+    responses(
+        (status = 200, description = "Backend response", body = String),
+        (status = 400, description = "No hosts, MACs or NIDs given"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage some of the hosts, or is not an admin and gave MACs or NIDs"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn delete_bss_boot_parameters(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
   Json(boot_parameters): Json<BootParameters>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
+  let backend = &site.backend;
 
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  if let Err(e) =
+    authorize_boot_parameters(&site, &claims, &auth_token, &boot_parameters)
+      .await
+  {
+    return e.into_response();
+  }
 
   let bss_boot_parameters_rslt = backend
-    .delete_bootparameters(auth_token, &boot_parameters)
+    .delete_bootparameters(&auth_token, &boot_parameters)
     .await;
 
   match bss_boot_parameters_rslt {
     Ok(response) => return (StatusCode::OK, Json(response)).into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
 async fn delete_bss_boot_parameters_by_xname(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Json(boot_parameters): Json<BootParameters>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
   let bss_boot_parameters_rslt = backend
-    .delete_bootparameters(auth_token, &boot_parameters)
+    .delete_bootparameters(&auth_token, &boot_parameters)
     .await;
 
   match bss_boot_parameters_rslt {
     Ok(response) => return (StatusCode::OK, Json(response)).into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
-async fn get_all_groups(headers: HeaderMap) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
+/// HSM groups available to the caller
+#[utoipa::path(
+    get,
+    path = "/group",
+    tag = "group",
+    params(SiteHeader),
+    responses(
+        (status = 200, description = "HSM groups", body = Vec<GroupSchema>),
+        (status = 304, description = "Not modified since the ETag or date sent by the client"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
+async fn get_all_groups(
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+) -> Response {
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
+  let backend = &site.backend;
 
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let hsm_group_available_vec =
+    match backend.get_group_available(&auth_token).await {
+      Ok(hsm_group_available_vec) => hsm_group_available_vec,
+      Err(e) => return ApiError::from(e).into_response(),
+    };
 
-  let hsm_group_available_name_vec = backend
-    .get_group_available(auth_token)
-    .await
-    .unwrap()
+  let hsm_group_available_name_vec = hsm_group_available_vec
     .iter()
     .map(|hsm_group| hsm_group.label.clone())
     .collect::<Vec<String>>();
@@ -1165,55 +1316,40 @@ async fn get_all_groups(headers: HeaderMap) -> Response {
       // Convert response to JSON
       return (StatusCode::OK, Json(response)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
+/// Details of the members of an HSM group
+#[utoipa::path(
+    get,
+    path = "/group/{group}",
+    tag = "group",
+    params(SiteHeader, ("group" = String, Path, description = "HSM group name")),
+    responses(
+        (status = 200, description = "Group members", body = Vec<NodeDetailsSchema>),
+        (status = 304, description = "Not modified since the ETag or date sent by the client"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 404, description = "HSM group not found"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn get_group_details(
+  SelectedSite(site): SelectedSite,
   Path(group): Path<String>,
-  headers: HeaderMap,
+  AuthToken(auth_token): AuthToken,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
+  let shasta_base_url = &site.config.shasta_base_url;
+  let shasta_root_cert = &site.shasta_root_cert;
 
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
-  let group = backend.get_group(&auth_token, &group).await.unwrap();
+  let group = match backend.get_group(&auth_token, &group).await {
+    Ok(group) => group,
+    Err(e) => return ApiError::from(e).into_response(),
+  };
 
   let hsm_groups_node_list = group.get_members();
 
@@ -1229,55 +1365,41 @@ async fn get_group_details(
     Ok(response) => {
       return (StatusCode::OK, Json(response)).into_response();
     }
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
+    Err(e) => return ApiError::from(e).into_response(),
   }
 }
 
+/// Hardware inventory calls in flight per `get_hsm_hardware` request
+const HW_INVENTORY_CONCURRENCY: usize = 5;
+
+/// Hardware summary of the members of an HSM group
+#[utoipa::path(
+    get,
+    path = "/group/{group}/hardware",
+    tag = "group",
+    params(SiteHeader, ("group" = String, Path, description = "HSM group name")),
+    responses(
+        (status = 200, description = "Hardware of each member", body = Vec<NodeSummarySchema>),
+        (status = 304, description = "Not modified since the ETag or date sent by the client"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 404, description = "HSM group not found"),
+        (status = 429, description = "Rate limit exceeded or too many concurrent backend calls"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn get_hsm_hardware(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
+  Extension(backend_permit): Extension<BackendPermit>,
   Path(group): Path<String>,
 ) -> Response {
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
+  let shasta_base_url = &site.config.shasta_base_url;
+  let shasta_root_cert = &site.shasta_root_cert;
 
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
 
-  let hsm_group = csm_rs::hsm::group::http_client::get(
+  let hsm_group_vec = match csm_rs::hsm::group::http_client::get(
     &auth_token,
     &shasta_base_url,
     &shasta_root_cert,
@@ -1285,20 +1407,36 @@ async fn get_hsm_hardware(
     None,
   )
   .await
-  .unwrap();
+  {
+    Ok(hsm_group_vec) => hsm_group_vec,
+    Err(e) => return ApiError::from(e).into_response(),
+  };
+
+  let Some(hsm_group) = hsm_group_vec.first() else {
+    return ApiError::not_found(format!("HSM group '{}' not found", group))
+      .into_response();
+  };
 
   let hsm_group_target_members =
-    csm_rs::hsm::group::utils::get_member_vec_from_hsm_group(
-      &hsm_group.first().unwrap(),
-    );
+    csm_rs::hsm::group::utils::get_member_vec_from_hsm_group(hsm_group);
 
   let mut hsm_summary: Vec<NodeSummary> = Vec::new();
 
   let mut tasks = tokio::task::JoinSet::new();
 
-  let sem = Arc::new(Semaphore::new(5)); // CSM 1.3.1 higher number of concurrent tasks won't
+  let backend_calls =
+    HW_INVENTORY_CONCURRENCY.min(site.backend_limiter.max_permits());
+
+  let sem = Arc::new(Semaphore::new(backend_calls)); // CSM 1.3.1 higher number of concurrent tasks won't
   // make it faster
 
+  if let Err(e) = backend_permit
+    .widen(&site.backend_limiter, backend_calls)
+    .await
+  {
+    return e.into_response();
+  }
+
   // Get HW inventory details for target HSM group
   for hsm_member in hsm_group_target_members.clone() {
     let shasta_token_string = auth_token.to_string(); // TODO: make it static
@@ -1319,19 +1457,21 @@ async fn get_hsm_hardware(
         &hsm_member_string,
       )
       .await
-      .unwrap()
     });
   }
 
   while let Some(message_rslt) = tasks.join_next().await {
     match message_rslt {
-      Ok(node_summary) => {
+      Ok(Ok(node_summary)) => {
         hsm_summary.push(node_summary);
       }
+      Ok(Err(e)) => {
+        tracing::error!("Failed fetching node hw information");
+        return ApiError::from(e).into_response();
+      }
       Err(e) => {
         tracing::error!("Failed procesing/fetching node hw information");
-        return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-          .into_response();
+        return ApiError::internal(e.to_string()).into_response();
       }
     }
   }
@@ -1341,224 +1481,63 @@ async fn get_hsm_hardware(
   return (StatusCode::OK, Json(hsm_summary)).into_response();
 }
 
-async fn power_off_node(
-  Path(node): Path<String>,
-  headers: HeaderMap,
-) -> Response {
-  tracing::info!("Power OFF node {}", node);
-
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
-  // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
-
-  let response_rslt = backend.power_off_sync(auth_token, &[node], true).await;
-
-  match response_rslt {
-    Ok(_) => return (StatusCode::OK, ()).into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
-  }
-}
-
-#[debug_handler]
-async fn power_on_node(
-  headers: HeaderMap,
-  Path(node): Path<String>,
-) -> Response {
-  tracing::info!("Power ON node {}", node);
-
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
-  // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
-
-  let response_rslt = backend.power_on_sync(auth_token, &[node]).await;
-
-  match response_rslt {
-    Ok(_) => return (StatusCode::OK, ()).into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
-  }
-}
-
-async fn power_reset_node(
-  headers: HeaderMap,
-  Path(node): Path<String>,
-) -> Response {
-  tracing::debug!("Power RESET node {}", node);
-
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg))
-        .into_response();
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
-  // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
-
-  let response_rslt = backend.power_on_sync(auth_token, &[node]).await;
-
-  match response_rslt {
-    Ok(_) => return (StatusCode::OK, ()).into_response(),
-    Err(e) => {
-      return (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string()))
-        .into_response();
-    }
-  }
+/// Former power routes. Power actions are `POST /power/{on|off|reset}` so
+/// link prefetchers and crawlers can not trigger them
+#[utoipa::path(
+    get,
+    path = "/node/{node}/power-{action}",
+    tag = "power",
+    params(
+        ("node" = String, Path, description = "Node xname"),
+        ("action" = String, Path, description = "`on`, `off` or `reset`")
+    ),
+    responses(
+        (status = 405, description = "Use `POST /power/{action}` instead")
+    )
+)]
+async fn power_node_moved(Path(node): Path<String>) -> Response {
+  let mut response = ApiError::new(
+    StatusCode::METHOD_NOT_ALLOWED,
+    ErrorCode::MethodNotAllowed,
+    format!(
+      "Power actions moved to 'POST /power/{{on|off|reset}}' with body {{\"xnames\": [\"{}\"]}}",
+      node
+    ),
+  )
+  .into_response();
+  response
+    .headers_mut()
+    .insert(header::ALLOW, HeaderValue::from_static(""));
+  response
 }
 
 // TODO: these need to be imported from csm-rs and ochami-rs ? or dispatcher ?
-#[derive(Deserialize, Debug)]
-pub struct PowerStatusQueryParams {
-  power_state_filter: Option<String>,
-  management_state_filter: Option<String>,
-}
-
+#[utoipa::path(
+    get,
+    path = "/node/{node}/power-status",
+    tag = "power",
+    params(SiteHeader, ("node" = String, Path, description = "Node xname"), PowerStatusQueryParams),
+    responses(
+        (status = 200, description = "Power status of the node", body = PowerStatusAllSchema),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn power_status_node(
-  headers: HeaderMap,
+  SelectedSite(site): SelectedSite,
+  AuthToken(auth_token): AuthToken,
   Path(node): Path<String>,
   Query(query_param): Query<PowerStatusQueryParams>,
-) -> Result<impl IntoResponse, impl IntoResponse> {
+) -> Result<impl IntoResponse, ApiError> {
   tracing::debug!("Power STATUS node {}", node);
 
-  // Configuration
-  let settings = common::config::get_configuration().await.unwrap();
-
-  let configuration: MantaConfiguration = settings.try_deserialize().unwrap();
-
-  let site_name: String = configuration.site;
-  let site_detail_value_opt = configuration.sites.get(&site_name);
-
-  let site = match site_detail_value_opt {
-    Some(site_detail_value) => site_detail_value,
-    None => {
-      let error_msg =
-        format!("ERROR - Site '{}' not found in configuration", site_name);
-      return Err(
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_msg)).into_response(),
-      );
-    }
-  };
-
-  let backend_tech = &site.backend;
-  let shasta_base_url = &site.shasta_base_url;
-
-  let root_ca_cert_file = &site.root_ca_cert_file;
-
-  let shasta_root_cert =
-    common::config::get_csm_root_cert_content(&root_ca_cert_file).unwrap();
-
   // Backend
-  let backend = StaticBackendDispatcher::new(
-    &backend_tech,
-    &shasta_base_url,
-    &shasta_root_cert,
-  );
-
-  // Get auth token
-  let auth_header = headers.get("authorization").unwrap().to_str().unwrap();
-  let auth_token = auth_header.split(" ").nth(1).unwrap();
+  let backend = &site.backend;
 
   let response = backend
     .power_status(
-      auth_token,
+      &auth_token,
       &[node],
       query_param.power_state_filter.as_deref(), // Convert Option<String> to Option<&str>
       query_param.management_state_filter.as_deref(), // Convert Option<String> to Option<&str>
@@ -1567,22 +1546,45 @@ async fn power_status_node(
     )
     .await;
 
-  match response {
-    Ok(response) => Ok(Json(response)),
-    Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
-  }
+  Ok(Json(response?))
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
 pub struct NodeMigrationQueryParams {
+  /// Xnames, comma separated
   ids: String,
+  /// Create the target group if it does not exist
   create_hsm_group: bool,
 }
 
+/// Moves nodes from the parent HSM group to the target one. The caller must
+/// manage both groups and the nodes
+#[utoipa::path(
+    put,
+    path = "/node-migration/target/{target}/parent/{parent}",
+    tag = "group",
+    params(
+        SiteHeader,
+        ("target" = String, Path, description = "HSM group the nodes move to"),
+        ("parent" = String, Path, description = "HSM group the nodes move from"),
+        NodeMigrationQueryParams
+    ),
+    responses(
+        (status = 200, description = "Nodes moved"),
+        (status = 401, description = "Token missing or not valid"),
+        (status = 403, description = "Caller does not manage the groups or the nodes"),
+        (status = 422, description = "Target group does not exist and 'create_hsm_group' is not set"),
+        (status = 502, description = "Backend failed")
+    ),
+    security(("bearer_token" = []), ("client_certificate" = []))
+)]
 async fn node_migration(
+  SelectedSite(site): SelectedSite,
   Path((target, parent)): Path<(String, String)>,
   Query(query_param): Query<NodeMigrationQueryParams>,
-  headers: HeaderMap,
+  claims: Claims,
+  AuthToken(auth_token): AuthToken,
 ) -> Response {
   tracing::info!(
     "Migrate nodes '{}' from parent '{}' to target {}. Create HSM group if doesn't exists? {}",
@@ -1595,45 +1597,37 @@ async fn node_migration(
   let ids = query_param.ids;
   let create_hsm_group = query_param.create_hsm_group;
 
-  let settings = get_configuration();
-
-  let site_detail_hashmap = settings.get_table("sites").unwrap();
-  let site_detail_value = site_detail_hashmap
-    .get("alps")
-    .unwrap()
-    .clone()
-    .into_table()
-    .unwrap();
-
-  let shasta_base_url = site_detail_value
-    .get("shasta_base_url")
-    .unwrap()
-    .to_string();
-
-  let shasta_root_cert = get_csm_root_cert_content("alps")
-    .map_err(|e| {
-      eprintln!(
-        "ERROR - Unable to get CSM root cert content. Reason:\n{:#?}",
-        e
-      );
-      StatusCode::INTERNAL_SERVER_ERROR
-    })
-    .unwrap();
-
-  // Get auth token
-  let auth_token = if let Some(auth_header) = headers.get("authorization") {
-    auth_header.to_str().unwrap().split(" ").nth(1).unwrap()
-  } else {
-    return (StatusCode::UNAUTHORIZED).into_response();
-  };
+  let shasta_base_url = &site.config.shasta_base_url;
+  let shasta_root_cert = &site.shasta_root_cert;
 
   let new_target_hsm_members = ids
     .split(',')
     .map(|xname| xname.trim())
     .collect::<Vec<&str>>();
 
+  // Caller must manage both groups and the nodes moved between them
+  for group_name in [&target, &parent] {
+    if let Err(e) =
+      authorize_group(&site, &claims, &auth_token, group_name).await
+    {
+      return e.into_response();
+    }
+  }
+
+  let new_target_hsm_member_vec: Vec<String> = new_target_hsm_members
+    .iter()
+    .map(|xname| xname.to_string())
+    .collect();
+
+  if let Err(e) =
+    authorize_xnames(&site, &claims, &auth_token, &new_target_hsm_member_vec)
+      .await
+  {
+    return e.into_response();
+  }
+
   if csm_rs::hsm::group::http_client::get(
-    auth_token,
+    &auth_token,
     &shasta_base_url,
     &shasta_root_cert,
     Some(&[&target]),
@@ -1649,8 +1643,8 @@ async fn node_migration(
         "HSM group {} does not exist, but the option to create the group has been selected, creating it now.",
         target.to_string()
       );
-      csm_rs::hsm::group::http_client::create_new_group(
-        auth_token,
+      if let Err(e) = csm_rs::hsm::group::http_client::create_new_group(
+        &auth_token,
         &shasta_base_url,
         &shasta_root_cert,
         &target,
@@ -1660,18 +1654,26 @@ async fn node_migration(
         &[],
       )
       .await
-      .expect("Unable to create new HSM group");
+      {
+        return ApiError::from(e).into_response();
+      }
     } else {
       tracing::error!(
         "HSM group {} does not exist, but the option to create the group was NOT specificied, cannot continue.",
         target.to_string()
       );
-      return (StatusCode::UNPROCESSABLE_ENTITY).into_response();
+      return error_respond(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        format!(
+          "HSM group '{}' does not exist and 'create_hsm_group' is not set",
+          target
+        ),
+      );
     }
   }
 
-  let _ = csm_rs::hsm::group::utils::migrate_hsm_members(
-    auth_token,
+  let migration_rslt = csm_rs::hsm::group::utils::migrate_hsm_members(
+    &auth_token,
     &shasta_base_url,
     &shasta_root_cert,
     &target,
@@ -1681,5 +1683,8 @@ async fn node_migration(
   )
   .await;
 
-  return ().into_response();
+  match migration_rslt {
+    Ok(_) => ().into_response(),
+    Err(e) => ApiError::from(e).into_response(),
+  }
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, pin::Pin};
+use std::{collections::HashMap, pin::Pin, time::Instant};
 
 /// This is the static backend dispatcher
 /// To add a new backend:
@@ -60,19 +60,66 @@ pub enum StaticBackendDispatcher {
 }
 
 impl StaticBackendDispatcher {
-  pub fn new(backend_type: &str, base_url: &str, root_cert: &[u8]) -> Self {
-    let csm = Csm::new(base_url, root_cert);
-    let ochami = Ochami::new(base_url, root_cert);
-
+  pub fn new(
+    backend_type: &str,
+    base_url: &str,
+    root_cert: &[u8],
+  ) -> Result<Self, Error> {
     match backend_type {
-      "csm" => Self::CSM(csm).into(),
-      "ochami" => Self::OCHAMI(ochami).into(),
-      _ => {
-        eprintln!("ERROR - Backend '{}' not supported", backend_type);
-        std::process::exit(1);
-      }
+      "csm" => Ok(Self::CSM(Csm::new(base_url, root_cert))),
+      "ochami" => Ok(Self::OCHAMI(Ochami::new(base_url, root_cert))),
+      _ => Err(Error::Message(format!(
+        "Backend '{}' not supported",
+        backend_type
+      ))),
     }
   }
+
+  /// Services the backend provides and the path of their health endpoint,
+  /// relative to `shasta_base_url`
+  pub fn service_health_paths(
+    &self,
+  ) -> &'static [(&'static str, &'static str)] {
+    match self {
+      CSM(_) => &[
+        ("hsm", "/smd/hsm/v2/service/ready"),
+        ("bss", "/bss/boot/v1/service/status"),
+        ("pcs", "/power-control/v1/readiness"),
+        ("ims", "/ims/healthz/ready"),
+        ("cfs", "/cfs/healthz"),
+        ("bos", "/bos/v2/healthz"),
+      ],
+      OCHAMI(_) => &[
+        ("hsm", "/hsm/v2/service/ready"),
+        ("bss", "/boot/v1/service/status"),
+        ("pcs", "/power-control/v1/readiness"),
+      ],
+    }
+  }
+
+  fn backend_type(&self) -> &'static str {
+    match self {
+      CSM(_) => "csm",
+      OCHAMI(_) => "ochami",
+    }
+  }
+
+  /// Records how long a backend call took, per method and backend type
+  async fn timed<T>(
+    &self,
+    method: &'static str,
+    call: impl Future<Output = T>,
+  ) -> T {
+    let start = Instant::now();
+    let output = call.await;
+    metrics::histogram!(
+      "manta_backend_request_duration_seconds",
+      "method" => method,
+      "backend" => self.backend_type(),
+    )
+    .record(start.elapsed().as_secs_f64());
+    output
+  }
 }
 
 impl GroupTrait for StaticBackendDispatcher {
@@ -80,20 +127,28 @@ impl GroupTrait for StaticBackendDispatcher {
     &self,
     auth_token: &str,
   ) -> Result<Vec<Group>, Error> {
-    match self {
-      CSM(b) => b.get_group_available(auth_token).await,
-      OCHAMI(b) => b.get_group_available(auth_token).await,
-    }
+    self
+      .timed("get_group_available", async {
+        match self {
+          CSM(b) => b.get_group_available(auth_token).await,
+          OCHAMI(b) => b.get_group_available(auth_token).await,
+        }
+      })
+      .await
   }
 
   async fn get_group_name_available(
     &self,
     jwt_token: &str,
   ) -> Result<Vec<String>, Error> {
-    match self {
-      CSM(b) => b.get_group_name_available(jwt_token).await,
-      OCHAMI(b) => b.get_group_name_available(jwt_token).await,
-    }
+    self
+      .timed("get_group_name_available", async {
+        match self {
+          CSM(b) => b.get_group_name_available(jwt_token).await,
+          OCHAMI(b) => b.get_group_name_available(jwt_token).await,
+        }
+      })
+      .await
   }
 
   async fn add_group(
@@ -101,10 +156,14 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_group: Group,
   ) -> Result<Group, Error> {
-    match self {
-      CSM(b) => b.add_group(auth_token, hsm_group).await,
-      OCHAMI(b) => b.add_group(auth_token, hsm_group).await,
-    }
+    self
+      .timed("add_group", async {
+        match self {
+          CSM(b) => b.add_group(auth_token, hsm_group).await,
+          OCHAMI(b) => b.add_group(auth_token, hsm_group).await,
+        }
+      })
+      .await
   }
 
   // FIXME: rename function to 'get_hsm_group_members'
@@ -113,16 +172,20 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_group_name_vec: &[&str],
   ) -> Result<Vec<String>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_member_vec_from_group_name_vec(auth_token, hsm_group_name_vec)
-          .await
-      }
-      OCHAMI(b) => {
-        b.get_member_vec_from_group_name_vec(auth_token, hsm_group_name_vec)
-          .await
-      }
-    }
+    self
+      .timed("get_member_vec_from_group_name_vec", async {
+        match self {
+          CSM(b) => {
+            b.get_member_vec_from_group_name_vec(auth_token, hsm_group_name_vec)
+              .await
+          }
+          OCHAMI(b) => {
+            b.get_member_vec_from_group_name_vec(auth_token, hsm_group_name_vec)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_group_map_and_filter_by_group_vec(
@@ -130,16 +193,20 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_name_vec: &[&str],
   ) -> Result<HashMap<String, Vec<String>>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_group_map_and_filter_by_group_vec(auth_token, hsm_name_vec)
-          .await
-      }
-      OCHAMI(b) => {
-        b.get_group_map_and_filter_by_group_vec(auth_token, hsm_name_vec)
-          .await
-      }
-    }
+    self
+      .timed("get_group_map_and_filter_by_group_vec", async {
+        match self {
+          CSM(b) => {
+            b.get_group_map_and_filter_by_group_vec(auth_token, hsm_name_vec)
+              .await
+          }
+          OCHAMI(b) => {
+            b.get_group_map_and_filter_by_group_vec(auth_token, hsm_name_vec)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_group_map_and_filter_by_member_vec(
@@ -147,26 +214,34 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     member_vec: &[&str],
   ) -> Result<HashMap<String, Vec<String>>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_group_map_and_filter_by_member_vec(auth_token, member_vec)
-          .await
-      }
-      OCHAMI(b) => {
-        b.get_group_map_and_filter_by_member_vec(auth_token, member_vec)
-          .await
-      }
-    }
+    self
+      .timed("get_group_map_and_filter_by_member_vec", async {
+        match self {
+          CSM(b) => {
+            b.get_group_map_and_filter_by_member_vec(auth_token, member_vec)
+              .await
+          }
+          OCHAMI(b) => {
+            b.get_group_map_and_filter_by_member_vec(auth_token, member_vec)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_all_groups(
     &self,
     auth_token: &str,
   ) -> Result<Vec<Group>, Error> {
-    match self {
-      CSM(b) => b.get_all_groups(auth_token).await,
-      OCHAMI(b) => b.get_all_groups(auth_token).await,
-    }
+    self
+      .timed("get_all_groups", async {
+        match self {
+          CSM(b) => b.get_all_groups(auth_token).await,
+          OCHAMI(b) => b.get_all_groups(auth_token).await,
+        }
+      })
+      .await
   }
 
   async fn get_group(
@@ -174,10 +249,14 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_name: &str,
   ) -> Result<Group, Error> {
-    match self {
-      CSM(b) => b.get_group(auth_token, hsm_name).await,
-      OCHAMI(b) => b.get_group(auth_token, hsm_name).await,
-    }
+    self
+      .timed("get_group", async {
+        match self {
+          CSM(b) => b.get_group(auth_token, hsm_name).await,
+          OCHAMI(b) => b.get_group(auth_token, hsm_name).await,
+        }
+      })
+      .await
   }
 
   async fn get_groups(
@@ -185,10 +264,14 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_name_vec: Option<&[&str]>,
   ) -> Result<Vec<Group>, Error> {
-    match self {
-      CSM(b) => b.get_groups(auth_token, hsm_name_vec).await,
-      OCHAMI(b) => b.get_groups(auth_token, hsm_name_vec).await,
-    }
+    self
+      .timed("get_groups", async {
+        match self {
+          CSM(b) => b.get_groups(auth_token, hsm_name_vec).await,
+          OCHAMI(b) => b.get_groups(auth_token, hsm_name_vec).await,
+        }
+      })
+      .await
   }
 
   async fn delete_group(
@@ -196,10 +279,14 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_group_label: &str,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.delete_group(auth_token, hsm_group_label).await,
-      OCHAMI(b) => b.delete_group(auth_token, hsm_group_label).await,
-    }
+    self
+      .timed("delete_group", async {
+        match self {
+          CSM(b) => b.delete_group(auth_token, hsm_group_label).await,
+          OCHAMI(b) => b.delete_group(auth_token, hsm_group_label).await,
+        }
+      })
+      .await
   }
 
   async fn get_hsm_map_and_filter_by_hsm_name_vec(
@@ -207,16 +294,20 @@ impl GroupTrait for StaticBackendDispatcher {
     auth_token: &str,
     hsm_name_vec: &[&str],
   ) -> Result<HashMap<String, Vec<String>>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_hsm_map_and_filter_by_hsm_name_vec(auth_token, hsm_name_vec)
-          .await
-      }
-      OCHAMI(b) => {
-        b.get_hsm_map_and_filter_by_hsm_name_vec(auth_token, hsm_name_vec)
-          .await
-      }
-    }
+    self
+      .timed("get_hsm_map_and_filter_by_hsm_name_vec", async {
+        match self {
+          CSM(b) => {
+            b.get_hsm_map_and_filter_by_hsm_name_vec(auth_token, hsm_name_vec)
+              .await
+          }
+          OCHAMI(b) => {
+            b.get_hsm_map_and_filter_by_hsm_name_vec(auth_token, hsm_name_vec)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   async fn post_member(
@@ -225,10 +316,14 @@ impl GroupTrait for StaticBackendDispatcher {
     group_label: &str,
     xname: &str,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.post_member(auth_token, group_label, xname).await,
-      OCHAMI(b) => b.post_member(auth_token, group_label, xname).await,
-    }
+    self
+      .timed("post_member", async {
+        match self {
+          CSM(b) => b.post_member(auth_token, group_label, xname).await,
+          OCHAMI(b) => b.post_member(auth_token, group_label, xname).await,
+        }
+      })
+      .await
   }
 
   // Add members to group.
@@ -239,16 +334,20 @@ impl GroupTrait for StaticBackendDispatcher {
     group_label: &str,
     xnames: &[&str],
   ) -> Result<Vec<String>, Error> {
-    match self {
-      CSM(b) => {
-        b.add_members_to_group(auth_token, group_label, xnames)
-          .await
-      }
-      OCHAMI(b) => {
-        b.add_members_to_group(auth_token, group_label, xnames)
-          .await
-      }
-    }
+    self
+      .timed("add_members_to_group", async {
+        match self {
+          CSM(b) => {
+            b.add_members_to_group(auth_token, group_label, xnames)
+              .await
+          }
+          OCHAMI(b) => {
+            b.add_members_to_group(auth_token, group_label, xnames)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   async fn delete_member_from_group(
@@ -257,16 +356,20 @@ impl GroupTrait for StaticBackendDispatcher {
     group_label: &str,
     xname: &str,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.delete_member_from_group(auth_token, group_label, xname)
-          .await
-      }
-      OCHAMI(b) => {
-        b.delete_member_from_group(auth_token, group_label, xname)
-          .await
-      }
-    }
+    self
+      .timed("delete_member_from_group", async {
+        match self {
+          CSM(b) => {
+            b.delete_member_from_group(auth_token, group_label, xname)
+              .await
+          }
+          OCHAMI(b) => {
+            b.delete_member_from_group(auth_token, group_label, xname)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   // HSM/GROUP
@@ -277,26 +380,30 @@ impl GroupTrait for StaticBackendDispatcher {
     parent_hsm_group_name: &str,
     new_target_hsm_members: &[&str],
   ) -> Result<(Vec<String>, Vec<String>), Error> {
-    match self {
-      CSM(b) => {
-        b.migrate_group_members(
-          auth_token,
-          target_hsm_group_name,
-          parent_hsm_group_name,
-          new_target_hsm_members,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.migrate_group_members(
-          auth_token,
-          target_hsm_group_name,
-          parent_hsm_group_name,
-          new_target_hsm_members,
-        )
-        .await
-      }
-    }
+    self
+      .timed("migrate_group_members", async {
+        match self {
+          CSM(b) => {
+            b.migrate_group_members(
+              auth_token,
+              target_hsm_group_name,
+              parent_hsm_group_name,
+              new_target_hsm_members,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.migrate_group_members(
+              auth_token,
+              target_hsm_group_name,
+              parent_hsm_group_name,
+              new_target_hsm_members,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   // HSM/GROUP
@@ -307,26 +414,30 @@ impl GroupTrait for StaticBackendDispatcher {
     members_to_remove: &[&str],
     members_to_add: &[&str],
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.update_group_members(
-          auth_token,
-          group_name,
-          members_to_remove,
-          members_to_add,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.update_group_members(
-          auth_token,
-          group_name,
-          members_to_remove,
-          members_to_add,
-        )
-        .await
-      }
-    }
+    self
+      .timed("update_group_members", async {
+        match self {
+          CSM(b) => {
+            b.update_group_members(
+              auth_token,
+              group_name,
+              members_to_remove,
+              members_to_add,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.update_group_members(
+              auth_token,
+              group_name,
+              members_to_remove,
+              members_to_add,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -336,10 +447,14 @@ impl HardwareInventory for StaticBackendDispatcher {
     auth_token: &str,
     xname: &str,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.get_inventory_hardware(auth_token, xname).await,
-      OCHAMI(b) => b.get_inventory_hardware(auth_token, xname).await,
-    }
+    self
+      .timed("get_inventory_hardware", async {
+        match self {
+          CSM(b) => b.get_inventory_hardware(auth_token, xname).await,
+          OCHAMI(b) => b.get_inventory_hardware(auth_token, xname).await,
+        }
+      })
+      .await
   }
 
   async fn get_inventory_hardware_query(
@@ -352,20 +467,24 @@ impl HardwareInventory for StaticBackendDispatcher {
     partition: Option<&str>,
     format: Option<&str>,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => {
-        b.get_inventory_hardware_query(
-          auth_token, xname, r#type, children, parents, partition, format,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_inventory_hardware_query(
-          auth_token, xname, r#type, children, parents, partition, format,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_inventory_hardware_query", async {
+        match self {
+          CSM(b) => {
+            b.get_inventory_hardware_query(
+              auth_token, xname, r#type, children, parents, partition, format,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_inventory_hardware_query(
+              auth_token, xname, r#type, children, parents, partition, format,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn post_inventory_hardware(
@@ -373,10 +492,14 @@ impl HardwareInventory for StaticBackendDispatcher {
     auth_token: &str,
     hardware: HWInventoryByLocationList,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.post_inventory_hardware(auth_token, hardware).await,
-      OCHAMI(b) => b.post_inventory_hardware(auth_token, hardware).await,
-    }
+    self
+      .timed("post_inventory_hardware", async {
+        match self {
+          CSM(b) => b.post_inventory_hardware(auth_token, hardware).await,
+          OCHAMI(b) => b.post_inventory_hardware(auth_token, hardware).await,
+        }
+      })
+      .await
   }
 }
 
@@ -386,20 +509,28 @@ impl ComponentTrait for StaticBackendDispatcher {
     auth_token: &str,
     nid_only: Option<&str>,
   ) -> Result<NodeMetadataArray, Error> {
-    match self {
-      CSM(b) => b.get_all_nodes(auth_token, nid_only).await,
-      OCHAMI(b) => b.get_all_nodes(auth_token, nid_only).await,
-    }
+    self
+      .timed("get_all_nodes", async {
+        match self {
+          CSM(b) => b.get_all_nodes(auth_token, nid_only).await,
+          OCHAMI(b) => b.get_all_nodes(auth_token, nid_only).await,
+        }
+      })
+      .await
   }
 
   async fn get_node_metadata_available(
     &self,
     auth_token: &str,
   ) -> Result<Vec<Component>, Error> {
-    match self {
-      CSM(b) => b.get_node_metadata_available(auth_token).await,
-      OCHAMI(b) => b.get_node_metadata_available(auth_token).await,
-    }
+    self
+      .timed("get_node_metadata_available", async {
+        match self {
+          CSM(b) => b.get_node_metadata_available(auth_token).await,
+          OCHAMI(b) => b.get_node_metadata_available(auth_token).await,
+        }
+      })
+      .await
   }
 
   async fn get(
@@ -426,60 +557,64 @@ impl ComponentTrait for StaticBackendDispatcher {
     role_only: Option<&str>,
     nid_only: Option<&str>,
   ) -> Result<NodeMetadataArray, Error> {
-    match self {
-      CSM(b) => {
-        b.get(
-          auth_token,
-          id,
-          r#type,
-          state,
-          flag,
-          role,
-          subrole,
-          enabled,
-          software_status,
-          subtype,
-          arch,
-          class,
-          nid,
-          nid_start,
-          nid_end,
-          partition,
-          group,
-          state_only,
-          flag_only,
-          role_only,
-          nid_only,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get(
-          auth_token,
-          id,
-          r#type,
-          state,
-          flag,
-          role,
-          subrole,
-          enabled,
-          software_status,
-          subtype,
-          arch,
-          class,
-          nid,
-          nid_start,
-          nid_end,
-          partition,
-          group,
-          state_only,
-          flag_only,
-          role_only,
-          nid_only,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get", async {
+        match self {
+          CSM(b) => {
+            b.get(
+              auth_token,
+              id,
+              r#type,
+              state,
+              flag,
+              role,
+              subrole,
+              enabled,
+              software_status,
+              subtype,
+              arch,
+              class,
+              nid,
+              nid_start,
+              nid_end,
+              partition,
+              group,
+              state_only,
+              flag_only,
+              role_only,
+              nid_only,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get(
+              auth_token,
+              id,
+              r#type,
+              state,
+              flag,
+              role,
+              subrole,
+              enabled,
+              software_status,
+              subtype,
+              arch,
+              class,
+              nid,
+              nid_start,
+              nid_end,
+              partition,
+              group,
+              state_only,
+              flag_only,
+              role_only,
+              nid_only,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn post_nodes(
@@ -487,10 +622,14 @@ impl ComponentTrait for StaticBackendDispatcher {
     auth_token: &str,
     component: ComponentArrayPostArray,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => b.post_nodes(auth_token, component).await,
-      OCHAMI(b) => b.post_nodes(auth_token, component).await,
-    }
+    self
+      .timed("post_nodes", async {
+        match self {
+          CSM(b) => b.post_nodes(auth_token, component).await,
+          OCHAMI(b) => b.post_nodes(auth_token, component).await,
+        }
+      })
+      .await
   }
 
   async fn delete_node(
@@ -498,10 +637,14 @@ impl ComponentTrait for StaticBackendDispatcher {
     auth_token: &str,
     id: &str,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.delete_node(auth_token, id).await,
-      OCHAMI(b) => b.delete_node(auth_token, id).await,
-    }
+    self
+      .timed("delete_node", async {
+        match self {
+          CSM(b) => b.delete_node(auth_token, id).await,
+          OCHAMI(b) => b.delete_node(auth_token, id).await,
+        }
+      })
+      .await
   }
 
   async fn nid_to_xname(
@@ -510,10 +653,16 @@ impl ComponentTrait for StaticBackendDispatcher {
     user_input_nid: &str,
     is_regex: bool,
   ) -> Result<Vec<String>, Error> {
-    match self {
-      CSM(b) => b.nid_to_xname(auth_token, user_input_nid, is_regex).await,
-      OCHAMI(b) => b.nid_to_xname(auth_token, user_input_nid, is_regex).await,
-    }
+    self
+      .timed("nid_to_xname", async {
+        match self {
+          CSM(b) => b.nid_to_xname(auth_token, user_input_nid, is_regex).await,
+          OCHAMI(b) => {
+            b.nid_to_xname(auth_token, user_input_nid, is_regex).await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -523,10 +672,14 @@ impl PCSTrait for StaticBackendDispatcher {
     auth_token: &str,
     nodes: &[String],
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.power_on_sync(auth_token, nodes).await,
-      OCHAMI(b) => b.power_on_sync(auth_token, nodes).await,
-    }
+    self
+      .timed("power_on_sync", async {
+        match self {
+          CSM(b) => b.power_on_sync(auth_token, nodes).await,
+          OCHAMI(b) => b.power_on_sync(auth_token, nodes).await,
+        }
+      })
+      .await
   }
 
   async fn power_off_sync(
@@ -535,10 +688,14 @@ impl PCSTrait for StaticBackendDispatcher {
     nodes: &[String],
     force: bool,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.power_off_sync(auth_token, nodes, force).await,
-      OCHAMI(b) => b.power_off_sync(auth_token, nodes, force).await,
-    }
+    self
+      .timed("power_off_sync", async {
+        match self {
+          CSM(b) => b.power_off_sync(auth_token, nodes, force).await,
+          OCHAMI(b) => b.power_off_sync(auth_token, nodes, force).await,
+        }
+      })
+      .await
   }
 
   async fn power_reset_sync(
@@ -547,10 +704,14 @@ impl PCSTrait for StaticBackendDispatcher {
     nodes: &[String],
     force: bool,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.power_reset_sync(auth_token, nodes, force).await,
-      OCHAMI(b) => b.power_reset_sync(auth_token, nodes, force).await,
-    }
+    self
+      .timed("power_reset_sync", async {
+        match self {
+          CSM(b) => b.power_reset_sync(auth_token, nodes, force).await,
+          OCHAMI(b) => b.power_reset_sync(auth_token, nodes, force).await,
+        }
+      })
+      .await
   }
 
   async fn power_status(
@@ -560,26 +721,30 @@ impl PCSTrait for StaticBackendDispatcher {
     power_status_filter: Option<&str>,
     management_state_filter: Option<&str>,
   ) -> Result<FrontEndPowerStatusAll, Error> {
-    match self {
-      CSM(b) => {
-        b.power_status(
-          auth_token,
-          nodes,
-          power_status_filter,
-          management_state_filter,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.power_status(
-          auth_token,
-          nodes,
-          power_status_filter,
-          management_state_filter,
-        )
-        .await
-      }
-    }
+    self
+      .timed("power_status", async {
+        match self {
+          CSM(b) => {
+            b.power_status(
+              auth_token,
+              nodes,
+              power_status_filter,
+              management_state_filter,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.power_status(
+              auth_token,
+              nodes,
+              power_status_filter,
+              management_state_filter,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -588,10 +753,14 @@ impl BootParametersTrait for StaticBackendDispatcher {
     &self,
     auth_token: &str,
   ) -> Result<Vec<BootParameters>, Error> {
-    match self {
-      CSM(b) => b.get_all_bootparameters(auth_token).await,
-      OCHAMI(b) => b.get_all_bootparameters(auth_token).await,
-    }
+    self
+      .timed("get_all_bootparameters", async {
+        match self {
+          CSM(b) => b.get_all_bootparameters(auth_token).await,
+          OCHAMI(b) => b.get_all_bootparameters(auth_token).await,
+        }
+      })
+      .await
   }
 
   async fn get_bootparameters(
@@ -599,10 +768,14 @@ impl BootParametersTrait for StaticBackendDispatcher {
     auth_token: &str,
     nodes: &[String],
   ) -> Result<Vec<BootParameters>, Error> {
-    match self {
-      CSM(b) => b.get_bootparameters(auth_token, nodes).await,
-      OCHAMI(b) => b.get_bootparameters(auth_token, nodes).await,
-    }
+    self
+      .timed("get_bootparameters", async {
+        match self {
+          CSM(b) => b.get_bootparameters(auth_token, nodes).await,
+          OCHAMI(b) => b.get_bootparameters(auth_token, nodes).await,
+        }
+      })
+      .await
   }
 
   async fn add_bootparameters(
@@ -610,10 +783,14 @@ impl BootParametersTrait for StaticBackendDispatcher {
     auth_token: &str,
     boot_parameters: &BootParameters,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => b.add_bootparameters(auth_token, boot_parameters).await,
-      OCHAMI(b) => b.add_bootparameters(auth_token, boot_parameters).await,
-    }
+    self
+      .timed("add_bootparameters", async {
+        match self {
+          CSM(b) => b.add_bootparameters(auth_token, boot_parameters).await,
+          OCHAMI(b) => b.add_bootparameters(auth_token, boot_parameters).await,
+        }
+      })
+      .await
   }
 
   async fn update_bootparameters(
@@ -621,10 +798,16 @@ impl BootParametersTrait for StaticBackendDispatcher {
     auth_token: &str,
     boot_parameters: &BootParameters,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => b.update_bootparameters(auth_token, boot_parameters).await,
-      OCHAMI(b) => b.update_bootparameters(auth_token, boot_parameters).await,
-    }
+    self
+      .timed("update_bootparameters", async {
+        match self {
+          CSM(b) => b.update_bootparameters(auth_token, boot_parameters).await,
+          OCHAMI(b) => {
+            b.update_bootparameters(auth_token, boot_parameters).await
+          }
+        }
+      })
+      .await
   }
 
   async fn delete_bootparameters(
@@ -632,10 +815,16 @@ impl BootParametersTrait for StaticBackendDispatcher {
     auth_token: &str,
     boot_parameters: &BootParameters,
   ) -> Result<String, Error> {
-    match self {
-      CSM(b) => b.delete_bootparameters(auth_token, boot_parameters).await,
-      OCHAMI(b) => b.delete_bootparameters(auth_token, boot_parameters).await,
-    }
+    self
+      .timed("delete_bootparameters", async {
+        match self {
+          CSM(b) => b.delete_bootparameters(auth_token, boot_parameters).await,
+          OCHAMI(b) => {
+            b.delete_bootparameters(auth_token, boot_parameters).await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -644,10 +833,14 @@ impl RedfishEndpointTrait for StaticBackendDispatcher {
     &self,
     auth_token: &str,
   ) -> Result<RedfishEndpointArray, Error> {
-    match self {
-      CSM(b) => b.get_all_redfish_endpoints(auth_token).await,
-      OCHAMI(b) => b.get_all_redfish_endpoints(auth_token).await,
-    }
+    self
+      .timed("get_all_redfish_endpoints", async {
+        match self {
+          CSM(b) => b.get_all_redfish_endpoints(auth_token).await,
+          OCHAMI(b) => b.get_all_redfish_endpoints(auth_token).await,
+        }
+      })
+      .await
   }
 
   async fn get_redfish_endpoints(
@@ -661,34 +854,38 @@ impl RedfishEndpointTrait for StaticBackendDispatcher {
     ip_address: Option<&str>,
     last_status: Option<&str>,
   ) -> Result<RedfishEndpointArray, Error> {
-    match self {
-      CSM(b) => {
-        b.get_redfish_endpoints(
-          auth_token,
-          id,
-          fqdn,
-          r#type,
-          uuid,
-          macaddr,
-          ip_address,
-          last_status,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_redfish_endpoints(
-          auth_token,
-          id,
-          fqdn,
-          r#type,
-          uuid,
-          macaddr,
-          ip_address,
-          last_status,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_redfish_endpoints", async {
+        match self {
+          CSM(b) => {
+            b.get_redfish_endpoints(
+              auth_token,
+              id,
+              fqdn,
+              r#type,
+              uuid,
+              macaddr,
+              ip_address,
+              last_status,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_redfish_endpoints(
+              auth_token,
+              id,
+              fqdn,
+              r#type,
+              uuid,
+              macaddr,
+              ip_address,
+              last_status,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn add_redfish_endpoint(
@@ -696,10 +893,16 @@ impl RedfishEndpointTrait for StaticBackendDispatcher {
     auth_token: &str,
     redfish_endpoint: &RedfishEndpointArray,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => b.add_redfish_endpoint(auth_token, redfish_endpoint).await,
-      OCHAMI(b) => b.add_redfish_endpoint(auth_token, redfish_endpoint).await,
-    }
+    self
+      .timed("add_redfish_endpoint", async {
+        match self {
+          CSM(b) => b.add_redfish_endpoint(auth_token, redfish_endpoint).await,
+          OCHAMI(b) => {
+            b.add_redfish_endpoint(auth_token, redfish_endpoint).await
+          }
+        }
+      })
+      .await
   }
 
   async fn update_redfish_endpoint(
@@ -707,16 +910,20 @@ impl RedfishEndpointTrait for StaticBackendDispatcher {
     auth_token: &str,
     redfish_endpoint: &RedfishEndpoint,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.update_redfish_endpoint(auth_token, redfish_endpoint)
-          .await
-      }
-      OCHAMI(b) => {
-        b.update_redfish_endpoint(auth_token, redfish_endpoint)
-          .await
-      }
-    }
+    self
+      .timed("update_redfish_endpoint", async {
+        match self {
+          CSM(b) => {
+            b.update_redfish_endpoint(auth_token, redfish_endpoint)
+              .await
+          }
+          OCHAMI(b) => {
+            b.update_redfish_endpoint(auth_token, redfish_endpoint)
+              .await
+          }
+        }
+      })
+      .await
   }
 
   async fn delete_redfish_endpoint(
@@ -724,10 +931,14 @@ impl RedfishEndpointTrait for StaticBackendDispatcher {
     auth_token: &str,
     id: &str,
   ) -> Result<Value, Error> {
-    match self {
-      CSM(b) => b.delete_redfish_endpoint(auth_token, id).await,
-      OCHAMI(b) => b.delete_redfish_endpoint(auth_token, id).await,
-    }
+    self
+      .timed("delete_redfish_endpoint", async {
+        match self {
+          CSM(b) => b.delete_redfish_endpoint(auth_token, id).await,
+          OCHAMI(b) => b.delete_redfish_endpoint(auth_token, id).await,
+        }
+      })
+      .await
   }
 }
 
@@ -741,16 +952,30 @@ impl CfsTrait for StaticBackendDispatcher {
     shasta_root_cert: &[u8],
     session: &CfsSessionPostRequest,
   ) -> Result<CfsSessionGetResponse, Error> {
-    match self {
-      CSM(b) => {
-        b.post_session(shasta_token, shasta_base_url, shasta_root_cert, session)
-          .await
-      }
-      OCHAMI(b) => {
-        b.post_session(shasta_token, shasta_base_url, shasta_root_cert, session)
-          .await
-      }
-    }
+    self
+      .timed("post_session", async {
+        match self {
+          CSM(b) => {
+            b.post_session(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              session,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.post_session(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              session,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_sessions(
@@ -768,42 +993,46 @@ impl CfsTrait for StaticBackendDispatcher {
     is_succeded_opt: Option<bool>,
     tags_opt: Option<String>,
   ) -> Result<Vec<CfsSessionGetResponse>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_sessions(
-          auth_token,
-          base_url,
-          root_cert,
-          session_name_opt,
-          limit_opt,
-          after_id_opt,
-          min_age_opt,
-          max_age_opt,
-          status_opt,
-          name_contains_opt,
-          is_succeded_opt,
-          tags_opt,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_sessions(
-          auth_token,
-          base_url,
-          root_cert,
-          session_name_opt,
-          limit_opt,
-          after_id_opt,
-          min_age_opt,
-          max_age_opt,
-          status_opt,
-          name_contains_opt,
-          is_succeded_opt,
-          tags_opt,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_sessions", async {
+        match self {
+          CSM(b) => {
+            b.get_sessions(
+              auth_token,
+              base_url,
+              root_cert,
+              session_name_opt,
+              limit_opt,
+              after_id_opt,
+              min_age_opt,
+              max_age_opt,
+              status_opt,
+              name_contains_opt,
+              is_succeded_opt,
+              tags_opt,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_sessions(
+              auth_token,
+              base_url,
+              root_cert,
+              session_name_opt,
+              limit_opt,
+              after_id_opt,
+              min_age_opt,
+              max_age_opt,
+              status_opt,
+              name_contains_opt,
+              is_succeded_opt,
+              tags_opt,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_and_filter_sessions(
@@ -821,42 +1050,46 @@ impl CfsTrait for StaticBackendDispatcher {
     limit_number_opt: Option<&u8>,
     is_succeded_opt: Option<bool>,
   ) -> Result<Vec<CfsSessionGetResponse>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_and_filter_sessions(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          hsm_group_name_vec_opt,
-          xname_vec_opt,
-          min_age_opt,
-          max_age_opt,
-          type_opt,
-          status_opt,
-          cfs_session_name_opt,
-          limit_number_opt,
-          is_succeded_opt,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_and_filter_sessions(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          hsm_group_name_vec_opt,
-          xname_vec_opt,
-          min_age_opt,
-          max_age_opt,
-          type_opt,
-          status_opt,
-          cfs_session_name_opt,
-          limit_number_opt,
-          is_succeded_opt,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_and_filter_sessions", async {
+        match self {
+          CSM(b) => {
+            b.get_and_filter_sessions(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              hsm_group_name_vec_opt,
+              xname_vec_opt,
+              min_age_opt,
+              max_age_opt,
+              type_opt,
+              status_opt,
+              cfs_session_name_opt,
+              limit_number_opt,
+              is_succeded_opt,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_and_filter_sessions(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              hsm_group_name_vec_opt,
+              xname_vec_opt,
+              min_age_opt,
+              max_age_opt,
+              type_opt,
+              status_opt,
+              cfs_session_name_opt,
+              limit_number_opt,
+              is_succeded_opt,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn create_configuration_from_repos(
@@ -868,30 +1101,34 @@ impl CfsTrait for StaticBackendDispatcher {
     local_git_commit_vec: &[&str],
     playbook_file_name_opt: Option<&str>,
   ) -> Result<CfsConfigurationRequest, Error> {
-    match self {
-      CSM(b) => {
-        b.create_configuration_from_repos(
-          gitea_token,
-          gitea_base_url,
-          shasta_root_cert,
-          repo_name_vec,
-          local_git_commit_vec,
-          playbook_file_name_opt,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.create_configuration_from_repos(
-          gitea_token,
-          gitea_base_url,
-          shasta_root_cert,
-          repo_name_vec,
-          local_git_commit_vec,
-          playbook_file_name_opt,
-        )
-        .await
-      }
-    }
+    self
+      .timed("create_configuration_from_repos", async {
+        match self {
+          CSM(b) => {
+            b.create_configuration_from_repos(
+              gitea_token,
+              gitea_base_url,
+              shasta_root_cert,
+              repo_name_vec,
+              local_git_commit_vec,
+              playbook_file_name_opt,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.create_configuration_from_repos(
+              gitea_token,
+              gitea_base_url,
+              shasta_root_cert,
+              repo_name_vec,
+              local_git_commit_vec,
+              playbook_file_name_opt,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_configuration(
@@ -901,26 +1138,30 @@ impl CfsTrait for StaticBackendDispatcher {
     root_cert: &[u8],
     cfs_configuration_name_opt: Option<&String>,
   ) -> Result<Vec<CfsConfigurationResponse>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_configuration(
-          auth_token,
-          base_url,
-          root_cert,
-          cfs_configuration_name_opt,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_configuration(
-          auth_token,
-          base_url,
-          root_cert,
-          cfs_configuration_name_opt,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_configuration", async {
+        match self {
+          CSM(b) => {
+            b.get_configuration(
+              auth_token,
+              base_url,
+              root_cert,
+              cfs_configuration_name_opt,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_configuration(
+              auth_token,
+              base_url,
+              root_cert,
+              cfs_configuration_name_opt,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_and_filter_configuration(
@@ -935,36 +1176,40 @@ impl CfsTrait for StaticBackendDispatcher {
     until_opt: Option<NaiveDateTime>,
     limit_number_opt: Option<&u8>,
   ) -> Result<Vec<CfsConfigurationResponse>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_and_filter_configuration(
-          auth_token,
-          base_url,
-          root_cert,
-          configuration_name,
-          configuration_name_pattern,
-          hsm_group_name_vec,
-          since_opt,
-          until_opt,
-          limit_number_opt,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_and_filter_configuration(
-          auth_token,
-          base_url,
-          root_cert,
-          configuration_name,
-          configuration_name_pattern,
-          hsm_group_name_vec,
-          since_opt,
-          until_opt,
-          limit_number_opt,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_and_filter_configuration", async {
+        match self {
+          CSM(b) => {
+            b.get_and_filter_configuration(
+              auth_token,
+              base_url,
+              root_cert,
+              configuration_name,
+              configuration_name_pattern,
+              hsm_group_name_vec,
+              since_opt,
+              until_opt,
+              limit_number_opt,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_and_filter_configuration(
+              auth_token,
+              base_url,
+              root_cert,
+              configuration_name,
+              configuration_name_pattern,
+              hsm_group_name_vec,
+              since_opt,
+              until_opt,
+              limit_number_opt,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_configuration_layer_details(
@@ -975,28 +1220,32 @@ impl CfsTrait for StaticBackendDispatcher {
     layer: Layer,
     site_name: &str,
   ) -> Result<LayerDetails, Error> {
-    match self {
-      CSM(b) => {
-        b.get_configuration_layer_details(
-          shasta_root_cert,
-          gitea_base_url,
-          gitea_token,
-          layer,
-          site_name,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_configuration_layer_details(
-          shasta_root_cert,
-          gitea_base_url,
-          gitea_token,
-          layer,
-          site_name,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_configuration_layer_details", async {
+        match self {
+          CSM(b) => {
+            b.get_configuration_layer_details(
+              shasta_root_cert,
+              gitea_base_url,
+              gitea_token,
+              layer,
+              site_name,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_configuration_layer_details(
+              shasta_root_cert,
+              gitea_base_url,
+              gitea_token,
+              layer,
+              site_name,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn update_runtime_configuration(
@@ -1008,30 +1257,34 @@ impl CfsTrait for StaticBackendDispatcher {
     desired_configuration: &str,
     enabled: bool,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.update_runtime_configuration(
-          auth_token,
-          base_url,
-          root_cert,
-          xnames,
-          desired_configuration,
-          enabled,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.update_runtime_configuration(
-          auth_token,
-          base_url,
-          root_cert,
-          xnames,
-          desired_configuration,
-          enabled,
-        )
-        .await
-      }
-    }
+    self
+      .timed("update_runtime_configuration", async {
+        match self {
+          CSM(b) => {
+            b.update_runtime_configuration(
+              auth_token,
+              base_url,
+              root_cert,
+              xnames,
+              desired_configuration,
+              enabled,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.update_runtime_configuration(
+              auth_token,
+              base_url,
+              root_cert,
+              xnames,
+              desired_configuration,
+              enabled,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn put_configuration(
@@ -1043,30 +1296,34 @@ impl CfsTrait for StaticBackendDispatcher {
     configuration_name: &str,
     overwrite: bool,
   ) -> Result<CfsConfigurationResponse, Error> {
-    match self {
-      CSM(b) => {
-        b.put_configuration(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          configuration,
-          configuration_name,
-          overwrite,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.put_configuration(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          configuration,
-          configuration_name,
-          overwrite,
-        )
-        .await
-      }
-    }
+    self
+      .timed("put_configuration", async {
+        match self {
+          CSM(b) => {
+            b.put_configuration(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              configuration,
+              configuration_name,
+              overwrite,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.put_configuration(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              configuration,
+              configuration_name,
+              overwrite,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   // Get all CFS sessions, IMS images and BOS sessiontemplates related to a CFS configuration
@@ -1084,16 +1341,30 @@ impl CfsTrait for StaticBackendDispatcher {
     ),
     Error,
   > {
-    match self {
-      CSM(b) => {
-        b.get_derivatives(auth_token, base_url, root_cert, configuration_name)
-          .await
-      }
-      OCHAMI(b) => {
-        b.get_derivatives(auth_token, base_url, root_cert, configuration_name)
-          .await
-      }
-    }
+    self
+      .timed("get_derivatives", async {
+        match self {
+          CSM(b) => {
+            b.get_derivatives(
+              auth_token,
+              base_url,
+              root_cert,
+              configuration_name,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_derivatives(
+              auth_token,
+              base_url,
+              root_cert,
+              configuration_name,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_session_logs_stream(
@@ -1104,28 +1375,32 @@ impl CfsTrait for StaticBackendDispatcher {
     timestamps: bool,
     k8s: &K8sDetails,
   ) -> Result<Pin<Box<dyn AsyncBufRead + Send>>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_session_logs_stream(
-          shasta_token,
-          site_name,
-          cfs_session_name,
-          timestamps,
-          k8s,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_session_logs_stream(
-          shasta_token,
-          site_name,
-          cfs_session_name,
-          timestamps,
-          k8s,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_session_logs_stream", async {
+        match self {
+          CSM(b) => {
+            b.get_session_logs_stream(
+              shasta_token,
+              site_name,
+              cfs_session_name,
+              timestamps,
+              k8s,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_session_logs_stream(
+              shasta_token,
+              site_name,
+              cfs_session_name,
+              timestamps,
+              k8s,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 
   async fn get_session_logs_stream_by_xname(
@@ -1136,20 +1411,24 @@ impl CfsTrait for StaticBackendDispatcher {
     timestamps: bool,
     k8s: &K8sDetails,
   ) -> Result<Pin<Box<dyn AsyncBufRead + Send>>, Error> {
-    match self {
-      CSM(b) => {
-        b.get_session_logs_stream_by_xname(
-          auth_token, site_name, xname, timestamps, k8s,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.get_session_logs_stream_by_xname(
-          auth_token, site_name, xname, timestamps, k8s,
-        )
-        .await
-      }
-    }
+    self
+      .timed("get_session_logs_stream_by_xname", async {
+        match self {
+          CSM(b) => {
+            b.get_session_logs_stream_by_xname(
+              auth_token, site_name, xname, timestamps, k8s,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.get_session_logs_stream_by_xname(
+              auth_token, site_name, xname, timestamps, k8s,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -1176,56 +1455,60 @@ impl SatTrait for StaticBackendDispatcher {
     overwrite: bool,
     dry_run: bool,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.apply_sat_file(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          vault_base_url,
-          vault_secret_path,
-          k8s_api_url,
-          shasta_k8s_secrets,
-          sat_template_file_yaml,
-          hsm_group_available_vec,
-          ansible_verbosity_opt,
-          ansible_passthrough_opt,
-          gitea_base_url,
-          gitea_token,
-          do_not_reboot,
-          watch_logs,
-          timestamps,
-          debug_on_failure,
-          overwrite,
-          dry_run,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.apply_sat_file(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          vault_base_url,
-          vault_secret_path,
-          k8s_api_url,
-          shasta_k8s_secrets,
-          sat_template_file_yaml,
-          hsm_group_available_vec,
-          ansible_verbosity_opt,
-          ansible_passthrough_opt,
-          gitea_base_url,
-          gitea_token,
-          do_not_reboot,
-          watch_logs,
-          timestamps,
-          debug_on_failure,
-          overwrite,
-          dry_run,
-        )
-        .await
-      }
-    }
+    self
+      .timed("apply_sat_file", async {
+        match self {
+          CSM(b) => {
+            b.apply_sat_file(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              vault_base_url,
+              vault_secret_path,
+              k8s_api_url,
+              shasta_k8s_secrets,
+              sat_template_file_yaml,
+              hsm_group_available_vec,
+              ansible_verbosity_opt,
+              ansible_passthrough_opt,
+              gitea_base_url,
+              gitea_token,
+              do_not_reboot,
+              watch_logs,
+              timestamps,
+              debug_on_failure,
+              overwrite,
+              dry_run,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.apply_sat_file(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              vault_base_url,
+              vault_secret_path,
+              k8s_api_url,
+              shasta_k8s_secrets,
+              sat_template_file_yaml,
+              hsm_group_available_vec,
+              ansible_verbosity_opt,
+              ansible_passthrough_opt,
+              gitea_base_url,
+              gitea_token,
+              do_not_reboot,
+              watch_logs,
+              timestamps,
+              debug_on_failure,
+              overwrite,
+              dry_run,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -1242,36 +1525,40 @@ impl ApplyHwClusterPin for StaticBackendDispatcher {
     create_target_hsm_group: bool,
     delete_empty_parent_hsm_group: bool,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.apply_hw_cluster_pin(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          target_hsm_group_name,
-          parent_hsm_group_name,
-          pattern,
-          nodryrun,
-          create_target_hsm_group,
-          delete_empty_parent_hsm_group,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.apply_hw_cluster_pin(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          target_hsm_group_name,
-          parent_hsm_group_name,
-          pattern,
-          nodryrun,
-          create_target_hsm_group,
-          delete_empty_parent_hsm_group,
-        )
-        .await
-      }
-    }
+    self
+      .timed("apply_hw_cluster_pin", async {
+        match self {
+          CSM(b) => {
+            b.apply_hw_cluster_pin(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              target_hsm_group_name,
+              parent_hsm_group_name,
+              pattern,
+              nodryrun,
+              create_target_hsm_group,
+              delete_empty_parent_hsm_group,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.apply_hw_cluster_pin(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              target_hsm_group_name,
+              parent_hsm_group_name,
+              pattern,
+              nodryrun,
+              create_target_hsm_group,
+              delete_empty_parent_hsm_group,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -1281,10 +1568,14 @@ impl ImsTrait for StaticBackendDispatcher {
     shasta_token: &str,
     image_id_opt: Option<&str>,
   ) -> Result<Vec<Image>, Error> {
-    match self {
-      CSM(b) => b.get_images(shasta_token, image_id_opt).await,
-      OCHAMI(b) => b.get_images(shasta_token, image_id_opt).await,
-    }
+    self
+      .timed("get_images", async {
+        match self {
+          CSM(b) => b.get_images(shasta_token, image_id_opt).await,
+          OCHAMI(b) => b.get_images(shasta_token, image_id_opt).await,
+        }
+      })
+      .await
   }
 }
 
@@ -1308,44 +1599,48 @@ impl ApplySessionTrait for StaticBackendDispatcher {
     /* kafka_audit: &Kafka,
     k8s: &K8sDetails, */
   ) -> Result<(String, String), Error> {
-    match self {
-      CSM(b) => {
-        b.apply_session(
-          gitea_token,
-          gitea_base_url,
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          cfs_conf_sess_name,
-          playbook_yaml_file_name_opt,
-          hsm_group,
-          repos_name_vec,
-          repos_last_commit_id_vec,
-          ansible_limit,
-          ansible_verbosity,
-          ansible_passthrough,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.apply_session(
-          gitea_token,
-          gitea_base_url,
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          cfs_conf_sess_name,
-          playbook_yaml_file_name_opt,
-          hsm_group,
-          repos_name_vec,
-          repos_last_commit_id_vec,
-          ansible_limit,
-          ansible_verbosity,
-          ansible_passthrough,
-        )
-        .await
-      }
-    }
+    self
+      .timed("apply_session", async {
+        match self {
+          CSM(b) => {
+            b.apply_session(
+              gitea_token,
+              gitea_base_url,
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              cfs_conf_sess_name,
+              playbook_yaml_file_name_opt,
+              hsm_group,
+              repos_name_vec,
+              repos_last_commit_id_vec,
+              ansible_limit,
+              ansible_verbosity,
+              ansible_passthrough,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.apply_session(
+              gitea_token,
+              gitea_base_url,
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              cfs_conf_sess_name,
+              playbook_yaml_file_name_opt,
+              hsm_group,
+              repos_name_vec,
+              repos_last_commit_id_vec,
+              ansible_limit,
+              ansible_verbosity,
+              ansible_passthrough,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -1365,42 +1660,46 @@ impl MigrateRestoreTrait for StaticBackendDispatcher {
     overwrite_image: bool,
     overwrite_template: bool,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.migrate_restore(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          bos_file,
-          cfs_file,
-          hsm_file,
-          ims_file,
-          image_dir,
-          overwrite_group,
-          overwrite_configuration,
-          overwrite_image,
-          overwrite_template,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.migrate_restore(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          bos_file,
-          cfs_file,
-          hsm_file,
-          ims_file,
-          image_dir,
-          overwrite_group,
-          overwrite_configuration,
-          overwrite_image,
-          overwrite_template,
-        )
-        .await
-      }
-    }
+    self
+      .timed("migrate_restore", async {
+        match self {
+          CSM(b) => {
+            b.migrate_restore(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              bos_file,
+              cfs_file,
+              hsm_file,
+              ims_file,
+              image_dir,
+              overwrite_group,
+              overwrite_configuration,
+              overwrite_image,
+              overwrite_template,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.migrate_restore(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              bos_file,
+              cfs_file,
+              hsm_file,
+              ims_file,
+              image_dir,
+              overwrite_group,
+              overwrite_configuration,
+              overwrite_image,
+              overwrite_template,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
 
@@ -1413,27 +1712,31 @@ impl MigrateBackupTrait for StaticBackendDispatcher {
     bos: Option<&str>,
     destination: Option<&str>,
   ) -> Result<(), Error> {
-    match self {
-      CSM(b) => {
-        b.migrate_backup(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          bos,
-          destination,
-        )
-        .await
-      }
-      OCHAMI(b) => {
-        b.migrate_backup(
-          shasta_token,
-          shasta_base_url,
-          shasta_root_cert,
-          bos,
-          destination,
-        )
-        .await
-      }
-    }
+    self
+      .timed("migrate_backup", async {
+        match self {
+          CSM(b) => {
+            b.migrate_backup(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              bos,
+              destination,
+            )
+            .await
+          }
+          OCHAMI(b) => {
+            b.migrate_backup(
+              shasta_token,
+              shasta_base_url,
+              shasta_root_cert,
+              bos,
+              destination,
+            )
+            .await
+          }
+        }
+      })
+      .await
   }
 }
@@ -0,0 +1,206 @@
+//! OpenAPI document of the routes served by manta-ws, rendered on `/openapi`
+//! and browsable on `/docs`
+
+mod schemas;
+
+pub use schemas::*;
+
+use utoipa::{
+  Modify, OpenApi,
+  openapi::{
+    Content, OpenApi as OpenApiDoc, Ref, RefOr, Response,
+    security::{Http, HttpAuthScheme, SecurityScheme},
+  },
+};
+
+use crate::common::audit_file::AuditRecord;
+use crate::common::power_jobs::{
+  JobNode, JobStatus, NodeTransition, PowerAction, PowerJob,
+};
+use crate::error::{ErrorCode, ProblemDetails};
+use crate::handlers::get_audit::AuditPage;
+use crate::handlers::get_group_power_status::{
+  GroupPowerStatus, ManagementStateCounts, PowerStateCounts,
+};
+use crate::handlers::post_power::PowerRequest;
+
+#[derive(OpenApi)]
+#[openapi(
+  info(
+    title = "Manta API",
+    description = "API for managing Manta services",
+    version = "0.1.2"
+  ),
+  paths(
+    crate::root,
+    crate::test_ping,
+    crate::test_ws,
+    crate::test_whoami,
+    crate::get_openapi,
+    crate::get_version,
+    crate::get_healthz,
+    crate::handlers::get_readyz::get_readyz,
+    crate::create_user,
+    crate::authenticate,
+    crate::handlers::get_kernel_parameters::get_kernel_parameters,
+    crate::handlers::get_audit::get_audit,
+    crate::get_cfs_health_check,
+    crate::get_bos_health_check,
+    crate::handlers::get_health_services::get_health_services,
+    crate::get_all_bss_boot_parameters,
+    crate::get_bss_boot_parameters,
+    crate::post_bss_boot_parameters,
+    crate::delete_bss_boot_parameters,
+    crate::commands::get_all_redfish,
+    crate::commands::get_redfish,
+    crate::commands::post_redfish,
+    crate::commands::delete_redfish,
+    crate::ws_console,
+    crate::get_cfs_session,
+    crate::ws_cfs_session_logs,
+    crate::get_all_groups,
+    crate::get_group_details,
+    crate::get_hsm_hardware,
+    crate::handlers::post_power::post_power_on,
+    crate::handlers::post_power::post_power_off,
+    crate::handlers::post_power::post_power_reset,
+    crate::handlers::get_jobs::get_jobs,
+    crate::handlers::get_job::get_job,
+    crate::handlers::delete_job::delete_job,
+    crate::handlers::get_job_events::get_job_events,
+    crate::power_node_moved,
+    crate::power_status_node,
+    crate::handlers::get_group_power_status::get_group_power_status,
+    crate::node_migration,
+  ),
+  components(schemas(
+    ProblemDetails,
+    ErrorCode,
+    AuditPage,
+    AuditRecord,
+    BootParametersSchema,
+    RedfishEndpointArraySchema,
+    RedfishEndpointSchema,
+    DiscoveryInfoSchema,
+    GroupSchema,
+    MemberSchema,
+    NodeDetailsSchema,
+    NodeSummarySchema,
+    ArtifactSummarySchema,
+    ArtifactTypeSchema,
+    PowerStatusAllSchema,
+    PowerStatusSchema,
+    PowerStateSchema,
+    ManagementStateSchema,
+    PowerTransitionSchema,
+    GroupPowerStatus,
+    PowerStateCounts,
+    ManagementStateCounts,
+    PowerAction,
+    PowerRequest,
+    PowerJob,
+    JobStatus,
+    JobNode,
+    NodeTransition,
+    CfsSessionSchema,
+  )),
+  modifiers(&SecuritySchemes, &ErrorResponses),
+  tags(
+    (name = "auth", description = "Tokens"),
+    (name = "audit", description = "Audit log"),
+    (name = "bss", description = "Boot parameters"),
+    (name = "redfish", description = "Redfish endpoints"),
+    (name = "cfs", description = "CFS sessions"),
+    (name = "console", description = "Node consoles"),
+    (name = "group", description = "HSM groups"),
+    (name = "power", description = "Node power"),
+    (name = "health", description = "Backend services health"),
+    (name = "meta", description = "Server information and tests"),
+  )
+)]
+pub struct ApiDoc;
+
+/// `bearer_token`: Keycloak token of the selected site.
+/// `client_certificate`: client certificate mapped to an identity in
+/// `client_identities`, used instead of a token.
+/// `basic`: Keycloak credentials, only on `/authenticate`
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+  fn modify(&self, openapi: &mut OpenApiDoc) {
+    let components = openapi.components.get_or_insert_with(Default::default);
+
+    components.add_security_scheme(
+      "bearer_token",
+      SecurityScheme::Http(
+        Http::builder()
+          .scheme(HttpAuthScheme::Bearer)
+          .bearer_format("JWT")
+          .build(),
+      ),
+    );
+    components.add_security_scheme(
+      "client_certificate",
+      SecurityScheme::MutualTls {
+        description: Some(
+          "Client certificate mapped to an identity in 'client_identities'"
+            .to_string(),
+        ),
+        extensions: None,
+      },
+    );
+    components.add_security_scheme(
+      "basic",
+      SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)),
+    );
+  }
+}
+
+/// Every error is a `ProblemDetails` document. Adds it to the error
+/// responses declared by the routes, and as the `default` response for the
+/// ones they do not declare
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+  fn modify(&self, openapi: &mut OpenApiDoc) {
+    let problem_details =
+      Content::new(Some(Ref::from_schema_name("ProblemDetails")));
+
+    for path_item in openapi.paths.paths.values_mut() {
+      for operation in [
+        &mut path_item.get,
+        &mut path_item.put,
+        &mut path_item.post,
+        &mut path_item.delete,
+        &mut path_item.patch,
+      ]
+      .into_iter()
+      .flatten()
+      {
+        let responses = &mut operation.responses.responses;
+
+        for (status, response) in responses.iter_mut() {
+          if let RefOr::T(response) = response
+            && !status.starts_with('1')
+            && !status.starts_with('2')
+            && response.content.is_empty()
+          {
+            response.content.insert(
+              "application/problem+json".to_string(),
+              problem_details.clone(),
+            );
+          }
+        }
+
+        let mut default_response = Response::new("Error");
+        default_response.content.insert(
+          "application/problem+json".to_string(),
+          problem_details.clone(),
+        );
+        responses
+          .entry("default".to_string())
+          .or_insert(RefOr::T(default_response));
+      }
+    }
+  }
+}
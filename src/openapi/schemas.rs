@@ -0,0 +1,287 @@
+//! Dispatcher and csm-rs types do not implement `ToSchema`. The structs below
+//! describe their JSON representation and must be kept in sync with them.
+//! They are only read by the `utoipa` derives, never built
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+/// Site the request targets. Defaults to `site` in the configuration
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct SiteHeader {
+  #[param(rename = "X-Manta-Site")]
+  pub site: Option<String>,
+}
+
+/// `manta_backend_dispatcher::types::bss::BootParameters`
+#[derive(ToSchema)]
+#[schema(as = BootParameters)]
+pub struct BootParametersSchema {
+  pub hosts: Vec<String>,
+  pub macs: Option<Vec<String>>,
+  pub nids: Option<Vec<u32>>,
+  /// Kernel parameters, space separated
+  pub params: String,
+  pub kernel: String,
+  pub initrd: String,
+  #[schema(rename = "cloud-init")]
+  pub cloud_init: Option<Value>,
+}
+
+/// `manta_backend_dispatcher::types::hsm::inventory::RedfishEndpointArray`
+#[derive(ToSchema)]
+#[schema(as = RedfishEndpointArray)]
+pub struct RedfishEndpointArraySchema {
+  #[schema(rename = "RedfishEndpoints")]
+  pub redfish_endpoints: Option<Vec<RedfishEndpointSchema>>,
+}
+
+/// `manta_backend_dispatcher::types::hsm::inventory::RedfishEndpoint`
+#[derive(ToSchema)]
+#[schema(as = RedfishEndpoint, rename_all = "PascalCase")]
+pub struct RedfishEndpointSchema {
+  #[schema(rename = "ID")]
+  pub id: String,
+  pub r#type: Option<String>,
+  pub name: Option<String>,
+  pub hostname: Option<String>,
+  pub domain: Option<String>,
+  #[schema(rename = "FQDN")]
+  pub fqdn: Option<String>,
+  pub enabled: Option<bool>,
+  #[schema(rename = "UUID")]
+  pub uuid: Option<String>,
+  pub user: Option<String>,
+  pub password: Option<String>,
+  #[schema(rename = "UseSSDP")]
+  pub use_ssdp: Option<bool>,
+  pub mac_required: Option<bool>,
+  #[schema(rename = "MACAddr")]
+  pub mac_addr: Option<String>,
+  #[schema(rename = "IPAddress")]
+  pub ip_address: Option<String>,
+  pub rediscover_on_update: Option<bool>,
+  #[schema(rename = "TemplateID")]
+  pub template_id: Option<String>,
+  pub discovery_info: Option<DiscoveryInfoSchema>,
+}
+
+/// `manta_backend_dispatcher::types::hsm::inventory::DiscoveryInfo`
+#[derive(ToSchema)]
+#[schema(as = DiscoveryInfo, rename_all = "PascalCase")]
+pub struct DiscoveryInfoSchema {
+  pub last_attempt: Option<String>,
+  pub last_status: Option<String>,
+  pub redfish_version: Option<String>,
+}
+
+/// `manta_backend_dispatcher::types::Group`
+#[derive(ToSchema)]
+#[schema(as = Group)]
+pub struct GroupSchema {
+  pub label: String,
+  pub description: Option<String>,
+  pub tags: Option<Vec<String>>,
+  pub members: Option<MemberSchema>,
+  #[schema(rename = "exclusiveGroup")]
+  pub exclusive_group: Option<String>,
+}
+
+/// `manta_backend_dispatcher::types::Member`
+#[derive(ToSchema)]
+#[schema(as = Member)]
+pub struct MemberSchema {
+  /// Xnames
+  pub ids: Option<Vec<String>>,
+}
+
+/// `csm_rs::node::types::NodeDetails`
+#[derive(ToSchema)]
+#[schema(as = NodeDetails)]
+pub struct NodeDetailsSchema {
+  pub xname: String,
+  pub nid: String,
+  /// HSM groups, comma separated
+  pub hsm: String,
+  pub power_status: String,
+  pub desired_configuration: String,
+  pub configuration_status: String,
+  pub enabled: String,
+  pub error_count: String,
+  pub boot_image_id: String,
+  pub boot_configuration: String,
+  pub kernel_params: String,
+}
+
+/// `csm_rs::hsm::hw_inventory::hw_component::types::NodeSummary`
+#[derive(ToSchema)]
+#[schema(as = NodeSummary)]
+pub struct NodeSummarySchema {
+  pub xname: String,
+  pub r#type: String,
+  pub processors: Vec<ArtifactSummarySchema>,
+  pub memory: Vec<ArtifactSummarySchema>,
+  pub node_accels: Vec<ArtifactSummarySchema>,
+  pub node_hsn_nics: Vec<ArtifactSummarySchema>,
+}
+
+/// `csm_rs::hsm::hw_inventory::hw_component::types::ArtifactSummary`
+#[derive(ToSchema)]
+#[schema(as = ArtifactSummary)]
+pub struct ArtifactSummarySchema {
+  pub xname: String,
+  pub r#type: ArtifactTypeSchema,
+  pub info: Option<String>,
+}
+
+/// `csm_rs::hsm::hw_inventory::hw_component::types::ArtifactType`
+#[derive(ToSchema)]
+#[schema(as = ArtifactType)]
+pub enum ArtifactTypeSchema {
+  Memory,
+  Processor,
+  NodeAccel,
+  NodeHsnNic,
+  Drive,
+  CabinetPDU,
+  CabinetPDUPowerConnector,
+  CMMRectifier,
+  NodeAccelRiser,
+  NodeEnclosurePowerSupplie,
+  NodeBMC,
+  RouterBMC,
+}
+
+/// `manta_backend_dispatcher::types::pcs::power_status::types::PowerStatusAll`
+#[derive(ToSchema)]
+#[schema(as = PowerStatusAll)]
+pub struct PowerStatusAllSchema {
+  pub status: Vec<PowerStatusSchema>,
+}
+
+/// `manta_backend_dispatcher::types::pcs::power_status::types::PowerStatus`
+#[derive(ToSchema)]
+#[schema(as = PowerStatus)]
+pub struct PowerStatusSchema {
+  pub xname: String,
+  #[schema(rename = "powerState")]
+  pub power_state: Option<PowerStateSchema>,
+  pub management_state: Option<ManagementStateSchema>,
+  pub error: Option<String>,
+  #[schema(rename = "supportedPowerTransitions")]
+  pub supported_power_transitions: Vec<PowerTransitionSchema>,
+  pub last_updated: String,
+}
+
+/// `manta_backend_dispatcher::types::pcs::power_status::types::PowerState`
+#[derive(ToSchema)]
+#[schema(as = PowerState, rename_all = "lowercase")]
+pub enum PowerStateSchema {
+  On,
+  Off,
+  Undefined,
+}
+
+/// `manta_backend_dispatcher::types::pcs::power_status::types::ManagementState`
+#[derive(ToSchema)]
+#[schema(as = ManagementState)]
+pub enum ManagementStateSchema {
+  Unavailable,
+  Available,
+}
+
+/// `manta_backend_dispatcher::types::pcs::transitions::types::Operation`
+#[derive(ToSchema)]
+#[schema(as = PowerTransition, rename_all = "kebab-case")]
+pub enum PowerTransitionSchema {
+  On,
+  Off,
+  SoftOff,
+  SoftRestart,
+  HardRestart,
+  Init,
+  ForceOff,
+}
+
+/// `manta_backend_dispatcher::types::cfs::session::CfsSessionGetResponse`
+#[derive(ToSchema)]
+#[schema(as = CfsSession)]
+pub struct CfsSessionSchema {
+  pub name: Option<String>,
+  pub configuration: Option<CfsSessionConfigurationSchema>,
+  pub ansible: Option<CfsSessionAnsibleSchema>,
+  pub target: Option<CfsSessionTargetSchema>,
+  pub status: Option<CfsSessionStatusSchema>,
+  pub tags: Option<HashMap<String, String>>,
+  pub debug_on_failure: bool,
+  pub logs: Option<String>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionConfiguration)]
+pub struct CfsSessionConfigurationSchema {
+  pub name: Option<String>,
+  pub limit: Option<String>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionAnsible)]
+pub struct CfsSessionAnsibleSchema {
+  pub config: Option<String>,
+  pub limit: Option<String>,
+  pub verbosity: Option<u64>,
+  pub passthrough: Option<String>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionTarget)]
+pub struct CfsSessionTargetSchema {
+  /// `dynamic` or `image`
+  pub definition: Option<String>,
+  pub groups: Option<Vec<CfsSessionTargetGroupSchema>>,
+  pub image_map: Option<Vec<CfsSessionImageMapSchema>>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionTargetGroup)]
+pub struct CfsSessionTargetGroupSchema {
+  pub name: String,
+  pub members: Vec<String>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionImageMap)]
+pub struct CfsSessionImageMapSchema {
+  pub source_id: String,
+  pub result_name: String,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionStatus)]
+pub struct CfsSessionStatusSchema {
+  pub artifacts: Option<Vec<CfsSessionArtifactSchema>>,
+  pub session: Option<CfsSessionStateSchema>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionArtifact)]
+pub struct CfsSessionArtifactSchema {
+  pub image_id: Option<String>,
+  pub result_id: Option<String>,
+  pub r#type: Option<String>,
+}
+
+#[derive(ToSchema)]
+#[schema(as = CfsSessionState)]
+pub struct CfsSessionStateSchema {
+  pub job: Option<String>,
+  pub ims_job: Option<String>,
+  pub completion_time: Option<String>,
+  pub start_time: Option<String>,
+  /// `pending`, `running` or `complete`
+  pub status: Option<String>,
+  pub succeeded: Option<String>,
+}